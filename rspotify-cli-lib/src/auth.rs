@@ -0,0 +1,330 @@
+//! Authorization flows that do not require pasting the redirect URL.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rspotify::{prelude::*, Token};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use url::Url;
+
+use crate::{error::Error, init_spotify::SpotifyClient, token_store::TokenStore};
+
+/// Page shown in the browser once the authorization code has been received.
+const SUCCESS_PAGE: &str =
+    "<html><body>Authorization complete, you can close this window.</body></html>";
+
+/// Page shown in the browser when the authorization was denied.
+const FAILURE_PAGE: &str =
+    "<html><body>Authorization failed, see the terminal for details.</body></html>";
+
+/// How long a connection to the redirect listener may take to be answered.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Authorize the client by capturing the redirect on a local listener.
+///
+/// The authorization URL is opened in the browser, or only printed when
+/// `open_browser` is false, for example over SSH with port forwarding.
+pub async fn authorize(spotify: &mut SpotifyClient, open_browser: bool) -> Result<()> {
+    let redirect_uri = Url::parse(spotify.redirect_uri()).context(Error::RedirectURI)?;
+    let listener = listen(&redirect_uri).await?;
+
+    let url = spotify.authorize_url()?;
+
+    if open_browser && webbrowser::open(&url).is_ok() {
+        println!("Opened the authorization URL in your browser:");
+    } else {
+        println!("Open this URL in your browser to authorize rspotify-cli:");
+    }
+    println!("{url}");
+
+    let response = accept_redirect(&listener, &redirect_uri).await?;
+    match spotify {
+        SpotifyClient::AuthCode(spotify) => request_token(spotify, &response).await,
+        SpotifyClient::Pkce(spotify) => request_token(spotify, &response).await,
+    }
+}
+
+/// Exchange the code in the redirect URL for a token, which the client
+/// stores.
+async fn request_token(spotify: &impl OAuthClient, response: &str) -> Result<()> {
+    let code = spotify.parse_response_code(response).context(Error::Auth)?;
+
+    spotify.request_token(&code).await.context(Error::Auth)
+}
+
+/// Authorize the client with a refresh token that was obtained elsewhere.
+pub async fn authorize_with_refresh_token(
+    spotify: &SpotifyClient,
+    refresh_token: String,
+) -> Result<()> {
+    match spotify {
+        SpotifyClient::AuthCode(spotify) => refresh(spotify, refresh_token).await,
+        SpotifyClient::Pkce(spotify) => refresh(spotify, refresh_token).await,
+    }
+}
+
+/// Replace the client's token with one that only has `refresh_token`, then
+/// refresh it.
+async fn refresh(spotify: &impl OAuthClient, refresh_token: String) -> Result<()> {
+    *spotify
+        .get_token()
+        .lock()
+        .await
+        .ok()
+        .context(Error::TokenLock)? = Some(Token {
+        refresh_token: Some(refresh_token),
+        ..Default::default()
+    });
+
+    spotify.refresh_token().await.context(Error::Auth)
+}
+
+/// Details about the stored token and the account it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenStatus {
+    /// Spotify user id of the account.
+    pub user_id: String,
+
+    /// Display name of the account, if it has one.
+    pub display_name: Option<String>,
+
+    /// Scopes granted to the token, sorted.
+    pub scopes: Vec<String>,
+
+    /// When the access token expires.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Inspect the stored token and fetch the account it belongs to.
+///
+/// An expired access token is refreshed first.
+pub async fn token_status(spotify: &SpotifyClient, store: &TokenStore) -> Result<TokenStatus> {
+    if !spotify.load_token(store).await? {
+        anyhow::bail!(Error::NotLoggedIn);
+    }
+
+    match spotify {
+        SpotifyClient::AuthCode(spotify) => status(spotify).await,
+        SpotifyClient::Pkce(spotify) => status(spotify).await,
+    }
+}
+
+/// Fetch the current user with the loaded token.
+async fn status(spotify: &impl OAuthClient) -> Result<TokenStatus> {
+    let user = spotify.current_user().await.context(Error::Auth)?;
+
+    let token = spotify.get_token();
+    let token = token.lock().await.ok().context(Error::TokenLock)?;
+    let token = token.as_ref().context(Error::NotLoggedIn)?;
+    let mut scopes: Vec<_> = token.scopes.iter().cloned().collect();
+    scopes.sort();
+
+    Ok(TokenStatus {
+        user_id: user.id.id().to_owned(),
+        display_name: user.display_name,
+        scopes,
+        expires_at: token.expires_at,
+    })
+}
+
+/// Refresh the stored token even if it has not expired yet, and return when
+/// the new access token expires.
+pub async fn force_refresh(
+    spotify: &SpotifyClient,
+    store: &TokenStore,
+) -> Result<Option<DateTime<Utc>>> {
+    let token = store.load()?.context(Error::NotLoggedIn)?;
+    match spotify {
+        SpotifyClient::AuthCode(spotify) => refresh_stored(spotify, token).await,
+        SpotifyClient::Pkce(spotify) => refresh_stored(spotify, token).await,
+    }
+}
+
+/// Refresh a stored token.
+async fn refresh_stored(spotify: &impl OAuthClient, token: Token) -> Result<Option<DateTime<Utc>>> {
+    *spotify
+        .get_token()
+        .lock()
+        .await
+        .ok()
+        .context(Error::TokenLock)? = Some(token);
+    spotify.refresh_token().await.context(Error::Auth)?;
+
+    let token = spotify.get_token();
+    let expires_at = token
+        .lock()
+        .await
+        .ok()
+        .context(Error::TokenLock)?
+        .as_ref()
+        .and_then(|token| token.expires_at);
+    Ok(expires_at)
+}
+
+/// Listen on the host and port of the redirect URI.
+pub async fn listen(redirect_uri: &Url) -> Result<TcpListener> {
+    let host = redirect_uri.host_str().context(Error::RedirectURI)?;
+    let port = redirect_uri
+        .port_or_known_default()
+        .context(Error::RedirectURI)?;
+
+    TcpListener::bind((host, port))
+        .await
+        .context(Error::RedirectURI)
+}
+
+/// Wait for the browser to be redirected to the redirect URI, and return the
+/// full URL it was redirected to.
+///
+/// Requests for any other path are answered with a 404 and ignored, as are
+/// connections that fail or send nothing for [`CONNECTION_TIMEOUT`].
+pub async fn accept_redirect(listener: &TcpListener, redirect_uri: &Url) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let answer = tokio::time::timeout(CONNECTION_TIMEOUT, answer(&mut stream, redirect_uri));
+        if let Ok(Ok(Some(redirect))) = answer.await {
+            return redirect;
+        }
+    }
+}
+
+/// Answer a connection to the redirect listener.
+///
+/// Returns the URL it was redirected to, or the error the authorization
+/// failed with, and `None` for a request for any other path.
+async fn answer(
+    stream: &mut TcpStream,
+    redirect_uri: &Url,
+) -> std::io::Result<Option<Result<String>>> {
+    let request = read_request_head(stream).await?;
+
+    // request line looks like `GET /callback?code=...&state=... HTTP/1.1`
+    let Some(target) = request.lines().next().and_then(|l| l.split(' ').nth(1)) else {
+        return Ok(None);
+    };
+    let Ok(url) = redirect_uri.join(target) else {
+        return Ok(None);
+    };
+
+    if url.path() != redirect_uri.path() {
+        respond(stream, "404 Not Found", "").await?;
+        return Ok(None);
+    }
+
+    if let Some((_, error)) = url.query_pairs().find(|(key, _)| key == "error") {
+        respond(stream, "400 Bad Request", FAILURE_PAGE).await?;
+        return Ok(Some(Err(
+            Error::AuthorizationDenied(error.into_owned()).into()
+        )));
+    }
+
+    respond(stream, "200 OK", SUCCESS_PAGE).await?;
+    Ok(Some(Ok(url.into())))
+}
+
+/// Read the request line and headers, which may arrive in several packets.
+async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < 16384 {
+        let len = stream.read(&mut buf).await?;
+        if len == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..len]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+/// Write a minimal HTTP response.
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: \
+         close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream
+            .write_all(format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn listener() -> (TcpListener, Url) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let url = Url::parse(&format!("http://127.0.0.1:{port}/callback")).unwrap();
+        (listener, url)
+    }
+
+    #[tokio::test]
+    async fn captures_redirect() {
+        let (listener, redirect_uri) = listener().await;
+        let port = redirect_uri.port().unwrap();
+
+        let client = tokio::spawn(async move {
+            assert!(get(port, "/favicon.ico").await.starts_with("HTTP/1.1 404"));
+            assert!(get(port, "/callback?code=abc&state=xyz")
+                .await
+                .starts_with("HTTP/1.1 200"));
+        });
+
+        let url = accept_redirect(&listener, &redirect_uri).await.unwrap();
+        client.await.unwrap();
+        assert_eq!(
+            url,
+            format!("http://127.0.0.1:{port}/callback?code=abc&state=xyz")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn skips_broken_connections() {
+        let (listener, redirect_uri) = listener().await;
+        let port = redirect_uri.port().unwrap();
+
+        let client = tokio::spawn(async move {
+            // reset before sending a whole request
+            let mut reset = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            reset.write_all(b"GET /call").await.unwrap();
+            reset.set_linger(Some(Duration::ZERO)).unwrap();
+            drop(reset);
+
+            let _silent = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            get(port, "/callback?code=abc&state=xyz").await
+        });
+
+        let url = accept_redirect(&listener, &redirect_uri).await.unwrap();
+        assert!(client.await.unwrap().starts_with("HTTP/1.1 200"));
+        assert!(url.ends_with("/callback?code=abc&state=xyz"));
+    }
+
+    #[tokio::test]
+    async fn denied() {
+        let (listener, redirect_uri) = listener().await;
+        let port = redirect_uri.port().unwrap();
+
+        let client = tokio::spawn(async move {
+            get(port, "/callback?error=access_denied&state=xyz").await;
+        });
+
+        let err = accept_redirect(&listener, &redirect_uri).await.unwrap_err();
+        client.await.unwrap();
+        assert!(err.to_string().contains("access_denied"));
+    }
+}
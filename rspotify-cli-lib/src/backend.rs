@@ -0,0 +1,236 @@
+//! Abstraction over the Spotify Web API calls made by the library.
+
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Duration;
+use rspotify::{
+    model::{
+        CurrentPlaybackContext, CurrentUserQueue, Device, Offset, PlayContextId, PlayableId,
+        RepeatState, SearchResult, SearchType, TrackId,
+    },
+    prelude::*,
+};
+
+use crate::error::Error;
+
+/// Every Web API call that [`CurrentlyPlaying`] makes.
+///
+/// Every rspotify client with user authorization is a real implementation,
+/// while [`FakeBackend`] keeps everything in memory so that the library can be
+/// exercised without a network connection.
+///
+/// [`CurrentlyPlaying`]: crate::currently_playing::CurrentlyPlaying
+/// [`FakeBackend`]: crate::fake_backend::FakeBackend
+#[async_trait]
+pub trait PlayerBackend: Send + Sync {
+    /// Get the scopes granted to the token, or `None` if they are unknown.
+    async fn granted_scopes(&self) -> Result<Option<HashSet<String>>>;
+
+    /// Get the current playback state, if there is any.
+    async fn current_playback(&self) -> Result<Option<CurrentPlaybackContext>>;
+
+    /// Get the devices the user can play on.
+    async fn device(&self) -> Result<Vec<Device>>;
+
+    /// Move playback to a device, and start playing if `play` is set.
+    async fn transfer_playback(&self, device_id: &str, play: Option<bool>) -> Result<()>;
+
+    /// Get the currently playing item and the items queued after it.
+    async fn current_user_queue(&self) -> Result<CurrentUserQueue>;
+
+    /// Add a track or episode to the end of the queue.
+    async fn add_item_to_queue(
+        &self,
+        item: PlayableId<'static>,
+        device_id: Option<&str>,
+    ) -> Result<()>;
+
+    /// Check whether the given tracks are in the user's library.
+    async fn current_user_saved_tracks_contains(
+        &self,
+        ids: Vec<TrackId<'static>>,
+    ) -> Result<Vec<bool>>;
+
+    /// Add the given tracks to the user's library.
+    async fn current_user_saved_tracks_add(&self, ids: Vec<TrackId<'static>>) -> Result<()>;
+
+    /// Remove the given tracks from the user's library.
+    async fn current_user_saved_tracks_delete(&self, ids: Vec<TrackId<'static>>) -> Result<()>;
+
+    /// Resume playback, optionally from a position.
+    async fn resume_playback(
+        &self,
+        device_id: Option<&str>,
+        position: Option<Duration>,
+    ) -> Result<()>;
+
+    /// Pause playback.
+    async fn pause_playback(&self, device_id: Option<&str>) -> Result<()>;
+
+    /// Skip to the next item.
+    async fn next_track(&self, device_id: Option<&str>) -> Result<()>;
+
+    /// Skip to the previous item.
+    async fn previous_track(&self, device_id: Option<&str>) -> Result<()>;
+
+    /// Set the repeat state.
+    async fn repeat(&self, state: RepeatState, device_id: Option<&str>) -> Result<()>;
+
+    /// Set the volume in percent.
+    async fn volume(&self, volume_percent: u8, device_id: Option<&str>) -> Result<()>;
+
+    /// Set the shuffle state.
+    async fn shuffle(&self, state: bool, device_id: Option<&str>) -> Result<()>;
+
+    /// Seek to a position in the current item.
+    async fn seek_track(&self, position: Duration, device_id: Option<&str>) -> Result<()>;
+
+    /// Start playing a list of tracks or episodes.
+    async fn start_uris_playback(
+        &self,
+        uris: Vec<PlayableId<'static>>,
+        device_id: Option<&str>,
+        offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()>;
+
+    /// Start playing an album, artist, playlist or show.
+    async fn start_context_playback(
+        &self,
+        context_uri: PlayContextId<'static>,
+        device_id: Option<&str>,
+        offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()>;
+
+    /// Search the catalog.
+    async fn search(
+        &self,
+        query: &str,
+        kind: SearchType,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<SearchResult>;
+}
+
+#[async_trait]
+impl<C: OAuthClient + Send + Sync> PlayerBackend for C {
+    async fn granted_scopes(&self) -> Result<Option<HashSet<String>>> {
+        let token = self.get_token();
+        let token = token.lock().await.ok().context(Error::TokenLock)?;
+        Ok(token
+            .as_ref()
+            .map(|token| token.scopes.clone())
+            .filter(|scopes| !scopes.is_empty()))
+    }
+
+    async fn current_playback(&self) -> Result<Option<CurrentPlaybackContext>> {
+        Ok(OAuthClient::current_playback(self, None, None::<Vec<_>>).await?)
+    }
+
+    async fn device(&self) -> Result<Vec<Device>> {
+        Ok(OAuthClient::device(self).await?)
+    }
+
+    async fn transfer_playback(&self, device_id: &str, play: Option<bool>) -> Result<()> {
+        Ok(OAuthClient::transfer_playback(self, device_id, play).await?)
+    }
+
+    async fn current_user_queue(&self) -> Result<CurrentUserQueue> {
+        Ok(OAuthClient::current_user_queue(self).await?)
+    }
+
+    async fn add_item_to_queue(
+        &self,
+        item: PlayableId<'static>,
+        device_id: Option<&str>,
+    ) -> Result<()> {
+        Ok(OAuthClient::add_item_to_queue(self, item, device_id).await?)
+    }
+
+    async fn current_user_saved_tracks_contains(
+        &self,
+        ids: Vec<TrackId<'static>>,
+    ) -> Result<Vec<bool>> {
+        Ok(OAuthClient::current_user_saved_tracks_contains(self, ids).await?)
+    }
+
+    async fn current_user_saved_tracks_add(&self, ids: Vec<TrackId<'static>>) -> Result<()> {
+        Ok(OAuthClient::current_user_saved_tracks_add(self, ids).await?)
+    }
+
+    async fn current_user_saved_tracks_delete(&self, ids: Vec<TrackId<'static>>) -> Result<()> {
+        Ok(OAuthClient::current_user_saved_tracks_delete(self, ids).await?)
+    }
+
+    async fn resume_playback(
+        &self,
+        device_id: Option<&str>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        Ok(OAuthClient::resume_playback(self, device_id, position).await?)
+    }
+
+    async fn pause_playback(&self, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::pause_playback(self, device_id).await?)
+    }
+
+    async fn next_track(&self, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::next_track(self, device_id).await?)
+    }
+
+    async fn previous_track(&self, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::previous_track(self, device_id).await?)
+    }
+
+    async fn repeat(&self, state: RepeatState, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::repeat(self, state, device_id).await?)
+    }
+
+    async fn volume(&self, volume_percent: u8, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::volume(self, volume_percent, device_id).await?)
+    }
+
+    async fn shuffle(&self, state: bool, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::shuffle(self, state, device_id).await?)
+    }
+
+    async fn seek_track(&self, position: Duration, device_id: Option<&str>) -> Result<()> {
+        Ok(OAuthClient::seek_track(self, position, device_id).await?)
+    }
+
+    async fn start_uris_playback(
+        &self,
+        uris: Vec<PlayableId<'static>>,
+        device_id: Option<&str>,
+        offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        Ok(OAuthClient::start_uris_playback(self, uris, device_id, offset, position).await?)
+    }
+
+    async fn start_context_playback(
+        &self,
+        context_uri: PlayContextId<'static>,
+        device_id: Option<&str>,
+        offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        Ok(
+            OAuthClient::start_context_playback(self, context_uri, device_id, offset, position)
+                .await?,
+        )
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        kind: SearchType,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<SearchResult> {
+        Ok(BaseClient::search(self, query, kind, None, None, limit, offset).await?)
+    }
+}
@@ -1,24 +1,40 @@
 //! Currently playing struct that handles all connections to the Spotify API.
 
+use std::{collections::HashSet, sync::Arc};
+
 use anyhow::{Context, Result};
 use chrono::Duration;
 pub use rspotify::model::enums::types::SearchType;
 use rspotify::{
     model::{
-        parse_uri, CurrentlyPlayingType, PlayableItem, PlaylistId, SearchResult, TrackId, Type,
+        parse_uri, AlbumId, ArtistId, CurrentUserQueue, CurrentlyPlayingType, Device, EpisodeId,
+        FullTrack, Offset, PlayableItem, PlaylistId, SearchResult, ShowId, SimplifiedArtist,
+        TrackId, Type,
     },
     prelude::*,
-    AuthCodeSpotify,
 };
 use serde_json::json;
 
-use crate::{error::Error, repeat_state::RepeatState, url_convert::url_to_uri};
+use crate::{
+    backend::PlayerBackend,
+    device_match::find_device,
+    error::Error,
+    repeat_state::RepeatState,
+    scopes::{self, LIBRARY_MODIFY, LIBRARY_READ, MODIFY_PLAYBACK_STATE, READ_PLAYBACK_STATE},
+    url_convert::{normalize_uri, url_to_uri},
+};
 
 /// Stores current playing state
 #[allow(missing_debug_implementations)]
 pub struct CurrentlyPlaying {
     /// Connector that fetches all the data.
-    spotify: AuthCodeSpotify,
+    spotify: Arc<dyn PlayerBackend>,
+
+    /// Scopes granted to the token, `None` when they are unknown.
+    granted_scopes: Option<HashSet<String>>,
+
+    /// Device that player commands are sent to, `None` for the active one.
+    device_id: Option<String>,
 
     /// Track id. Optional because it can be a local file.
     pub id: Option<PlayableId<'static>>,
@@ -54,6 +70,28 @@ pub struct CurrentlyPlaying {
     pub playing_type: Option<CurrentlyPlayingType>,
 }
 
+// ids only implement `Clone` for borrowed lifetimes
+impl Clone for CurrentlyPlaying {
+    fn clone(&self) -> Self {
+        Self {
+            spotify: Arc::clone(&self.spotify),
+            granted_scopes: self.granted_scopes.clone(),
+            device_id: self.device_id.clone(),
+            id: self.id.as_ref().map(PlayableId::clone_static),
+            title: self.title.clone(),
+            artist: self.artist.clone(),
+            progress: self.progress,
+            duration: self.duration,
+            volume: self.volume,
+            is_playing: self.is_playing,
+            repeat_state: self.repeat_state,
+            is_shuffled: self.is_shuffled,
+            device: self.device.clone(),
+            playing_type: self.playing_type,
+        }
+    }
+}
+
 impl CurrentlyPlaying {
     /// Attempt to create a new instance of `CurrentlyPlaying`.
     ///
@@ -62,12 +100,27 @@ impl CurrentlyPlaying {
     /// Returns an error if the current playback is not available. This is
     /// likely because there is no active device. Within a few seconds of
     /// pausing, the active device becomes inactive and unknown to the API.
-    pub async fn new(spotify: AuthCodeSpotify) -> Result<Self> {
-        if let Some(curr) = spotify.current_playback(None, None::<Vec<_>>).await? {
+    pub async fn new(spotify: impl PlayerBackend + 'static) -> Result<Self> {
+        Self::from_backend(Arc::new(spotify)).await
+    }
+
+    /// Attempt to create a new instance of `CurrentlyPlaying` from a shared
+    /// backend.
+    ///
+    /// # Errors
+    ///
+    /// See [`CurrentlyPlaying::new`].
+    pub async fn from_backend(spotify: Arc<dyn PlayerBackend>) -> Result<Self> {
+        let granted_scopes = spotify.granted_scopes().await?;
+        scopes::require(granted_scopes.as_ref(), READ_PLAYBACK_STATE)?;
+
+        if let Some(curr) = spotify.current_playback().await? {
             match curr.item.clone().context(Error::NoActiveDevice)? {
                 // TODO: might not work when playing local media
                 PlayableItem::Track(t) => Ok(Self {
                     spotify,
+                    granted_scopes,
+                    device_id: None,
                     id: t.id.map(PlayableId::Track),
                     title: Some(t.name),
                     artist: t.artists.first().cloned().map(|a| a.name),
@@ -82,6 +135,8 @@ impl CurrentlyPlaying {
                 }),
                 PlayableItem::Episode(t) => Ok(Self {
                     spotify,
+                    granted_scopes,
+                    device_id: None,
                     id: Some(PlayableId::Episode(t.id)),
                     title: Some(t.name),
                     artist: Some(t.show.name),
@@ -98,6 +153,8 @@ impl CurrentlyPlaying {
         } else {
             Ok(Self {
                 spotify,
+                granted_scopes,
+                device_id: None,
                 id: None,
                 title: None,
                 artist: None,
@@ -113,6 +170,11 @@ impl CurrentlyPlaying {
         }
     }
 
+    /// Check that the token was granted `scope`.
+    fn require(&self, scope: &str) -> Result<()> {
+        scopes::require(self.granted_scopes.as_ref(), scope)
+    }
+
     /// Returns the URL of the current track.
     pub fn generate_url(&self) -> Result<String> {
         self.id
@@ -141,11 +203,13 @@ impl CurrentlyPlaying {
 
     /// Whether the current song is liked or not.
     pub async fn is_liked(&self) -> Result<bool> {
+        self.require(LIBRARY_READ)?;
+
         if let Some(id) = &self.id {
             if let PlayableId::Track(id) = id {
                 Ok(*self
                     .spotify
-                    .current_user_saved_tracks_contains([id.clone_static()])
+                    .current_user_saved_tracks_contains(vec![id.clone_static()])
                     .await?
                     .first()
                     .context(Error::Control("fetch like status".to_owned()))?)
@@ -201,16 +265,20 @@ impl CurrentlyPlaying {
 
     /// Play the track.
     pub async fn play(&self) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         self.spotify
-            .resume_playback(None, None)
+            .resume_playback(self.device_id.as_deref(), None)
             .await
             .context(Error::Control("play song".to_owned()))
     }
 
     /// Pause the track.
     pub async fn pause(&self) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         self.spotify
-            .pause_playback(None)
+            .pause_playback(self.device_id.as_deref())
             .await
             .context(Error::Control("pause song".to_owned()))
     }
@@ -226,10 +294,12 @@ impl CurrentlyPlaying {
 
     /// Like the track.
     pub async fn like(&self) -> Result<()> {
+        self.require(LIBRARY_MODIFY)?;
+
         if let Some(id) = &self.id {
             if let PlayableId::Track(id) = id {
                 self.spotify
-                    .current_user_saved_tracks_add([id.clone_static()])
+                    .current_user_saved_tracks_add(vec![id.clone_static()])
                     .await
                     .context(Error::Control("like song".to_owned()))
             } else {
@@ -242,10 +312,12 @@ impl CurrentlyPlaying {
 
     /// Remove like from the song.
     pub async fn unlike(&self) -> Result<()> {
+        self.require(LIBRARY_MODIFY)?;
+
         if let Some(id) = &self.id {
             if let PlayableId::Track(id) = id {
                 self.spotify
-                    .current_user_saved_tracks_delete([id.clone_static()])
+                    .current_user_saved_tracks_delete(vec![id.clone_static()])
                     .await
                     .context(Error::Control("unlike song".to_owned()))
             } else {
@@ -267,33 +339,41 @@ impl CurrentlyPlaying {
 
     /// Go to the previous track.
     pub async fn previous(&self) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         self.spotify
-            .previous_track(None)
+            .previous_track(self.device_id.as_deref())
             .await
             .context(Error::Control("go to previous song".to_owned()))
     }
 
     /// Go to the next track.
     pub async fn next(&self) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         self.spotify
-            .next_track(None)
+            .next_track(self.device_id.as_deref())
             .await
             .context(Error::Control("go to next song".to_owned()))
     }
 
     /// Set the repeat state.
     pub async fn repeat(&self, repeat_state: RepeatState) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         self.spotify
-            .repeat(repeat_state.into(), None)
+            .repeat(repeat_state.into(), self.device_id.as_deref())
             .await
             .context(Error::Control("set repeat state".to_owned()))
     }
 
     /// Cycle between the three repeat states.
     pub async fn cycle_repeat(&self) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         if let Some(repeat_state) = self.repeat_state {
             self.spotify
-                .repeat(repeat_state.cycle().into(), None)
+                .repeat(repeat_state.cycle().into(), self.device_id.as_deref())
                 .await
                 .context(Error::Control("cycle repeat state".to_owned()))
         } else {
@@ -303,9 +383,11 @@ impl CurrentlyPlaying {
 
     /// Set the volume.
     pub async fn set_volume(&self, volume: u8) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         if self.volume.is_some() {
             self.spotify
-                .volume(volume.clamp(0, 100), None)
+                .volume(volume.clamp(0, 100), self.device_id.as_deref())
                 .await
                 .context(Error::Control("set volume".to_owned()))
         } else {
@@ -315,9 +397,14 @@ impl CurrentlyPlaying {
 
     /// Increase the volume by a certain amount.
     pub async fn volume_up(&self, incr: u8) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         if let Some(volume) = self.volume {
             self.spotify
-                .volume((volume + incr).clamp(0, 100), None)
+                .volume(
+                    volume.saturating_add(incr).clamp(0, 100),
+                    self.device_id.as_deref(),
+                )
                 .await
                 .context(Error::Control("volume up".to_owned()))
         } else {
@@ -327,9 +414,14 @@ impl CurrentlyPlaying {
 
     /// Decrease the volume by a certain amount.
     pub async fn volume_down(&self, incr: u8) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         if let Some(volume) = self.volume {
             self.spotify
-                .volume((volume - incr).clamp(0, 100), None)
+                .volume(
+                    volume.saturating_sub(incr).clamp(0, 100),
+                    self.device_id.as_deref(),
+                )
                 .await
                 .context(Error::Control("volume down".to_owned()))
         } else {
@@ -339,8 +431,10 @@ impl CurrentlyPlaying {
 
     /// Set the shuffle state.
     pub async fn shuffle(&self, state: bool) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
         self.spotify
-            .shuffle(state, None)
+            .shuffle(state, self.device_id.as_deref())
             .await
             .context(Error::Control("set shuffle state".to_owned()))
     }
@@ -354,45 +448,97 @@ impl CurrentlyPlaying {
         }
     }
 
-    /// Seek to a position in the track.
-    pub async fn seek(&self, position: u8) -> Result<()> {
+    /// Seek to a position in the item, clamped to its duration.
+    pub async fn seek_to(&self, position: Duration) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
+        let position = position.max(Duration::zero());
+        let position = self
+            .duration
+            .map_or(position, |duration| position.min(duration));
         self.spotify
-            .seek_track(Duration::seconds(position.into()), None)
+            .seek_track(position, self.device_id.as_deref())
             .await
             .context(Error::Control("seek position".to_owned()))
     }
 
+    /// Seek by an offset from the progress, clamped to the item.
+    pub async fn seek_relative(&self, offset: Duration) -> Result<()> {
+        let progress = self.progress.context(Error::NoActiveDevice)?;
+        let position = progress
+            .checked_add(&offset)
+            .context(Error::Control("seek position".to_owned()))?;
+        self.seek_to(position).await
+    }
+
     /// Play the current track again.
     pub async fn replay(&self) -> Result<()> {
-        self.seek(0).await
+        self.seek_to(Duration::zero()).await
     }
 
-    /// Play a track given a URI.
+    /// Play a track, an episode, or an album, artist, playlist or show given a
+    /// URI.
     pub async fn play_from_uri(&self, uri: String) -> Result<()> {
-        // check if is valid id
-        let (type_, _) = parse_uri(uri.as_str())?;
+        self.play_from_uri_at(&uri, None, None).await
+    }
 
-        if type_ == Type::Track || type_ == Type::Episode {
-            self.spotify
-                .start_uris_playback(
-                    [PlayableId::from(TrackId::from_uri(&uri)?)],
-                    None,
-                    None,
-                    None,
-                )
-                .await
-                .context(Error::Control("play from uri".to_owned()))
-        } else {
-            self.spotify
-                .start_context_playback(
-                    PlayContextId::from(PlaylistId::from_uri(&uri)?),
-                    None,
-                    None,
-                    None,
-                )
+    /// Play the item or context of a URI, starting at the item `offset` of an
+    /// album or playlist, and `position` into that item.
+    pub async fn play_from_uri_at(
+        &self,
+        uri: &str,
+        offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
+        let uri = normalize_uri(uri);
+        if let Some(item) = playable_id(&uri)? {
+            if offset.is_some() {
+                anyhow::bail!(Error::NoOffset(uri));
+            }
+            return self
+                .spotify
+                .start_uris_playback(vec![item], self.device_id.as_deref(), None, position)
                 .await
-                .context(Error::Control("play from uri".to_owned()))
+                .context(Error::Control("play from uri".to_owned()));
+        }
+
+        let context = match parse_uri(&uri)? {
+            (Type::Album, _) => PlayContextId::from(AlbumId::from_uri(&uri)?.into_static()),
+            (Type::Playlist, _) => PlayContextId::from(PlaylistId::from_uri(&uri)?.into_static()),
+            (Type::Artist, _) => PlayContextId::from(ArtistId::from_uri(&uri)?.into_static()),
+            (Type::Show, _) => PlayContextId::from(ShowId::from_uri(&uri)?.into_static()),
+            _ => anyhow::bail!(Error::NotPlayable(uri)),
+        };
+        if offset.is_some()
+            && !matches!(
+                context,
+                PlayContextId::Album(_) | PlayContextId::Playlist(_)
+            )
+        {
+            anyhow::bail!(Error::NoOffset(uri));
         }
+        self.spotify
+            .start_context_playback(context, self.device_id.as_deref(), offset, position)
+            .await
+            .context(Error::Control("play from uri".to_owned()))
+    }
+
+    /// Play tracks and episodes as one list, starting at the item `offset` of
+    /// the list, and `position` into that item.
+    pub async fn play_items(
+        &self,
+        items: Vec<PlayableId<'static>>,
+        offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
+        self.spotify
+            .start_uris_playback(items, self.device_id.as_deref(), offset, position)
+            .await
+            .context(Error::Control("play items".to_owned()))
     }
 
     /// Play a track given a URL.
@@ -401,6 +547,96 @@ impl CurrentlyPlaying {
         self.play_from_uri(uri).await
     }
 
+    /// Returns the devices that can be played on, including inactive ones.
+    pub async fn devices(&self) -> Result<Vec<Device>> {
+        self.require(READ_PLAYBACK_STATE)?;
+
+        self.spotify
+            .device()
+            .await
+            .context(Error::Control("list devices".to_owned()))
+    }
+
+    /// Returns the id of the device whose id or name matches `query`, see
+    /// [`find_device`].
+    pub async fn resolve_device(&self, query: &str) -> Result<String> {
+        let devices = self.devices().await?;
+        let device = find_device(&devices, query)?;
+        Ok(device.id.clone().unwrap_or_default())
+    }
+
+    /// Send player commands to the device whose id or name matches `query`
+    /// instead of the active one, see [`find_device`].
+    ///
+    /// A device other than the active one plays nothing, so actions that
+    /// depend on the playback, like toggling shuffle or seeking by an offset,
+    /// fail on it, and toggling play starts playing there.
+    pub async fn on_device(mut self, query: &str) -> Result<Self> {
+        let devices = self.devices().await?;
+        let device = find_device(&devices, query)?;
+        self.device_id = device.id.clone();
+        self.volume = device.volume_percent.and_then(|v| u8::try_from(v).ok());
+        if !device.is_active {
+            self.is_playing = Some(false);
+            self.progress = None;
+            self.duration = None;
+            self.repeat_state = None;
+            self.is_shuffled = None;
+        }
+        Ok(self)
+    }
+
+    /// Move playback to a device, and start playing there if `play` is set.
+    pub async fn transfer(&self, device_id: &str, play: bool) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
+        self.spotify
+            .transfer_playback(device_id, play.then_some(true))
+            .await
+            .context(Error::Control("transfer playback".to_owned()))
+    }
+
+    /// Returns the current item and the items queued after it.
+    pub async fn queue(&self) -> Result<CurrentUserQueue> {
+        self.require(READ_PLAYBACK_STATE)?;
+
+        self.spotify
+            .current_user_queue()
+            .await
+            .context(Error::Control("fetch queue".to_owned()))
+    }
+
+    /// Add a track or episode to the end of the queue given its URI.
+    pub async fn add_to_queue(&self, uri: &str) -> Result<()> {
+        self.require(MODIFY_PLAYBACK_STATE)?;
+
+        let item = playable_id(uri)?.ok_or_else(|| Error::NotQueueable(uri.to_owned()))?;
+        self.spotify
+            .add_item_to_queue(item, self.device_id.as_deref())
+            .await
+            .context(Error::Control("add to queue".to_owned()))
+    }
+
+    /// Returns the URI of a URI, a URL, or the top track found by searching
+    /// for anything else.
+    pub async fn resolve_uri(&self, what: &str) -> Result<String> {
+        let what = what.trim();
+        if what.starts_with("spotify:") {
+            parse_uri(what)?;
+            return Ok(what.to_owned());
+        }
+        if what.starts_with("http://") || what.starts_with("https://") {
+            return url_to_uri(what);
+        }
+
+        let tracks = self.search_tracks(what, 1).await?;
+        tracks
+            .first()
+            .and_then(|track| track.id.as_ref())
+            .map(Id::uri)
+            .context(Error::NoSearchResults(what.to_owned()))
+    }
+
     /// Search for a song.
     pub async fn search(
         &self,
@@ -409,12 +645,7 @@ impl CurrentlyPlaying {
         limit: u32,
         offset: u32,
     ) -> Result<String> {
-        match self
-            .spotify
-            .search(&what, kind, None, None, Some(limit), Some(offset))
-            .await
-            .context(Error::Control("search".to_owned()))?
-        {
+        match self.search_result(&what, kind, limit, Some(offset)).await? {
             SearchResult::Artists(page) => Ok(serde_json::to_string(&page.items)?),
             SearchResult::Shows(page) => Ok(serde_json::to_string(&page.items)?),
             SearchResult::Albums(page) => Ok(serde_json::to_string(&page.items)?),
@@ -423,4 +654,481 @@ impl CurrentlyPlaying {
             SearchResult::Playlists(page) => Ok(serde_json::to_string(&page.items)?),
         }
     }
+
+    /// Returns the playable results of a search, best match first.
+    pub async fn search_hits(
+        &self,
+        what: &str,
+        kind: SearchType,
+        limit: u32,
+    ) -> Result<Vec<SearchHit>> {
+        let first_artist = |artists: &[SimplifiedArtist]| artists.first().map(|a| a.name.clone());
+        Ok(match self.search_result(what, kind, limit, None).await? {
+            SearchResult::Artists(page) => page
+                .items
+                .into_iter()
+                .map(|artist| SearchHit::new(artist.id.uri(), artist.name, None))
+                .collect(),
+            SearchResult::Albums(page) => page
+                .items
+                .into_iter()
+                .filter_map(|album| {
+                    let by = first_artist(&album.artists);
+                    Some(SearchHit::new(album.id?.uri(), album.name, by))
+                })
+                .collect(),
+            SearchResult::Tracks(page) => page
+                .items
+                .into_iter()
+                .filter_map(|track| {
+                    let by = first_artist(&track.artists);
+                    Some(SearchHit::new(track.id?.uri(), track.name, by))
+                })
+                .collect(),
+            SearchResult::Playlists(page) => page
+                .items
+                .into_iter()
+                .map(|playlist| {
+                    let by = playlist.owner.display_name;
+                    SearchHit::new(playlist.id.uri(), playlist.name, by)
+                })
+                .collect(),
+            SearchResult::Shows(page) => page
+                .items
+                .into_iter()
+                .map(|show| SearchHit::new(show.id.uri(), show.name, Some(show.publisher)))
+                .collect(),
+            SearchResult::Episodes(page) => page
+                .items
+                .into_iter()
+                .map(|episode| SearchHit::new(episode.id.uri(), episode.name, None))
+                .collect(),
+        })
+    }
+
+    /// Search the catalog for one kind of item.
+    async fn search_result(
+        &self,
+        what: &str,
+        kind: SearchType,
+        limit: u32,
+        offset: Option<u32>,
+    ) -> Result<SearchResult> {
+        self.spotify
+            .search(what, kind, Some(limit), offset)
+            .await
+            .context(Error::Control("search".to_owned()))
+    }
+
+    /// Search for tracks, keeping the results as they are.
+    pub async fn search_tracks(&self, what: &str, limit: u32) -> Result<Vec<FullTrack>> {
+        match self
+            .search_result(what, SearchType::Track, limit, None)
+            .await?
+        {
+            SearchResult::Tracks(page) => Ok(page.items),
+            _ => Ok(Vec::new()),
+        }
+    }
+}
+
+/// A search result that can be played.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// URI of the item.
+    pub uri: String,
+
+    /// Name of the item, followed by its artist, owner or publisher.
+    pub description: String,
+}
+
+impl SearchHit {
+    /// Describe an item by its name and who it is by, if anyone.
+    fn new(uri: String, name: String, by: Option<String>) -> Self {
+        let description = match by {
+            Some(by) => format!("{name} - {by}"),
+            None => name,
+        };
+        Self { uri, description }
+    }
+}
+
+/// Returns the id of a track or episode URI, or `None` for other types.
+fn playable_id(uri: &str) -> Result<Option<PlayableId<'static>>> {
+    Ok(match parse_uri(uri)? {
+        (Type::Track, _) => Some(PlayableId::from(TrackId::from_uri(uri)?.into_static())),
+        (Type::Episode, _) => Some(PlayableId::from(EpisodeId::from_uri(uri)?.into_static())),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use rspotify::model::Page;
+
+    use super::*;
+    use crate::fake_backend::{device, track, Call, FakeBackend};
+
+    fn backend() -> Arc<FakeBackend> {
+        let id = TrackId::from_id("4cOdK2wGLETKBW3PvgPWqT").unwrap();
+        Arc::new(FakeBackend::with_track(track(
+            id,
+            "Title",
+            "Artist",
+            Duration::seconds(200),
+        )))
+    }
+
+    #[tokio::test]
+    async fn metadata() {
+        let curr = CurrentlyPlaying::from_backend(backend()).await.unwrap();
+        assert_eq!(curr.title.as_deref(), Some("Title"));
+        assert_eq!(curr.artist.as_deref(), Some("Artist"));
+        assert_eq!(curr.duration, Some(Duration::seconds(200)));
+        assert_eq!(curr.volume, Some(50));
+        assert_eq!(curr.is_playing, Some(false));
+        assert_eq!(
+            curr.generate_url().unwrap(),
+            "https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT"
+        );
+    }
+
+    #[tokio::test]
+    async fn no_active_device() {
+        let curr = CurrentlyPlaying::new(FakeBackend::new()).await.unwrap();
+        assert!(curr.display().await.is_err());
+        assert!(curr.toggle_shuffle().await.is_err());
+        assert!(curr.play().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn toggle_like_unlike() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        assert!(!curr.is_liked().await.unwrap());
+        curr.toggle_like_unlike().await.unwrap();
+        assert!(curr.is_liked().await.unwrap());
+        assert!(curr.display().await.unwrap().ends_with('\u{2665}'));
+        curr.toggle_like_unlike().await.unwrap();
+        assert!(!curr.is_liked().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn missing_scope() {
+        let backend = backend();
+        backend.state().granted_scopes = Some(HashSet::from([
+            READ_PLAYBACK_STATE.to_owned(),
+            LIBRARY_READ.to_owned(),
+        ]));
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        assert!(curr.display().await.is_ok());
+
+        let err = curr.next().await.unwrap_err();
+        assert!(err.to_string().contains(MODIFY_PLAYBACK_STATE));
+        let err = curr.like().await.unwrap_err();
+        assert!(err.to_string().contains(LIBRARY_MODIFY));
+        assert!(backend.calls().is_empty());
+
+        backend.state().granted_scopes = Some(HashSet::new());
+        assert!(CurrentlyPlaying::from_backend(backend).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn volume_is_clamped() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        curr.volume_up(80).await.unwrap();
+        curr.volume_down(80).await.unwrap();
+        assert_eq!(backend.calls(), vec![Call::Volume(100), Call::Volume(0)]);
+    }
+
+    #[tokio::test]
+    async fn seek_is_clamped() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        curr.seek_to(Duration::seconds(300)).await.unwrap();
+        curr.seek_to(Duration::seconds(-5)).await.unwrap();
+        curr.seek_relative(Duration::seconds(15)).await.unwrap();
+        curr.seek_relative(Duration::seconds(-90)).await.unwrap();
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::Seek(Duration::seconds(200)),
+                Call::Seek(Duration::zero()),
+                Call::Seek(Duration::seconds(15)),
+                Call::Seek(Duration::zero())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn play_from_uri() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        curr.play_from_uri("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".to_owned())
+            .await
+            .unwrap();
+        assert!(curr.play_from_uri("not a uri".to_owned()).await.is_err());
+        assert_eq!(
+            backend.calls(),
+            vec![Call::PlayContext(
+                "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".to_owned()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn play_from_uri_of_every_type() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        for uri in [
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+            "spotify:artist:0TnOYISbd1XYRBk9myaseg",
+            "spotify:show:5CfCWKI5pZ28U0uOzXkDHe",
+            "spotify:user:spotify:playlist:37i9dQZF1DXcBWIGoYBM5M",
+        ] {
+            curr.play_from_uri(uri.to_owned()).await.unwrap();
+        }
+        for uri in [
+            "spotify:track:4cOdK2wGLETKBW3PvgPWqT",
+            "spotify:episode:512ojhOuo1ktJprKbVcKyQ",
+        ] {
+            curr.play_from_uri(uri.to_owned()).await.unwrap();
+        }
+        assert!(curr
+            .play_from_uri("spotify:user:spotify".to_owned())
+            .await
+            .is_err());
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::PlayContext("spotify:album:4aawyAB9vmqN3uQ7FjRGTy".to_owned()),
+                Call::PlayContext("spotify:artist:0TnOYISbd1XYRBk9myaseg".to_owned()),
+                Call::PlayContext("spotify:show:5CfCWKI5pZ28U0uOzXkDHe".to_owned()),
+                Call::PlayContext("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".to_owned()),
+                Call::PlayUris(vec!["spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()]),
+                Call::PlayUris(vec!["spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned()]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn play_from_uri_at() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        let offset = || Some(Offset::Position(Duration::milliseconds(2)));
+        curr.play_from_uri_at(
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+            offset(),
+            Some(Duration::seconds(30)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            backend.state().playback.as_ref().unwrap().progress,
+            Some(Duration::seconds(30))
+        );
+        for uri in [
+            "spotify:artist:0TnOYISbd1XYRBk9myaseg",
+            "spotify:track:4cOdK2wGLETKBW3PvgPWqT",
+        ] {
+            let err = curr
+                .play_from_uri_at(uri, offset(), None)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("offset"), "{err}");
+        }
+        assert_eq!(backend.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn transfer() {
+        let backend = backend();
+        backend.state().devices.push(device("phone", "Phone"));
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        let names = |devices: Vec<Device>| -> Vec<String> {
+            devices.into_iter().map(|device| device.name).collect()
+        };
+        assert_eq!(
+            names(curr.devices().await.unwrap()),
+            ["Fake device", "Phone"]
+        );
+
+        curr.transfer("phone", true).await.unwrap();
+        assert!(curr.transfer("tv", false).await.is_err());
+        assert_eq!(
+            backend.calls(),
+            vec![Call::Transfer("phone".to_owned(), Some(true))]
+        );
+        let curr = CurrentlyPlaying::from_backend(backend).await.unwrap();
+        assert_eq!(curr.device.as_deref(), Some("Phone"));
+        assert_eq!(curr.is_playing, Some(true));
+    }
+
+    #[tokio::test]
+    async fn queue() {
+        let backend = backend();
+        let id = TrackId::from_id("2takcwOaAZWiXQijPHIx7B").unwrap();
+        let next = track(id, "Next", "Artist", Duration::seconds(100));
+        backend.state().queue.push(PlayableItem::Track(next));
+        let curr = CurrentlyPlaying::from_backend(backend).await.unwrap();
+
+        let queue = curr.queue().await.unwrap();
+        assert!(matches!(
+            queue.currently_playing,
+            Some(PlayableItem::Track(_))
+        ));
+        assert!(matches!(&queue.queue[..], [PlayableItem::Track(track)] if track.name == "Next"));
+    }
+
+    #[tokio::test]
+    async fn play_items() {
+        let backend = backend();
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        let items = vec![
+            PlayableId::from(TrackId::from_id("4cOdK2wGLETKBW3PvgPWqT").unwrap()),
+            PlayableId::from(EpisodeId::from_id("512ojhOuo1ktJprKbVcKyQ").unwrap()),
+        ];
+        curr.play_items(items, None, None).await.unwrap();
+        assert_eq!(
+            backend.calls(),
+            vec![Call::PlayUris(vec![
+                "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned(),
+                "spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned()
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_hits() {
+        let backend = backend();
+        let id = TrackId::from_id("2takcwOaAZWiXQijPHIx7B").unwrap();
+        let found = track(id, "Found", "Artist", Duration::seconds(100));
+        backend
+            .state()
+            .search_results
+            .push(SearchResult::Tracks(Page {
+                href: String::new(),
+                items: vec![found],
+                limit: 5,
+                next: None,
+                offset: 0,
+                previous: None,
+                total: 1,
+            }));
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            curr.search_hits("found", SearchType::Track, 5)
+                .await
+                .unwrap(),
+            [SearchHit {
+                uri: "spotify:track:2takcwOaAZWiXQijPHIx7B".to_owned(),
+                description: "Found - Artist".to_owned(),
+            }]
+        );
+        assert!(curr
+            .search_hits("found", SearchType::Album, 5)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn add_to_queue() {
+        let backend = backend();
+        let id = TrackId::from_id("2takcwOaAZWiXQijPHIx7B").unwrap();
+        let found = track(id, "Found", "Artist", Duration::seconds(100));
+        backend
+            .state()
+            .search_results
+            .push(SearchResult::Tracks(Page {
+                href: String::new(),
+                items: vec![found],
+                limit: 1,
+                next: None,
+                offset: 0,
+                previous: None,
+                total: 1,
+            }));
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+
+        let uri = curr.resolve_uri("some song").await.unwrap();
+        assert_eq!(uri, "spotify:track:2takcwOaAZWiXQijPHIx7B");
+        assert_eq!(
+            curr.resolve_uri("https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ")
+                .await
+                .unwrap(),
+            "spotify:episode:512ojhOuo1ktJprKbVcKyQ"
+        );
+        assert!(curr.resolve_uri("spotify:nope").await.is_err());
+
+        curr.add_to_queue(&uri).await.unwrap();
+        curr.add_to_queue("spotify:episode:512ojhOuo1ktJprKbVcKyQ")
+            .await
+            .unwrap();
+        let err = curr
+            .add_to_queue("spotify:album:0sNOF9WDwhWunNAHPD3Baj")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Only tracks and episodes"));
+        assert_eq!(
+            backend.calls()[1..],
+            [
+                Call::Queue(uri),
+                Call::Queue("spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn on_device() {
+        let backend = backend();
+        backend.state().devices.push(device("phone", "My Phone"));
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        curr.next().await.unwrap();
+        let curr = curr.on_device("phone").await.unwrap();
+        curr.next().await.unwrap();
+        assert_eq!(curr.resolve_device("my").await.unwrap(), "phone");
+        assert!(curr.clone().on_device("tv").await.is_err());
+        assert_eq!(backend.device_ids(), vec![None, Some("phone".to_owned())]);
+
+        // the playback of the active device doesn't apply to another one
+        assert!(curr.toggle_shuffle().await.is_err());
+        assert!(curr.seek_relative(Duration::seconds(10)).await.is_err());
+        curr.toggle_play_pause().await.unwrap();
+        assert_eq!(backend.calls().last(), Some(&Call::Play));
+
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        let curr = curr.on_device("fake").await.unwrap();
+        assert!(curr.progress.is_some() && curr.is_shuffled.is_some());
+    }
 }
@@ -0,0 +1,107 @@
+//! Find a device by its id or a loose spelling of its name
+
+use anyhow::Result;
+use rspotify::model::Device;
+
+use crate::error::Error;
+
+/// Whether the characters of `query` appear in `name` in order.
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut name = name.chars();
+    query.chars().all(|c| name.any(|n| n == c))
+}
+
+/// Find the device whose id is `query`, or else whose name matches it.
+///
+/// Names are compared without case, and the first of these that matches
+/// anything wins: the whole name, the start of the name, a part of the name,
+/// and the letters of the query in order, so `kit` and `ktchn` both find
+/// "Kitchen". Devices without an id can't be targeted and are skipped.
+///
+/// # Errors
+///
+/// Returns an error if nothing matches, or several devices match equally well.
+pub fn find_device<'a>(devices: &'a [Device], query: &str) -> Result<&'a Device> {
+    let devices: Vec<&Device> = devices
+        .iter()
+        .filter(|device| device.id.is_some())
+        .collect();
+    if let Some(device) = devices
+        .iter()
+        .find(|device| device.id.as_deref() == Some(query))
+    {
+        return Ok(device);
+    }
+
+    let query = query.trim().to_lowercase();
+    let matchers: [&dyn Fn(&str) -> bool; 4] = [
+        &|name| name == query,
+        &|name| name.starts_with(&query),
+        &|name| name.contains(&query),
+        &|name| is_subsequence(&query, name),
+    ];
+    for matches in matchers {
+        let found: Vec<&Device> = devices
+            .iter()
+            .copied()
+            .filter(|device| matches(&device.name.to_lowercase()))
+            .collect();
+        match found[..] {
+            [] => {},
+            [device] => return Ok(device),
+            _ => anyhow::bail!(Error::AmbiguousDevice(query.clone(), names(&found))),
+        }
+    }
+
+    anyhow::bail!(Error::UnknownDevice(query, names(&devices)))
+}
+
+/// Returns the names of the devices, separated by commas.
+fn names(devices: &[&Device]) -> String {
+    let names: Vec<&str> = devices.iter().map(|device| device.name.as_str()).collect();
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_backend::device;
+
+    fn devices() -> Vec<Device> {
+        vec![
+            device("a1", "Kitchen"),
+            device("b2", "Kitchen Speaker"),
+            device("c3", "Living Room TV"),
+            Device {
+                id: None,
+                ..device("", "Restricted")
+            },
+        ]
+    }
+
+    fn find(query: &str) -> Result<String> {
+        find_device(&devices(), query).map(|device| device.name.clone())
+    }
+
+    #[test]
+    fn by_id_or_name() {
+        assert_eq!(find("b2").unwrap(), "Kitchen Speaker");
+        assert_eq!(find("kitchen").unwrap(), "Kitchen");
+        assert_eq!(find("kitchen s").unwrap(), "Kitchen Speaker");
+        assert_eq!(find("living").unwrap(), "Living Room TV");
+        assert_eq!(find("room").unwrap(), "Living Room TV");
+        assert_eq!(find("lvngtv").unwrap(), "Living Room TV");
+    }
+
+    #[test]
+    fn no_match() {
+        let err = find("kit").unwrap_err().to_string();
+        assert!(err.contains("Kitchen, Kitchen Speaker"), "{err}");
+        let err = find("phone").unwrap_err().to_string();
+        assert!(
+            err.contains("Kitchen, Kitchen Speaker, Living Room TV"),
+            "{err}"
+        );
+        assert!(find("restricted").is_err());
+    }
+}
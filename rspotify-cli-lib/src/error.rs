@@ -8,14 +8,49 @@ use thiserror::Error;
 pub enum Error {
     #[error("The client id or client secret is invalid")]
     Auth,
+    #[error("The client secret is required unless auth_mode is set to pkce")]
+    MissingClientSecret,
     #[error("Unable to create authorization URI")]
     AuthorizationURI,
+    #[error("No usable cached token, run `rspotify-cli auth` to log in")]
+    NotLoggedIn,
+    #[error("Authorization was denied: {0}")]
+    AuthorizationDenied(String),
+    #[error("Unable to listen on the redirect URI")]
+    RedirectURI,
     #[error("No active device found")]
     NoActiveDevice,
     #[error("Unable to control song playback: {0}")]
     Control(String),
+    #[error("No device matches {0}, the devices are: {1}")]
+    UnknownDevice(String, String),
+    #[error("{0} matches several devices: {1}")]
+    AmbiguousDevice(String, String),
+    #[error("Only tracks and episodes can be queued, not {0}")]
+    NotQueueable(String),
+    #[error("Only albums, artists, playlists, shows, tracks and episodes can be played, not {0}")]
+    NotPlayable(String),
+    #[error("Only tracks and episodes can be played in a list, not {0}")]
+    NotPlayableInList(String),
+    #[error("Only albums and playlists can be started at an offset, not {0}")]
+    NoOffset(String),
+    #[error("No track found for {0}")]
+    NoSearchResults(String),
     #[error("Current playing media must be a track")]
     NotTrack,
     #[error("Unable to parse the invalid URL")]
     InvalidURL,
+    #[error("Unable to access the token held by the client")]
+    TokenLock,
+    #[error("Unable to access the token store: {0}")]
+    TokenStore(String),
+    #[error("Unable to decrypt the token, the passphrase may be wrong")]
+    TokenDecrypt,
+    #[error("Unknown scope or scope preset: {0}")]
+    UnknownScope(String),
+    #[error(
+        "The cached token lacks the {0} scope, add it to `scopes` in config.toml and run \
+         `rspotify-cli auth` to re-authorize"
+    )]
+    MissingScope(String),
 }
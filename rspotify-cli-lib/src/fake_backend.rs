@@ -0,0 +1,463 @@
+//! In-memory player backend for testing without a network connection.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use rspotify::{
+    model::{
+        Actions, CurrentPlaybackContext, CurrentUserQueue, CurrentlyPlayingType, Device,
+        DeviceType, FullTrack, Offset, Page, PlayContextId, PlayableId, PlayableItem,
+        RepeatState as RSpotifyRepeatState, SearchResult, SearchType, SimplifiedAlbum,
+        SimplifiedArtist, TrackId,
+    },
+    prelude::Id,
+};
+
+use crate::{backend::PlayerBackend, error::Error, repeat_state::RepeatState};
+
+/// A call that was made against a [`FakeBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    /// Playback was resumed.
+    Play,
+    /// Playback was paused.
+    Pause,
+    /// Skipped to the next item.
+    Next,
+    /// Skipped to the previous item.
+    Previous,
+    /// The repeat state was set.
+    Repeat(RepeatState),
+    /// The volume was set.
+    Volume(u8),
+    /// The shuffle state was set.
+    Shuffle(bool),
+    /// Seeked to a position.
+    Seek(Duration),
+    /// A track was added to the library.
+    Like(String),
+    /// A track was removed from the library.
+    Unlike(String),
+    /// A list of tracks or episodes was played.
+    PlayUris(Vec<String>),
+    /// A context was played.
+    PlayContext(String),
+    /// An item was added to the queue.
+    Queue(String),
+    /// Playback was moved to a device, and started if set.
+    Transfer(String, Option<bool>),
+    /// The catalog was searched.
+    Search(String, SearchType),
+}
+
+/// Mutable state of a [`FakeBackend`].
+#[derive(Debug, Default)]
+pub struct FakeState {
+    /// Current playback, `None` when there is no active device.
+    pub playback: Option<CurrentPlaybackContext>,
+
+    /// Ids of the tracks in the user's library.
+    pub liked: Vec<TrackId<'static>>,
+
+    /// Scopes granted to the token, `None` when they are unknown.
+    pub granted_scopes: Option<HashSet<String>>,
+
+    /// Canned search results, returned for searches of the matching kind.
+    pub search_results: Vec<SearchResult>,
+
+    /// Devices the user can play on, besides the one of the playback.
+    pub devices: Vec<Device>,
+
+    /// Items queued after the current one.
+    pub queue: Vec<PlayableItem>,
+
+    /// How many of the next playback fetches fail, as if the network was
+    /// down.
+    pub failing_fetches: usize,
+
+    /// Every call that modified the player, in order.
+    pub calls: Vec<Call>,
+
+    /// Device id sent with every player command in `calls`, in order, `None`
+    /// when it was sent to the active device.
+    pub device_ids: Vec<Option<String>>,
+}
+
+/// Player backend that keeps all of its state in memory.
+///
+/// Player commands update the stored playback the same way Spotify would,
+/// and are recorded so that tests can assert on them.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    /// State behind a lock so that the backend can be shared.
+    state: Mutex<FakeState>,
+}
+
+impl FakeBackend {
+    /// Create a backend without an active device.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a backend that is playing a track on an active device.
+    pub fn with_track(track: FullTrack) -> Self {
+        let backend = Self::new();
+        backend.state().playback = Some(playback(PlayableItem::Track(track)));
+        backend
+    }
+
+    /// Lock and return the state of the backend.
+    pub fn state(&self) -> MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Return every call that was made so far.
+    pub fn calls(&self) -> Vec<Call> {
+        self.state().calls.clone()
+    }
+
+    /// Return the device id sent with every player command so far.
+    pub fn device_ids(&self) -> Vec<Option<String>> {
+        self.state().device_ids.clone()
+    }
+
+    /// Record a call sent to `device_id` and apply `f` to the current
+    /// playback.
+    fn player(
+        &self,
+        call: Call,
+        device_id: Option<&str>,
+        f: impl FnOnce(&mut CurrentPlaybackContext),
+    ) -> Result<()> {
+        let mut state = self.state();
+        let playback = state.playback.as_mut().ok_or(Error::NoActiveDevice)?;
+        f(playback);
+        state.calls.push(call);
+        state.device_ids.push(device_id.map(ToOwned::to_owned));
+        drop(state);
+        Ok(())
+    }
+}
+
+/// Build a track with the given metadata.
+pub fn track(id: TrackId<'static>, title: &str, artist: &str, duration: Duration) -> FullTrack {
+    FullTrack {
+        album: SimplifiedAlbum {
+            album_group: None,
+            album_type: None,
+            artists: vec![],
+            available_markets: vec![],
+            external_urls: HashMap::new(),
+            href: None,
+            id: None,
+            images: vec![],
+            name: String::new(),
+            release_date: None,
+            release_date_precision: None,
+            restrictions: None,
+        },
+        artists: vec![SimplifiedArtist {
+            external_urls: HashMap::new(),
+            href: None,
+            id: None,
+            name: artist.to_owned(),
+        }],
+        available_markets: vec![],
+        disc_number: 1,
+        duration,
+        explicit: false,
+        external_ids: HashMap::new(),
+        external_urls: HashMap::new(),
+        href: None,
+        id: Some(id),
+        is_local: false,
+        is_playable: None,
+        linked_from: None,
+        restrictions: None,
+        name: title.to_owned(),
+        popularity: 0,
+        preview_url: None,
+        track_number: 1,
+    }
+}
+
+/// Build an inactive device at half volume.
+pub fn device(id: &str, name: &str) -> Device {
+    Device {
+        id: Some(id.to_owned()),
+        is_active: false,
+        is_private_session: false,
+        is_restricted: false,
+        name: name.to_owned(),
+        _type: DeviceType::Computer,
+        volume_percent: Some(50),
+    }
+}
+
+/// Build a paused playback of `item` on an active device at half volume.
+pub fn playback(item: PlayableItem) -> CurrentPlaybackContext {
+    let currently_playing_type = match item {
+        PlayableItem::Track(_) => CurrentlyPlayingType::Track,
+        PlayableItem::Episode(_) => CurrentlyPlayingType::Episode,
+    };
+    CurrentPlaybackContext {
+        device: Device {
+            is_active: true,
+            ..device("fake-device", "Fake device")
+        },
+        repeat_state: RSpotifyRepeatState::Off,
+        shuffle_state: false,
+        context: None,
+        timestamp: Utc::now(),
+        progress: Some(Duration::zero()),
+        is_playing: false,
+        item: Some(item),
+        currently_playing_type,
+        actions: Actions::default(),
+    }
+}
+
+/// Whether a search result is of the given kind.
+const fn matches_kind(result: &SearchResult, kind: SearchType) -> bool {
+    matches!(
+        (result, kind),
+        (SearchResult::Artists(_), SearchType::Artist)
+            | (SearchResult::Albums(_), SearchType::Album)
+            | (SearchResult::Tracks(_), SearchType::Track)
+            | (SearchResult::Playlists(_), SearchType::Playlist)
+            | (SearchResult::Shows(_), SearchType::Show)
+            | (SearchResult::Episodes(_), SearchType::Episode)
+    )
+}
+
+/// Return a page without any items.
+const fn empty_page<T>() -> Page<T> {
+    Page {
+        href: String::new(),
+        items: Vec::new(),
+        limit: 0,
+        next: None,
+        offset: 0,
+        previous: None,
+        total: 0,
+    }
+}
+
+/// Return an empty search result of the given kind.
+const fn empty_result(kind: SearchType) -> SearchResult {
+    match kind {
+        SearchType::Artist => SearchResult::Artists(empty_page()),
+        SearchType::Album => SearchResult::Albums(empty_page()),
+        SearchType::Track => SearchResult::Tracks(empty_page()),
+        SearchType::Playlist => SearchResult::Playlists(empty_page()),
+        SearchType::Show => SearchResult::Shows(empty_page()),
+        SearchType::Episode => SearchResult::Episodes(empty_page()),
+    }
+}
+
+#[async_trait]
+impl PlayerBackend for FakeBackend {
+    async fn granted_scopes(&self) -> Result<Option<HashSet<String>>> {
+        Ok(self.state().granted_scopes.clone())
+    }
+
+    async fn current_playback(&self) -> Result<Option<CurrentPlaybackContext>> {
+        let mut state = self.state();
+        if state.failing_fetches > 0 {
+            state.failing_fetches -= 1;
+            anyhow::bail!(Error::Control("fetch the playback".to_owned()));
+        }
+        Ok(state.playback.clone())
+    }
+
+    async fn device(&self) -> Result<Vec<Device>> {
+        let state = self.state();
+        Ok(state
+            .playback
+            .iter()
+            .map(|playback| playback.device.clone())
+            .chain(state.devices.iter().cloned())
+            .collect())
+    }
+
+    async fn transfer_playback(&self, device_id: &str, play: Option<bool>) -> Result<()> {
+        let mut state = self.state();
+        let FakeState {
+            playback, devices, ..
+        } = &mut *state;
+        let playback = playback.as_mut().ok_or(Error::NoActiveDevice)?;
+        if playback.device.id.as_deref() != Some(device_id) {
+            let index = devices
+                .iter()
+                .position(|device| device.id.as_deref() == Some(device_id))
+                .ok_or_else(|| Error::Control(format!("find device {device_id}")))?;
+            let mut device = devices.remove(index);
+            device.is_active = true;
+            playback.device.is_active = false;
+            devices.push(std::mem::replace(&mut playback.device, device));
+        }
+        if let Some(play) = play {
+            playback.is_playing = play;
+        }
+        state.calls.push(Call::Transfer(device_id.to_owned(), play));
+        drop(state);
+        Ok(())
+    }
+
+    async fn current_user_queue(&self) -> Result<CurrentUserQueue> {
+        let state = self.state();
+        Ok(CurrentUserQueue {
+            currently_playing: state
+                .playback
+                .as_ref()
+                .and_then(|playback| playback.item.clone()),
+            queue: state.queue.clone(),
+        })
+    }
+
+    async fn add_item_to_queue(
+        &self,
+        item: PlayableId<'static>,
+        device_id: Option<&str>,
+    ) -> Result<()> {
+        self.player(Call::Queue(item.uri()), device_id, |_| {})
+    }
+
+    async fn current_user_saved_tracks_contains(
+        &self,
+        ids: Vec<TrackId<'static>>,
+    ) -> Result<Vec<bool>> {
+        let state = self.state();
+        Ok(ids.iter().map(|id| state.liked.contains(id)).collect())
+    }
+
+    async fn current_user_saved_tracks_add(&self, ids: Vec<TrackId<'static>>) -> Result<()> {
+        let mut state = self.state();
+        for id in ids {
+            state.calls.push(Call::Like(id.uri()));
+            if !state.liked.contains(&id) {
+                state.liked.push(id);
+            }
+        }
+        drop(state);
+        Ok(())
+    }
+
+    async fn current_user_saved_tracks_delete(&self, ids: Vec<TrackId<'static>>) -> Result<()> {
+        let mut state = self.state();
+        for id in ids {
+            state.calls.push(Call::Unlike(id.uri()));
+            state.liked.retain(|liked| liked != &id);
+        }
+        drop(state);
+        Ok(())
+    }
+
+    async fn resume_playback(
+        &self,
+        device_id: Option<&str>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        self.player(Call::Play, device_id, |playback| {
+            playback.is_playing = true;
+            if position.is_some() {
+                playback.progress = position;
+            }
+        })
+    }
+
+    async fn pause_playback(&self, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Pause, device_id, |playback| {
+            playback.is_playing = false
+        })
+    }
+
+    async fn next_track(&self, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Next, device_id, |playback| {
+            playback.progress = Some(Duration::zero());
+        })
+    }
+
+    async fn previous_track(&self, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Previous, device_id, |playback| {
+            playback.progress = Some(Duration::zero());
+        })
+    }
+
+    async fn repeat(&self, state: RSpotifyRepeatState, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Repeat(state.into()), device_id, |playback| {
+            playback.repeat_state = state;
+        })
+    }
+
+    async fn volume(&self, volume_percent: u8, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Volume(volume_percent), device_id, |playback| {
+            playback.device.volume_percent = Some(volume_percent.into());
+        })
+    }
+
+    async fn shuffle(&self, state: bool, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Shuffle(state), device_id, |playback| {
+            playback.shuffle_state = state;
+        })
+    }
+
+    async fn seek_track(&self, position: Duration, device_id: Option<&str>) -> Result<()> {
+        self.player(Call::Seek(position), device_id, |playback| {
+            playback.progress = Some(position);
+        })
+    }
+
+    async fn start_uris_playback(
+        &self,
+        uris: Vec<PlayableId<'static>>,
+        device_id: Option<&str>,
+        _offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        let call = Call::PlayUris(uris.iter().map(Id::uri).collect());
+        self.player(call, device_id, |playback| {
+            playback.is_playing = true;
+            playback.progress = position.or_else(|| Some(Duration::zero()));
+        })
+    }
+
+    async fn start_context_playback(
+        &self,
+        context_uri: PlayContextId<'static>,
+        device_id: Option<&str>,
+        _offset: Option<Offset>,
+        position: Option<Duration>,
+    ) -> Result<()> {
+        self.player(
+            Call::PlayContext(context_uri.uri()),
+            device_id,
+            |playback| {
+                playback.is_playing = true;
+                playback.progress = position.or_else(|| Some(Duration::zero()));
+            },
+        )
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        kind: SearchType,
+        _limit: Option<u32>,
+        _offset: Option<u32>,
+    ) -> Result<SearchResult> {
+        let mut state = self.state();
+        state.calls.push(Call::Search(query.to_owned(), kind));
+        Ok(state
+            .search_results
+            .iter()
+            .find(|result| matches_kind(result, kind))
+            .cloned()
+            .unwrap_or_else(|| empty_result(kind)))
+    }
+}
@@ -1,60 +1,198 @@
 //! Initialize Spotify client object
 
-use std::path::PathBuf;
+use std::{collections::HashSet, sync::Arc};
 
 use anyhow::{Context, Result};
-use rspotify::{prelude::*, scopes, AuthCodeSpotify, Credentials, OAuth};
+use rspotify::{
+    prelude::*, AuthCodePkceSpotify, AuthCodeSpotify, CallbackError, Credentials, OAuth,
+    TokenCallback,
+};
+pub use rspotify::{DEFAULT_API_BASE_URL, DEFAULT_AUTH_BASE_URL};
+use serde::{Deserialize, Serialize};
 
-use crate::error::Error;
+use crate::{backend::PlayerBackend, error::Error, token_store::TokenStore};
+
+/// How the client is authorized to use the Spotify account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthMode {
+    /// Authorization code flow, which requires the client secret.
+    #[default]
+    AuthCode,
+
+    /// Authorization code flow with PKCE, which only requires the client id.
+    Pkce,
+}
+
+/// Spotify client object for either authorization mode
+#[derive(Debug, Clone)]
+pub enum SpotifyClient {
+    /// Client using the authorization code flow.
+    AuthCode(AuthCodeSpotify),
+
+    /// Client using the authorization code flow with PKCE.
+    Pkce(AuthCodePkceSpotify),
+}
+
+impl SpotifyClient {
+    /// Returns the redirect URI the client was configured with.
+    pub fn redirect_uri(&self) -> &str {
+        match self {
+            Self::AuthCode(spotify) => &spotify.get_oauth().redirect_uri,
+            Self::Pkce(spotify) => &spotify.get_oauth().redirect_uri,
+        }
+    }
+
+    /// Returns the URL the user has to visit to authorize the client.
+    ///
+    /// With PKCE this also generates a new code verifier, so the code
+    /// obtained from the previous URL can no longer be used.
+    pub fn authorize_url(&mut self) -> Result<String> {
+        match self {
+            Self::AuthCode(spotify) => spotify.get_authorize_url(false),
+            Self::Pkce(spotify) => spotify.get_authorize_url(None),
+        }
+        .context(Error::AuthorizationURI)
+    }
+
+    /// Load the stored token into the client, refreshing it if it has expired.
+    ///
+    /// Returns false if there is no stored token with every requested scope.
+    pub async fn load_token(&self, store: &TokenStore) -> Result<bool> {
+        match self {
+            Self::AuthCode(spotify) => load_token(spotify, store).await,
+            Self::Pkce(spotify) => load_token(spotify, store).await,
+        }
+    }
+
+    /// Returns the client as a backend for `CurrentlyPlaying`.
+    pub fn into_backend(self) -> Arc<dyn PlayerBackend> {
+        match self {
+            Self::AuthCode(spotify) => Arc::new(spotify),
+            Self::Pkce(spotify) => Arc::new(spotify),
+        }
+    }
+}
+
+/// Settings used to build the Spotify client object
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Where the token is kept.
+    pub token_store: TokenStore,
+
+    /// Client id for the Spotify API.
+    pub client_id: String,
+
+    /// Client secret for the Spotify API, only required by
+    /// [`AuthMode::AuthCode`].
+    pub client_secret: Option<String>,
+
+    /// How the client is authorized.
+    pub auth_mode: AuthMode,
+
+    /// Redirect URI for the Spotify API.
+    pub redirect_uri: String,
+
+    /// Scopes to request, see [`crate::scopes::resolve_scopes`].
+    pub scopes: HashSet<String>,
+
+    /// Base URL of the Spotify Web API.
+    pub api_base_url: String,
+
+    /// Base URL of the Spotify accounts service.
+    pub auth_base_url: String,
+}
+
+/// Build the Spotify client object without authorizing it
+pub fn build_spotify(config: ClientConfig) -> Result<SpotifyClient> {
+    let ClientConfig {
+        token_store,
+        client_id,
+        client_secret,
+        auth_mode,
+        redirect_uri,
+        scopes,
+        api_base_url,
+        auth_base_url,
+    } = config;
 
-/// Initialize Spotify client object
-pub async fn init_spotify(
-    cache_path: PathBuf,
-    client_id: String,
-    client_secret: String,
-    redirect_uri: String,
-) -> Result<AuthCodeSpotify> {
     let rspotify_config = rspotify::Config {
-        token_cached: true,
-        cache_path,
+        api_base_url,
+        auth_base_url,
+        token_callback_fn: Arc::new(Some(TokenCallback(Box::new(move |token| {
+            token_store
+                .save(&token)
+                .map_err(|err| CallbackError::CustomizedError(err.to_string()))
+        })))),
         ..Default::default()
     };
 
     let oauth = OAuth {
-        // use all scopes bc scopes are annoying
-        scopes: scopes!(
-            "ugc-image-upload",
-            "user-read-playback-state",    // see player state
-            "user-modify-playback-state",  // control player stuff
-            "user-read-currently-playing", // see player state
-            "app-remote-control",
-            "streaming",
-            "playlist-read-private",
-            "playlist-read-collaborative",
-            "playlist-modify-private",
-            "playlist-modify-public",
-            "user-follow-modify",
-            "user-follow-read",
-            "user-read-playback-position",
-            "user-top-read",             // get top artists and tracks
-            "user-read-recently-played", // see recently played
-            "user-library-modify",       // add/remove liked songs
-            "user-library-read",         // see liked songs
-            "user-read-email",
-            "user-read-private"
-        ),
+        scopes,
         redirect_uri,
         ..Default::default()
     };
 
-    let creds = Credentials::new(&client_id, &client_secret);
+    Ok(match auth_mode {
+        AuthMode::AuthCode => {
+            let client_secret = client_secret.context(Error::MissingClientSecret)?;
+            let creds = Credentials::new(&client_id, &client_secret);
+            SpotifyClient::AuthCode(AuthCodeSpotify::with_config(creds, oauth, rspotify_config))
+        },
+        AuthMode::Pkce => {
+            let creds = Credentials::new_pkce(&client_id);
+            SpotifyClient::Pkce(AuthCodePkceSpotify::with_config(
+                creds,
+                oauth,
+                rspotify_config,
+            ))
+        },
+    })
+}
 
-    let spotify = AuthCodeSpotify::with_config(creds, oauth, rspotify_config);
+/// Initialize Spotify client object, prompting for authorization if there is
+/// no usable stored token
+pub async fn init_spotify(config: ClientConfig) -> Result<SpotifyClient> {
+    let store = config.token_store.clone();
+    let mut spotify = build_spotify(config)?;
 
-    let url = spotify
-        .get_authorize_url(false)
-        .context(Error::AuthorizationURI)?;
-    spotify.prompt_for_token(&url).await.context(Error::Auth)?;
+    if !spotify.load_token(&store).await? {
+        let url = spotify.authorize_url()?;
+        match &spotify {
+            SpotifyClient::AuthCode(spotify) => prompt_for_token(spotify, &url).await,
+            SpotifyClient::Pkce(spotify) => prompt_for_token(spotify, &url).await,
+        }?;
+    }
 
     Ok(spotify)
 }
+
+/// Load the stored token into the client if it has every requested scope,
+/// refreshing it if it has expired.
+async fn load_token(spotify: &impl OAuthClient, store: &TokenStore) -> Result<bool> {
+    let Some(token) = store.load()? else {
+        return Ok(false);
+    };
+    if !spotify.get_oauth().scopes.is_subset(&token.scopes) {
+        return Ok(false);
+    }
+
+    let expired = token.is_expired();
+    *spotify
+        .get_token()
+        .lock()
+        .await
+        .ok()
+        .context(Error::TokenLock)? = Some(token);
+    if expired {
+        spotify.refresh_token().await.context(Error::Auth)?;
+    }
+
+    Ok(true)
+}
+
+/// Ask the user to open the authorization URL and paste the redirect URL.
+async fn prompt_for_token(spotify: &impl OAuthClient, url: &str) -> Result<()> {
+    let code = spotify.get_code_from_user(url).context(Error::Auth)?;
+    spotify.request_token(&code).await.context(Error::Auth)
+}
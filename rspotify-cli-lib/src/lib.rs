@@ -3,8 +3,14 @@
 #![forbid(unsafe_code)]
 #![warn(clippy::all, clippy::nursery, clippy::expect_used, clippy::unwrap_used)]
 
+pub mod auth;
+pub mod backend;
 pub mod currently_playing;
+pub mod device_match;
 pub mod error;
+pub mod fake_backend;
 pub mod init_spotify;
 pub mod repeat_state;
+pub mod scopes;
+pub mod token_store;
 pub mod url_convert;
@@ -0,0 +1,142 @@
+//! OAuth scopes and the presets that can be requested in the config.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use crate::error::Error;
+
+/// See the current playback state and the active device.
+pub const READ_PLAYBACK_STATE: &str = "user-read-playback-state";
+
+/// Control playback, volume, shuffle and repeat.
+pub const MODIFY_PLAYBACK_STATE: &str = "user-modify-playback-state";
+
+/// See the currently playing item.
+pub const READ_CURRENTLY_PLAYING: &str = "user-read-currently-playing";
+
+/// See the liked songs.
+pub const LIBRARY_READ: &str = "user-library-read";
+
+/// Add and remove liked songs.
+pub const LIBRARY_MODIFY: &str = "user-library-modify";
+
+/// Scopes needed to see what is playing and whether it is liked.
+pub const READ_ONLY: &[&str] = &[READ_PLAYBACK_STATE, READ_CURRENTLY_PLAYING, LIBRARY_READ];
+
+/// Scopes needed to see and control playback.
+pub const PLAYBACK: &[&str] = &[
+    READ_PLAYBACK_STATE,
+    READ_CURRENTLY_PLAYING,
+    LIBRARY_READ,
+    MODIFY_PLAYBACK_STATE,
+];
+
+/// Scopes needed to see and change the liked songs.
+pub const LIBRARY: &[&str] = &[LIBRARY_READ, LIBRARY_MODIFY];
+
+/// Every scope Spotify knows about.
+pub const FULL: &[&str] = &[
+    "ugc-image-upload",
+    READ_PLAYBACK_STATE,
+    MODIFY_PLAYBACK_STATE,
+    READ_CURRENTLY_PLAYING,
+    "app-remote-control",
+    "streaming",
+    "playlist-read-private",
+    "playlist-read-collaborative",
+    "playlist-modify-private",
+    "playlist-modify-public",
+    "user-follow-modify",
+    "user-follow-read",
+    "user-read-playback-position",
+    "user-top-read",
+    "user-read-recently-played",
+    LIBRARY_MODIFY,
+    LIBRARY_READ,
+    "user-read-email",
+    "user-read-private",
+];
+
+/// Returns the scopes of a preset, or `None` if there is no such preset.
+pub fn preset(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "read-only" => Some(READ_ONLY),
+        "playback" => Some(PLAYBACK),
+        "library" => Some(LIBRARY),
+        "full" => Some(FULL),
+        _ => None,
+    }
+}
+
+/// Resolve a list of presets and individual scopes into a set of scopes.
+///
+/// # Errors
+///
+/// Returns an error if an entry is neither a preset nor a known scope.
+pub fn resolve_scopes(entries: &[String]) -> Result<HashSet<String>> {
+    let mut scopes = HashSet::new();
+    for entry in entries {
+        if let Some(preset) = preset(entry) {
+            scopes.extend(preset.iter().map(|scope| (*scope).to_owned()));
+        } else if FULL.contains(&entry.as_str()) {
+            scopes.insert(entry.clone());
+        } else {
+            anyhow::bail!(Error::UnknownScope(entry.clone()));
+        }
+    }
+    Ok(scopes)
+}
+
+/// Check that `scope` was granted.
+///
+/// When the granted scopes are unknown, every scope is assumed to be granted
+/// and the API reports the error instead.
+///
+/// # Errors
+///
+/// Returns [`Error::MissingScope`] if `scope` was not granted.
+pub fn require(granted: Option<&HashSet<String>>, scope: &str) -> Result<()> {
+    match granted {
+        Some(granted) if !granted.contains(scope) => {
+            anyhow::bail!(Error::MissingScope(scope.to_owned()))
+        },
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|e| (*e).to_owned()).collect()
+    }
+
+    #[test]
+    fn presets_and_scopes() {
+        let scopes = resolve_scopes(&strings(&["read-only", "user-library-modify"])).unwrap();
+        assert_eq!(scopes.len(), 4);
+        assert!(scopes.contains(LIBRARY_MODIFY));
+        assert!(!scopes.contains(MODIFY_PLAYBACK_STATE));
+
+        assert_eq!(
+            resolve_scopes(&strings(&["full"])).unwrap().len(),
+            FULL.len()
+        );
+        assert!(resolve_scopes(&strings(&["everything"])).is_err());
+    }
+
+    #[test]
+    fn required_scope() {
+        let granted = resolve_scopes(&strings(&["read-only"])).unwrap();
+        assert!(require(Some(&granted), READ_PLAYBACK_STATE).is_ok());
+        assert!(require(None, MODIFY_PLAYBACK_STATE).is_ok());
+
+        let err = require(Some(&granted), MODIFY_PLAYBACK_STATE).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::MissingScope(scope)) if scope == MODIFY_PLAYBACK_STATE
+        ));
+    }
+}
@@ -0,0 +1,393 @@
+//! Where the OAuth token is kept between runs.
+
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rspotify::Token;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Length of the salt used to derive the key of an encrypted file.
+const SALT_LEN: usize = 16;
+
+/// Length of the nonce of an encrypted file.
+const NONCE_LEN: usize = 12;
+
+/// Which backend keeps the token, selected with `token_store` in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenStoreKind {
+    /// Plaintext JSON file.
+    #[default]
+    File,
+
+    /// The freedesktop Secret Service, macOS Keychain or Windows Credential
+    /// Manager.
+    Keyring,
+
+    /// File encrypted with a key derived from a passphrase.
+    EncryptedFile,
+}
+
+/// Passphrase that is never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(pub String);
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Storage for the OAuth token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenStore {
+    /// Plaintext JSON file, in the format of rspotify's token cache.
+    File(PathBuf),
+
+    /// Entry in the OS keyring.
+    Keyring {
+        /// Service the entry belongs to.
+        service: String,
+
+        /// Account the entry is stored under.
+        account: String,
+    },
+
+    /// File encrypted with a key derived from a passphrase.
+    EncryptedFile {
+        /// Path of the file.
+        path: PathBuf,
+
+        /// Passphrase the key is derived from.
+        passphrase: Passphrase,
+    },
+}
+
+/// Contents of an encrypted token file.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedToken {
+    /// Salt of the key derivation, base64 encoded.
+    salt: String,
+
+    /// Nonce of the cipher, base64 encoded.
+    nonce: String,
+
+    /// Encrypted token JSON, base64 encoded.
+    ciphertext: String,
+}
+
+impl TokenStore {
+    /// Returns a short description of where the token is kept.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::File(path) | Self::EncryptedFile { path, .. } => path.display().to_string(),
+            Self::Keyring { service, account } => format!("the keyring ({service}/{account})"),
+        }
+    }
+
+    /// Whether the OS keyring can be reached.
+    ///
+    /// Always true for the other backends.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Self::Keyring { service, account } => keyring::Entry::new(service, account)
+                .and_then(|entry| entry.get_password())
+                .map_or_else(|err| matches!(err, keyring::Error::NoEntry), |_| true),
+            Self::File(_) | Self::EncryptedFile { .. } => true,
+        }
+    }
+
+    /// Load the stored token, or `None` if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be read, or an encrypted file can't
+    /// be decrypted with the passphrase.
+    pub fn load(&self) -> Result<Option<Token>> {
+        let json = match self {
+            // an unreadable plaintext cache is treated as missing, like rspotify does
+            Self::File(path) => return Ok(read_file(path)?.and_then(|json| parse(&json).ok())),
+            Self::Keyring { service, account } => {
+                match keyring::Entry::new(service, account).and_then(|e| e.get_password()) {
+                    Ok(json) => json,
+                    Err(keyring::Error::NoEntry) => return Ok(None),
+                    Err(err) => anyhow::bail!(Error::TokenStore(err.to_string())),
+                }
+            },
+            Self::EncryptedFile { path, passphrase } => match read_file(path)? {
+                Some(contents) => decrypt(&contents, passphrase)?,
+                None => return Ok(None),
+            },
+        };
+
+        parse(&json).map(Some)
+    }
+
+    /// Store a token, replacing the previous one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be written.
+    pub fn save(&self, token: &Token) -> Result<()> {
+        let json = serde_json::to_string(token)?;
+        match self {
+            Self::File(path) => write_private(path, json.as_bytes()),
+            Self::Keyring { service, account } => keyring::Entry::new(service, account)
+                .and_then(|entry| entry.set_password(&json))
+                .map_err(|err| Error::TokenStore(err.to_string()).into()),
+            Self::EncryptedFile { path, passphrase } => {
+                write_private(path, encrypt(&json, passphrase)?.as_bytes())
+            },
+        }
+    }
+
+    /// Remove the stored token. Files are overwritten first so that the
+    /// refresh token does not linger on disk.
+    ///
+    /// Returns whether there was a token to remove.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store can't be written.
+    pub fn remove(&self) -> Result<bool> {
+        match self {
+            Self::File(path) | Self::EncryptedFile { path, .. } => remove_file(path),
+            Self::Keyring { service, account } => {
+                match keyring::Entry::new(service, account).and_then(|e| e.delete_password()) {
+                    Ok(()) => Ok(true),
+                    Err(keyring::Error::NoEntry) => Ok(false),
+                    Err(err) => anyhow::bail!(Error::TokenStore(err.to_string())),
+                }
+            },
+        }
+    }
+
+    /// Move the token from a plaintext JSON cache into this store.
+    ///
+    /// Returns whether a token was moved. Nothing happens if this store is the
+    /// JSON cache itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either store can't be accessed.
+    pub fn migrate_from(&self, json_path: &Path) -> Result<bool> {
+        let legacy = Self::File(json_path.to_path_buf());
+        if *self == legacy {
+            return Ok(false);
+        }
+
+        let Some(token) = legacy.load()? else {
+            return Ok(false);
+        };
+        self.save(&token)?;
+        legacy.remove()?;
+
+        Ok(true)
+    }
+}
+
+/// Parse a token from JSON.
+fn parse(json: &str) -> Result<Token> {
+    serde_json::from_str(json).context(Error::TokenStore("invalid token".to_owned()))
+}
+
+/// Read a file, returning `None` if it is missing or empty.
+fn read_file(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) if contents.trim().is_empty() => Ok(None),
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Write a file that only the current user can read.
+///
+/// The contents go to a temporary file created with those permissions, which
+/// then replaces the file, so that it is never readable by others nor left
+/// half written.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut temp_name = path
+        .file_name()
+        .context(Error::TokenStore("no file name".to_owned()))?
+        .to_owned();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    // left behind by a write that did not complete
+    if temp_path.exists() {
+        fs::remove_file(&temp_path)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Overwrite a file with zeros, then remove it.
+///
+/// Returns whether the file had any contents.
+fn remove_file(path: &Path) -> Result<bool> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(false);
+    };
+    let had_contents = metadata.len() > 0;
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.write_all(&vec![0; usize::try_from(metadata.len())?])?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::remove_file(path)?;
+    Ok(had_contents)
+}
+
+/// Derive the key of an encrypted file from the passphrase.
+fn derive_key(passphrase: &Passphrase, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.0.as_bytes(), salt, &mut key)
+        .map_err(|err| Error::TokenStore(err.to_string()))?;
+    Ok(key)
+}
+
+/// Encrypt the token JSON into the contents of an encrypted file.
+fn encrypt(json: &str, passphrase: &Passphrase) -> Result<String> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::getrandom(&mut salt).map_err(|err| Error::TokenStore(err.to_string()))?;
+    getrandom::getrandom(&mut nonce).map_err(|err| Error::TokenStore(err.to_string()))?;
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), json.as_bytes())
+        .map_err(|_| Error::TokenStore("encryption failed".to_owned()))?;
+
+    Ok(serde_json::to_string(&EncryptedToken {
+        salt: BASE64.encode(salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })?)
+}
+
+/// Decrypt the contents of an encrypted file into the token JSON.
+fn decrypt(contents: &str, passphrase: &Passphrase) -> Result<String> {
+    let encrypted: EncryptedToken =
+        serde_json::from_str(contents).context(Error::TokenStore("invalid file".to_owned()))?;
+    let salt = BASE64.decode(encrypted.salt)?;
+    let nonce = BASE64.decode(encrypted.nonce)?;
+    let ciphertext = BASE64.decode(encrypted.ciphertext)?;
+    if nonce.len() != NONCE_LEN {
+        anyhow::bail!(Error::TokenStore("invalid nonce".to_owned()));
+    }
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let json = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| Error::TokenDecrypt)?;
+
+    Ok(String::from_utf8(json)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> Token {
+        Token {
+            access_token: "access".to_owned(),
+            refresh_token: Some("refresh".to_owned()),
+            expires_at: None,
+            ..Default::default()
+        }
+    }
+
+    fn encrypted(dir: &Path, passphrase: &str) -> TokenStore {
+        TokenStore::EncryptedFile {
+            path: dir.join("token.enc"),
+            passphrase: Passphrase(passphrase.to_owned()),
+        }
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = TokenStore::File(dir.path().join("token.json"));
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(&token()).unwrap();
+        assert_eq!(store.load().unwrap(), Some(token()));
+
+        assert!(store.remove().unwrap());
+        assert!(!store.remove().unwrap());
+        assert_eq!(store.load().unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.json");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        TokenStore::File(path.clone()).save(&token()).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn encrypted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = encrypted(dir.path(), "correct horse");
+        store.save(&token()).unwrap();
+
+        let contents = fs::read_to_string(dir.path().join("token.enc")).unwrap();
+        assert!(!contents.contains("refresh"));
+        assert_eq!(store.load().unwrap(), Some(token()));
+
+        let err = encrypted(dir.path(), "wrong").load().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::TokenDecrypt)
+        ));
+    }
+
+    #[test]
+    fn migrates_json_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join("token.json");
+        TokenStore::File(json_path.clone()).save(&token()).unwrap();
+
+        let store = encrypted(dir.path(), "passphrase");
+        assert!(store.migrate_from(&json_path).unwrap());
+        assert!(!json_path.exists());
+        assert_eq!(store.load().unwrap(), Some(token()));
+        assert!(!store.migrate_from(&json_path).unwrap());
+
+        let file = TokenStore::File(json_path.clone());
+        file.save(&token()).unwrap();
+        assert!(!file.migrate_from(&json_path).unwrap());
+        assert!(json_path.exists());
+    }
+}
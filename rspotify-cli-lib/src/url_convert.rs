@@ -1,7 +1,10 @@
 //! Convert a Spotify URL to a PlayableId
 
 use anyhow::Result;
-use rspotify::model::Type;
+use rspotify::{
+    model::{parse_uri, AlbumId, ArtistId, EpisodeId, PlaylistId, ShowId, TrackId, Type},
+    prelude::PlayableId,
+};
 
 use crate::error::Error;
 
@@ -13,16 +16,17 @@ pub fn url_to_uri(url: &str) -> Result<String> {
         .trim_start_matches("http://");
 
     // remove the query string
-    let url = url.split('?').collect::<Vec<&str>>()[0];
+    let url = url.split_once('?').map_or(url, |(url, _)| url);
 
     // split the URL into parts
     let parts: Vec<&str> = url.split('/').collect();
 
-    if parts.len() != 3 {
-        anyhow::bail!(Error::InvalidURL);
-    }
-
-    let (domain, type_, id) = (parts[0], parts[1], parts[2]);
+    let (domain, type_, id) = match parts[..] {
+        [domain, type_, id] => (domain, type_, id),
+        // playlists used to be addressed under their owner
+        [domain, "user", _, "playlist", id] => (domain, "playlist", id),
+        _ => anyhow::bail!(Error::InvalidURL),
+    };
 
     if domain != "open.spotify.com" {
         anyhow::bail!(Error::InvalidURL);
@@ -34,8 +38,54 @@ pub fn url_to_uri(url: &str) -> Result<String> {
     }
 }
 
+/// Convert the URI of a playlist under its owner,
+/// `spotify:user:<owner>:playlist:<id>`, to a playlist URI, leaving any other
+/// URI as is
+pub fn normalize_uri(uri: &str) -> String {
+    match uri.split(':').collect::<Vec<_>>()[..] {
+        ["spotify", "user", _, "playlist", id] => format!("spotify:playlist:{id}"),
+        _ => uri.to_owned(),
+    }
+}
+
+/// Convert the URL or URI of anything that can be played to its URI, checking
+/// that its id is well formed
+pub fn to_playable_uri(item: &str) -> Result<String> {
+    let uri = if item.starts_with("spotify:") {
+        normalize_uri(item)
+    } else {
+        url_to_uri(item)?
+    };
+    match parse_uri(&uri)? {
+        (Type::Track, id) => drop(TrackId::from_id(id)?),
+        (Type::Episode, id) => drop(EpisodeId::from_id(id)?),
+        (Type::Album, id) => drop(AlbumId::from_id(id)?),
+        (Type::Artist, id) => drop(ArtistId::from_id(id)?),
+        (Type::Playlist, id) => drop(PlaylistId::from_id(id)?),
+        (Type::Show, id) => drop(ShowId::from_id(id)?),
+        _ => anyhow::bail!(Error::NotPlayable(uri)),
+    }
+    Ok(uri)
+}
+
+/// Convert the URL or URI of a track or episode to a PlayableId
+pub fn to_playable_id(item: &str) -> Result<PlayableId<'static>> {
+    let uri = if item.starts_with("spotify:") {
+        item.to_owned()
+    } else {
+        url_to_uri(item)?
+    };
+    match parse_uri(&uri)? {
+        (Type::Track, id) => Ok(PlayableId::from(TrackId::from_id(id)?.into_static())),
+        (Type::Episode, id) => Ok(PlayableId::from(EpisodeId::from_id(id)?.into_static())),
+        _ => anyhow::bail!(Error::NotPlayableInList(uri)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use rspotify::prelude::Id;
+
     use super::*;
 
     #[test]
@@ -80,6 +130,21 @@ mod tests {
         assert_eq!(id, "spotify:show:4cOdK2wGLETKBW3PvgPWqT");
     }
 
+    #[test]
+    fn user_playlist_type() {
+        let url = "https://open.spotify.com/user/spotify/playlist/37i9dQZF1DXcBWIGoYBM5M";
+        let id = url_to_uri(url).unwrap();
+        assert_eq!(id, "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M");
+    }
+
+    #[test]
+    fn user_playlist_uri() {
+        let uri = normalize_uri("spotify:user:spotify:playlist:37i9dQZF1DXcBWIGoYBM5M");
+        assert_eq!(uri, "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M");
+        let uri = normalize_uri("spotify:album:4aawyAB9vmqN3uQ7FjRGTy");
+        assert_eq!(uri, "spotify:album:4aawyAB9vmqN3uQ7FjRGTy");
+    }
+
     #[test]
     fn invalid_type() {
         let url = "https://open.spotify.com/invalid/4cOdK2wGLETKBW3PvgPWqT";
@@ -121,4 +186,30 @@ mod tests {
         let id = url_to_uri(url).unwrap();
         assert_eq!(id, "spotify:track:4cOdK2wGLETKBW3PvgPWqT");
     }
+
+    #[test]
+    fn playable_ids() {
+        let id = to_playable_id("https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ").unwrap();
+        assert_eq!(id.uri(), "spotify:episode:512ojhOuo1ktJprKbVcKyQ");
+        let id = to_playable_id("spotify:track:4cOdK2wGLETKBW3PvgPWqT").unwrap();
+        assert_eq!(id.uri(), "spotify:track:4cOdK2wGLETKBW3PvgPWqT");
+        assert!(to_playable_id("spotify:album:4aawyAB9vmqN3uQ7FjRGTy").is_err());
+        assert!(to_playable_id("spotify:track:not an id").is_err());
+        assert!(to_playable_id("some song").is_err());
+    }
+
+    #[test]
+    fn playable_uris() {
+        assert_eq!(
+            to_playable_uri("https://open.spotify.com/album/4aawyAB9vmqN3uQ7FjRGTy").unwrap(),
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy"
+        );
+        assert_eq!(
+            to_playable_uri("spotify:user:someone:playlist:37i9dQZF1DXcBWIGoYBM5M").unwrap(),
+            "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"
+        );
+        assert!(to_playable_uri("spotify:album:not an id").is_err());
+        assert!(to_playable_uri("spotify:user:someone").is_err());
+        assert!(to_playable_uri("some song").is_err());
+    }
 }
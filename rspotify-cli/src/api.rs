@@ -0,0 +1,532 @@
+//! Local HTTP API, so that dashboards and phone shortcuts can control the
+//! playback without the CLI.
+//!
+//! Every request must carry the `api_token` from the config as a bearer
+//! token. Requests are translated to the matching command line and run like
+//! the daemon runs them:
+//!
+//! - `GET /status`: the status as JSON
+//! - `POST /control/<action>`: any `control` flag without a value, like `next`
+//!   or `toggle-play`
+//! - `PUT /volume` with `{"volume": 30}`
+//! - `POST /play` with `{"uri": "spotify:track:..."}` or a URL
+//! - `GET /search?q=<query>&type=<type>&limit=<n>&offset=<n>`: the results as
+//!   JSON, searching tracks by default
+//! - `GET /ws`: a WebSocket pushing live updates, see [`crate::push`]. As
+//!   browsers can't set headers on WebSockets, the token may also be passed as
+//!   `?token=<token>`
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use hyper::{
+    body::HttpBody,
+    header::{
+        HeaderMap, HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE,
+        SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE, WWW_AUTHENTICATE,
+    },
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use rspotify_cli_lib::{
+    backend::PlayerBackend, currently_playing::CurrentlyPlaying, error::Error as LibError,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::{watch, Notify};
+
+use crate::{
+    cli::Cli,
+    dispatch::{dispatch, Settings},
+    error::Error,
+    push::push,
+    snapshot::{Polled, Poller, Snapshot},
+    websocket::accept_key,
+};
+
+/// Largest request body accepted.
+const MAX_BODY: usize = 16 * 1024;
+
+/// Kinds of items that can be searched.
+const SEARCH_TYPES: [&str; 6] = ["artist", "album", "track", "playlist", "show", "episode"];
+
+/// A request that could not be answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ApiError {
+    /// Status code of the response.
+    status: StatusCode,
+
+    /// Message sent back in the body.
+    message: String,
+}
+
+impl ApiError {
+    /// Create an error with a message.
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    /// Pick the status code for an error from a command.
+    fn from_command(err: &anyhow::Error) -> Self {
+        let status = match err.downcast_ref::<LibError>() {
+            Some(LibError::NoActiveDevice) => StatusCode::CONFLICT,
+            Some(LibError::MissingScope(_)) => StatusCode::FORBIDDEN,
+            _ if err.downcast_ref::<clap::Error>().is_some() => StatusCode::BAD_REQUEST,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        Self::new(status, format!("{err:#}"))
+    }
+
+    /// Build the JSON response.
+    fn response(&self) -> Response<Body> {
+        let mut response = json_response(self.status, json!({ "error": self.message }).to_string());
+        if self.status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Body of `PUT /volume`.
+#[derive(Debug, Deserialize)]
+struct VolumeBody {
+    /// Volume between 0 and 100.
+    volume: u8,
+}
+
+/// Body of `POST /play`.
+#[derive(Debug, Deserialize)]
+struct PlayBody {
+    /// URI or URL of what to play.
+    uri: String,
+}
+
+/// Build a response with a JSON body.
+fn json_response(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// Whether the `Authorization` header carries the token, compared in
+/// constant time.
+fn is_authorized(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|header| header.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Returns the `token` query parameter.
+fn query_token(query: Option<&str>) -> Option<String> {
+    url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Parse a JSON body.
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, ApiError> {
+    serde_json::from_slice(body)
+        .map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid body: {err}")))
+}
+
+/// Read a body of at most [`MAX_BODY`] bytes.
+///
+/// A larger `Content-Length` is rejected before anything is read, and the
+/// reading stops as soon as the body turns out larger than announced.
+async fn read_body(headers: &HeaderMap, mut body: Body) -> Result<Vec<u8>, ApiError> {
+    let too_large = || ApiError::new(StatusCode::PAYLOAD_TOO_LARGE, "Body too large");
+    let length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if length.is_some_and(|length| length > MAX_BODY) {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, err.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Translate a request to the arguments of the command answering it.
+fn route(
+    method: &Method,
+    path: &str,
+    query: Option<&str>,
+    body: &[u8],
+) -> Result<Vec<String>, ApiError> {
+    let not_allowed = || ApiError::new(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+    let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        ["status"] if method == Method::GET => Ok(vec!["status".into(), "--json".into()]),
+        ["control", action] if method == Method::POST => {
+            let is_flag = !action.is_empty()
+                && action.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+                && !matches!(*action, "help" | "seek" | "repeat" | "volume" | "shuffle");
+            if !is_flag {
+                return Err(ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    format!("Unknown action {action}"),
+                ));
+            }
+            Ok(vec!["control".into(), format!("--{action}")])
+        },
+        ["volume"] if method == Method::PUT => {
+            let VolumeBody { volume } = parse_body(body)?;
+            Ok(vec!["control".into(), format!("--volume={volume}")])
+        },
+        ["play"] if method == Method::POST => {
+            let PlayBody { uri } = parse_body(body)?;
+            let source = if uri.starts_with("http") {
+                "url"
+            } else {
+                "uri"
+            };
+            Ok(vec!["play-from".into(), format!("--{source}={uri}")])
+        },
+        ["search"] if method == Method::GET => {
+            let mut what = None;
+            let mut kind = "track".to_owned();
+            let mut args = vec!["search".to_owned()];
+            for (key, value) in url::form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+                match &*key {
+                    "q" => what = Some(value.into_owned()),
+                    "type" => kind = value.into_owned(),
+                    "limit" | "offset" => args.push(format!("--{key}={value}")),
+                    _ => {},
+                }
+            }
+            let what = what.ok_or_else(|| {
+                ApiError::new(StatusCode::BAD_REQUEST, "Missing query parameter q")
+            })?;
+            if !SEARCH_TYPES.contains(&kind.as_str()) {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Invalid type {kind}, expected one of {}",
+                        SEARCH_TYPES.join(", ")
+                    ),
+                ));
+            }
+            args.push(format!("--{kind}={what}"));
+            Ok(args)
+        },
+        ["status" | "volume" | "play" | "search"] | ["control", _] => Err(not_allowed()),
+        _ => Err(ApiError::new(StatusCode::NOT_FOUND, "Not found")),
+    }
+}
+
+/// State shared by every request.
+struct State {
+    /// Backend the commands are run with.
+    backend: Arc<dyn PlayerBackend>,
+
+    /// Settings the commands depend on.
+    settings: Settings,
+
+    /// Token clients must send.
+    token: String,
+
+    /// Playback polled for WebSocket clients, who subscribe to it.
+    playback: watch::Sender<Polled>,
+
+    /// Asks for the playback to be polled again right away.
+    refresh: Arc<Notify>,
+}
+
+impl State {
+    /// Run a command line against a freshly fetched playback, returning what
+    /// it printed.
+    async fn run(&self, args: &[String]) -> Result<String> {
+        let cli = Cli::try_parse_from(
+            std::iter::once("rspotify-cli").chain(args.iter().map(String::as_str)),
+        )?;
+        let curr = CurrentlyPlaying::from_backend(Arc::clone(&self.backend))
+            .await
+            .context(Error::Connect)?;
+
+        let mut out = Vec::new();
+        dispatch(
+            cli.command,
+            cli.device.as_deref(),
+            &curr,
+            &self.settings,
+            &mut out,
+        )
+        .await?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Switch a request for `/ws` to the WebSocket protocol, and push updates
+    /// once it is.
+    fn upgrade(&self, mut request: Request<Body>) -> Result<Response<Body>, ApiError> {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+        };
+        let is_websocket = header(UPGRADE)
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+            && header(SEC_WEBSOCKET_VERSION) == Some("13");
+        let Some(key) = header(SEC_WEBSOCKET_KEY).filter(|_| is_websocket) else {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "Expected a WebSocket handshake",
+            ));
+        };
+        let accept = HeaderValue::from_str(&accept_key(key))
+            .map_err(|err| ApiError::new(StatusCode::BAD_REQUEST, err.to_string()))?;
+
+        // only snapshots polled from now on are pushed
+        let playback = self.playback.subscribe();
+        self.refresh.notify_one();
+        let upgrade = hyper::upgrade::on(&mut request);
+        tokio::spawn(async move {
+            let result = match upgrade.await {
+                Ok(upgraded) => push(upgraded, playback).await,
+                Err(err) => Err(err.into()),
+            };
+            if let Err(err) = result {
+                eprintln!("WebSocket client went away: {err:#}");
+            }
+        });
+
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        let headers = response.headers_mut();
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.insert(SEC_WEBSOCKET_ACCEPT, accept);
+        Ok(response)
+    }
+
+    /// Answer a request.
+    async fn respond(&self, request: Request<Body>) -> Result<Response<Body>, ApiError> {
+        let is_websocket = request.uri().path() == "/ws";
+        let header = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        let authorized = is_authorized(header, &self.token)
+            || (is_websocket
+                && query_token(request.uri().query()).is_some_and(|token| {
+                    is_authorized(Some(&format!("Bearer {token}")), &self.token)
+                }));
+        if !authorized {
+            return Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid bearer token",
+            ));
+        }
+        if is_websocket {
+            return self.upgrade(request);
+        }
+
+        let (parts, body) = request.into_parts();
+        let body = read_body(&parts.headers, body).await?;
+
+        let args = route(&parts.method, parts.uri.path(), parts.uri.query(), &body)?;
+        let output = self
+            .run(&args)
+            .await
+            .map_err(|err| ApiError::from_command(&err))?;
+        if parts.method != Method::GET {
+            self.refresh.notify_one();
+        }
+
+        // commands that print nothing succeeded without content
+        Ok(if output.is_empty() {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NO_CONTENT;
+            response
+        } else {
+            json_response(StatusCode::OK, output.trim_end().to_owned())
+        })
+    }
+}
+
+/// Serve the API on `address` until interrupted, polling the playback every
+/// `interval` while WebSocket clients are connected.
+///
+/// # Errors
+///
+/// Returns an error if no token is set, or the address can't be listened on.
+pub async fn serve(
+    backend: Arc<dyn PlayerBackend>,
+    settings: &Settings,
+    address: SocketAddr,
+    interval: Duration,
+) -> Result<()> {
+    let token = settings
+        .api_token
+        .clone()
+        .filter(|token| !token.is_empty())
+        .context(Error::NoApiToken)?;
+    let (playback, _) = watch::channel(Polled {
+        snapshot: Snapshot::default(),
+        at: Instant::now(),
+    });
+    let refresh = Arc::new(Notify::new());
+    let poller = Poller::new(Arc::clone(&backend), interval, true);
+    tokio::spawn(poller.publish(playback.clone(), Arc::clone(&refresh)));
+
+    let state = Arc::new(State {
+        backend,
+        settings: settings.clone(),
+        token,
+        playback,
+        refresh,
+    });
+
+    let make_service = make_service_fn(move |_| {
+        let state = Arc::clone(&state);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let state = Arc::clone(&state);
+                async move {
+                    Ok::<_, Infallible>(
+                        state
+                            .respond(request)
+                            .await
+                            .unwrap_or_else(|err| err.response()),
+                    )
+                }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&address)?.serve(make_service);
+    println!("Listening on http://{}", server.local_addr());
+    server
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(method: &Method, target: &str, body: &str) -> Result<Vec<String>, StatusCode> {
+        let (path, query) = target
+            .split_once('?')
+            .map_or((target, None), |(path, query)| (path, Some(query)));
+        route(method, path, query, body.as_bytes()).map_err(|err| err.status)
+    }
+
+    #[tokio::test]
+    async fn body_limit() {
+        let read = |length: Option<usize>, body| async move {
+            let mut headers = HeaderMap::new();
+            if let Some(length) = length {
+                headers.insert(CONTENT_LENGTH, length.into());
+            }
+            read_body(&headers, body).await.map_err(|err| err.status)
+        };
+
+        assert_eq!(
+            read(Some(5), Body::from("{}...")).await,
+            Ok(b"{}...".to_vec())
+        );
+        assert_eq!(
+            read(Some(MAX_BODY + 1), Body::empty()).await,
+            Err(StatusCode::PAYLOAD_TOO_LARGE)
+        );
+
+        // without a length the body is read until it is too large
+        let (mut sender, body) = Body::channel();
+        let chunk = vec![b' '; MAX_BODY / 2 + 1];
+        tokio::spawn(async move { while sender.send_data(chunk.clone().into()).await.is_ok() {} });
+        assert_eq!(read(None, body).await, Err(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    #[test]
+    fn authorization() {
+        assert!(is_authorized(Some("Bearer secret"), "secret"));
+        assert!(!is_authorized(Some("Bearer secre"), "secret"));
+        assert!(!is_authorized(Some("secret"), "secret"));
+        assert!(!is_authorized(None, "secret"));
+    }
+
+    #[test]
+    fn routes() {
+        assert_eq!(
+            args(&Method::GET, "/status", ""),
+            Ok(vec!["status".into(), "--json".into()])
+        );
+        assert_eq!(
+            args(&Method::POST, "/control/toggle-play", ""),
+            Ok(vec!["control".into(), "--toggle-play".into()])
+        );
+        assert_eq!(
+            args(&Method::POST, "/control/help", ""),
+            Err(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            args(&Method::GET, "/control/next", ""),
+            Err(StatusCode::METHOD_NOT_ALLOWED)
+        );
+        assert_eq!(
+            args(&Method::PUT, "/volume", r#"{"volume": 30}"#),
+            Ok(vec!["control".into(), "--volume=30".into()])
+        );
+        assert_eq!(
+            args(&Method::PUT, "/volume", r#"{"volume": 300}"#),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            args(
+                &Method::POST,
+                "/play",
+                r#"{"uri": "https://open.spotify.com/track/a"}"#
+            ),
+            Ok(vec![
+                "play-from".into(),
+                "--url=https://open.spotify.com/track/a".into()
+            ])
+        );
+        assert_eq!(
+            args(&Method::GET, "/search?q=some%20song&limit=2", ""),
+            Ok(vec![
+                "search".into(),
+                "--limit=2".into(),
+                "--track=some song".into()
+            ])
+        );
+        assert_eq!(
+            args(&Method::GET, "/search?q=a&type=user", ""),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(args(&Method::GET, "/", ""), Err(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,49 @@
+//! Commands for managing the cached token.
+
+use std::io::Write;
+
+use anyhow::Result;
+use rspotify_cli_lib::{
+    auth::{force_refresh, token_status},
+    init_spotify::{build_spotify, ClientConfig},
+};
+
+use crate::cli::AuthCommands;
+
+/// Run an auth command against the cached token of the current profile.
+pub async fn auth_command(
+    command: AuthCommands,
+    client_config: ClientConfig,
+    out: &mut impl Write,
+) -> Result<()> {
+    let store = client_config.token_store.clone();
+    let spotify = build_spotify(client_config)?;
+
+    match command {
+        AuthCommands::Status => {
+            let status = token_status(&spotify, &store).await?;
+            match status.display_name {
+                Some(name) => writeln!(out, "Logged in as {name} ({})", status.user_id)?,
+                None => writeln!(out, "Logged in as {}", status.user_id)?,
+            }
+            writeln!(out, "Scopes: {}", status.scopes.join(" "))?;
+            match status.expires_at {
+                Some(expires_at) => writeln!(out, "Expires at: {expires_at}")?,
+                None => writeln!(out, "Expires at: unknown")?,
+            }
+        },
+        AuthCommands::Refresh => match force_refresh(&spotify, &store).await? {
+            Some(expires_at) => writeln!(out, "Refreshed the token, it expires at {expires_at}")?,
+            None => writeln!(out, "Refreshed the token")?,
+        },
+        AuthCommands::Logout => {
+            if store.remove()? {
+                writeln!(out, "Logged out")?;
+            } else {
+                writeln!(out, "Not logged in")?;
+            }
+        },
+    }
+
+    Ok(())
+}
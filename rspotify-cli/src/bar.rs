@@ -0,0 +1,232 @@
+//! Status output for status bars.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use rspotify_cli_lib::currently_playing::CurrentlyPlaying;
+
+use crate::{snapshot::Snapshot, template::Template};
+
+/// Text shown in the bar unless `--format` is given.
+pub const DEFAULT_BAR_FORMAT: &str = "{artist} - {title}{liked? ♥}";
+
+/// Tooltip shown by bars that support one.
+const TOOLTIP_FORMAT: &str = "{title}\n{artist}\n{device} {progress}/{duration}";
+
+/// Color of the icon while playing.
+const PLAYING_COLOR: &str = "#1db954";
+
+/// Color of the icon while paused.
+const PAUSED_COLOR: &str = "#808080";
+
+/// Status bar to format the status for.
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Bar {
+    /// JSON for a waybar custom module with `return-type` set to `json`.
+    Waybar,
+
+    /// A line with polybar formatting tags.
+    Polybar,
+
+    /// The full text, short text and color lines of an i3blocks block.
+    I3blocks,
+
+    /// A line with tmux style markup, for `status-right`.
+    Tmux,
+}
+
+/// Whether anything is playing.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+enum State {
+    /// An item is playing.
+    Playing,
+
+    /// An item is paused.
+    Paused,
+
+    /// There is no active device or nothing is loaded.
+    Stopped,
+}
+
+impl State {
+    /// Returns the state of a snapshot.
+    const fn of(snapshot: &Snapshot) -> Self {
+        match snapshot.is_playing {
+            _ if snapshot.title.is_none() => Self::Stopped,
+            Some(true) => Self::Playing,
+            _ => Self::Paused,
+        }
+    }
+
+    /// Returns the CSS class of the state.
+    const fn class(self) -> &'static str {
+        match self {
+            Self::Playing => "playing",
+            Self::Paused => "paused",
+            Self::Stopped => "stopped",
+        }
+    }
+
+    /// Returns the icon shown before the text.
+    const fn icon(self) -> &'static str {
+        match self {
+            Self::Playing => "▶",
+            Self::Paused | Self::Stopped => "⏸",
+        }
+    }
+
+    /// Returns the color of the icon.
+    const fn color(self) -> &'static str {
+        match self {
+            Self::Playing => PLAYING_COLOR,
+            Self::Paused | Self::Stopped => PAUSED_COLOR,
+        }
+    }
+}
+
+/// Render a snapshot for a status bar, with `text` as the main text.
+///
+/// When nothing is playing the output hides the module in every bar.
+pub fn render_bar(bar: Bar, snapshot: &Snapshot, text: &Template) -> Result<String> {
+    let state = State::of(snapshot);
+    let stopped = state == State::Stopped;
+    let text = if stopped {
+        String::new()
+    } else {
+        text.render(snapshot)
+    };
+
+    Ok(match bar {
+        Bar::Waybar => {
+            let mut class = vec![state.class()];
+            if snapshot.is_liked == Some(true) {
+                class.push("liked");
+            }
+            let tooltip = if stopped {
+                String::new()
+            } else {
+                Template::parse(TOOLTIP_FORMAT)?.render(snapshot)
+            };
+
+            serde_json::json!({
+                "text": escape_pango(&text),
+                "tooltip": escape_pango(&tooltip),
+                "alt": state.class(),
+                "class": class,
+            })
+            .to_string()
+        },
+        Bar::Polybar if stopped => String::new(),
+        Bar::Polybar => format!(
+            "%{{F{}}}{}%{{F-}} {}",
+            state.color(),
+            state.icon(),
+            text.replace('%', "%%")
+        ),
+        Bar::I3blocks if stopped => String::new(),
+        Bar::I3blocks => format!(
+            "{} {text}\n{}\n{}",
+            state.icon(),
+            snapshot.title.as_deref().unwrap_or_default(),
+            state.color()
+        ),
+        Bar::Tmux if stopped => String::new(),
+        Bar::Tmux => format!(
+            "#[fg={}]{}#[default] {}",
+            state.color(),
+            state.icon(),
+            text.replace('#', "##")
+        ),
+    })
+}
+
+/// Render the current playback for a status bar, with the text from a
+/// template or a named format.
+pub async fn render_bar_status(
+    bar: Bar,
+    curr: &CurrentlyPlaying,
+    format: Option<&str>,
+    formats: &HashMap<String, String>,
+) -> Result<String> {
+    let text = Template::named(format.unwrap_or(DEFAULT_BAR_FORMAT), formats)?;
+    render_bar(bar, &Snapshot::capture(curr, true).await?, &text)
+}
+
+/// Escape text for Pango markup, which waybar uses for the text and tooltip.
+fn escape_pango(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            title: Some("Rock & Roll".to_owned()),
+            artist: Some("Artist".to_owned()),
+            progress: Some(Duration::seconds(61)),
+            duration: Some(Duration::seconds(200)),
+            is_playing: Some(true),
+            device: Some("Laptop".to_owned()),
+            is_liked: Some(true),
+            ..Default::default()
+        }
+    }
+
+    fn render(bar: Bar, snapshot: &Snapshot) -> String {
+        render_bar(bar, snapshot, &Template::parse(DEFAULT_BAR_FORMAT).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn waybar() {
+        let json: serde_json::Value =
+            serde_json::from_str(&render(Bar::Waybar, &snapshot())).unwrap();
+        assert_eq!(json["text"], "Artist - Rock &amp; Roll ♥");
+        assert_eq!(json["tooltip"], "Rock &amp; Roll\nArtist\nLaptop 1:01/3:20");
+        assert_eq!(json["alt"], "playing");
+        assert_eq!(json["class"], serde_json::json!(["playing", "liked"]));
+
+        let paused = Snapshot {
+            is_playing: Some(false),
+            is_liked: Some(false),
+            ..snapshot()
+        };
+        let json: serde_json::Value = serde_json::from_str(&render(Bar::Waybar, &paused)).unwrap();
+        assert_eq!(json["class"], serde_json::json!(["paused"]));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(Bar::Waybar, &Snapshot::default())).unwrap();
+        assert_eq!(json["text"], "");
+        assert_eq!(json["class"], serde_json::json!(["stopped"]));
+    }
+
+    #[test]
+    fn text_bars() {
+        let snapshot = Snapshot {
+            title: Some("100% #1".to_owned()),
+            ..snapshot()
+        };
+        assert_eq!(
+            render(Bar::Polybar, &snapshot),
+            "%{F#1db954}▶%{F-} Artist - 100%% #1 ♥"
+        );
+        assert_eq!(
+            render(Bar::I3blocks, &snapshot),
+            "▶ Artist - 100% #1 ♥\n100% #1\n#1db954"
+        );
+        assert_eq!(
+            render(Bar::Tmux, &snapshot),
+            "#[fg=#1db954]▶#[default] Artist - 100% ##1 ♥"
+        );
+
+        for bar in [Bar::Polybar, Bar::I3blocks, Bar::Tmux] {
+            assert_eq!(render(bar, &Snapshot::default()), "");
+        }
+    }
+}
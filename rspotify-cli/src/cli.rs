@@ -1,8 +1,37 @@
 //! The command line interface for rspotify-cli.
 
-use clap::{value_parser, Parser, Subcommand};
+use std::{net::SocketAddr, path::PathBuf};
+
+use chrono::Duration;
+use clap::{value_parser, ArgGroup, Parser, Subcommand};
+use rspotify::model::Offset;
 use rspotify_cli_lib::repeat_state::RepeatState;
 
+use crate::{
+    bar::Bar,
+    play_search::SearchKind,
+    position::{parse_offset, parse_start, Position},
+};
+
+/// Status flags that print a single field, which can't be combined with
+/// `--bar` or `--watch`.
+const FIELD_FLAGS: [&str; 14] = [
+    "json",
+    "id",
+    "url",
+    "uri",
+    "title",
+    "artist",
+    "progress",
+    "duration",
+    "is_playing",
+    "repeat_state",
+    "is_shuffled",
+    "show_device",
+    "playing_type",
+    "is_liked",
+];
+
 /// The CLI.
 #[derive(Debug, Parser, Clone)]
 #[command(
@@ -12,6 +41,21 @@ use rspotify_cli_lib::repeat_state::RepeatState;
     about = clap::crate_description!(),
 )]
 pub struct Cli {
+    /// Account profile to use instead of the default one.
+    #[arg(
+        long,
+        global = true,
+        env = "RSPOTIFY_PROFILE",
+        value_name = "NAME",
+        display_order = 100
+    )]
+    pub profile: Option<String>,
+
+    /// Control or play on this device instead of the active one, by id or by
+    /// name, which may be abbreviated.
+    #[arg(long, global = true, value_name = "DEVICE", display_order = 100)]
+    pub device: Option<String>,
+
     /// CLI Commands.
     #[command(subcommand)]
     pub command: Commands,
@@ -22,174 +66,250 @@ pub struct Cli {
 pub enum Commands {
     /// Print the current status.
     /// The API quickly forgets the song if it has not been playing for a while.
-    #[command()]
+    #[command(group(ArgGroup::new("display").multiple(false)))]
     Status {
         /// Print the full status in json to be used for external parsing.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "display")]
         json: bool,
 
         /// Print the id.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         id: bool,
 
         /// Print the url.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         url: bool,
 
         /// Print the uri.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         uri: bool,
 
         /// Print the title.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         title: bool,
 
         /// Print the artist name.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         artist: bool,
 
         /// Print the progress.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         progress: bool,
 
         /// Print the duration.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         duration: bool,
 
         /// Print if the song is currently playing.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         is_playing: bool,
 
         /// Print the repeat_state.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         repeat_state: bool,
 
         /// Print if it is shuffled.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         is_shuffled: bool,
 
         /// Print the device name.
-        #[arg(long, help_heading = "Display", exclusive = true)]
-        device: bool,
+        #[arg(long, help_heading = "Display", group = "display")]
+        show_device: bool,
 
         /// Print the playing type.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         playing_type: bool,
 
         /// Print if the song is liked.
-        #[arg(long, help_heading = "Display", exclusive = true)]
+        #[arg(long, help_heading = "Display", group = "display")]
         is_liked: bool,
+
+        /// Print the status with a template like `{artist} - {title}
+        /// {liked?♥:♡}`, or a named format from the config.
+        #[arg(long, group = "display", value_name = "TEMPLATE")]
+        format: Option<String>,
+
+        /// Print the status for a status bar, with the text from `--format`
+        /// if given.
+        #[arg(
+            long,
+            value_name = "BAR",
+            conflicts_with_all = FIELD_FLAGS
+        )]
+        bar: Option<Bar>,
+
+        /// Keep printing the status whenever it changes, with `--format` or
+        /// `--bar`.
+        #[arg(long, conflicts_with_all = FIELD_FLAGS)]
+        watch: bool,
+
+        /// Seconds between polls of the playback in watch mode.
+        #[arg(
+            long,
+            requires = "watch",
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
     },
 
     /// Control the current playback.
-    #[command(arg_required_else_help = true)]
+    #[command(
+        arg_required_else_help = true,
+        group(ArgGroup::new("action").multiple(false).required(true))
+    )]
     Control {
         /// Play the song if it was previously paused.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         play: bool,
 
         /// Pause the song if it was previously playing.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         pause: bool,
 
         /// Toggle the state of the song between playing and paused.
-        #[arg(long = "toggle-play", exclusive = true)]
+        #[arg(long = "toggle-play", group = "action")]
         toggle_play_pause: bool,
 
         /// Like the current song.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         like: bool,
 
         /// Unlike the current song.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         unlike: bool,
 
         /// Toggle like/unlike for the current song.
-        #[arg(long = "toggle-like", exclusive = true)]
+        #[arg(long = "toggle-like", group = "action")]
         toggle_like_unlike: bool,
 
         /// Go to the previous song.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         previous: bool,
 
         /// Go to the next song.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         next: bool,
 
         /// Set the repeat state.
-        #[arg(long, exclusive = true, value_name = "STATE")]
+        #[arg(long, group = "action", value_name = "STATE")]
         repeat: Option<RepeatState>,
 
         /// Cycle between repeat states.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         cycle_repeat: bool,
 
         /// Set the volume.
-        #[arg(long, exclusive = true, value_parser = value_parser!(u8).range(0..=100))]
+        #[arg(long, group = "action", value_parser = value_parser!(u8).range(0..=100))]
         volume: Option<u8>,
 
         /// Increase volume by a set amount.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         volume_up: bool,
 
         /// Decrease volume by a set amount.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         volume_down: bool,
 
         /// Set the shuffle state.
-        #[arg(long, exclusive = true, value_name = "STATE")]
+        #[arg(long, group = "action", value_name = "STATE")]
         shuffle: Option<bool>,
 
         /// Toggle the shuffle state.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         toggle_shuffle: bool,
 
-        /// Seek to a location in the current song in seconds.
-        #[arg(long, exclusive = true, value_name = "POSITION")]
-        seek: Option<u8>,
+        /// Seek in the current item, to a time like 90 or 1:23 or 1:02:03, by
+        /// an offset like +15s or -30, or to a percentage like 50%.
+        #[arg(
+            long,
+            group = "action",
+            value_name = "POSITION",
+            allow_hyphen_values = true
+        )]
+        seek: Option<Position>,
 
         /// Replay the current song.
-        #[arg(long, exclusive = true)]
+        #[arg(long, group = "action")]
         replay: bool,
     },
 
     /// Play songs.
-    #[command(arg_required_else_help = true)]
+    #[command(
+        arg_required_else_help = true,
+        group(ArgGroup::new("source").multiple(false).required(true))
+    )]
     PlayFrom {
-        /// Play a track given a URL.
-        #[arg(long, exclusive = true)]
-        url: Option<String>,
-
-        /// Play a track given a URI.
-        #[arg(long, exclusive = true)]
-        uri: Option<String>,
+        /// Play a track, episode, album, artist, playlist or show given a URL,
+        /// or several tracks and episodes as one list.
+        #[arg(long, group = "source", num_args = 1..)]
+        url: Option<Vec<String>>,
+
+        /// Play a track, episode, album, artist, playlist or show given a URI,
+        /// or several tracks and episodes as one list.
+        #[arg(long, group = "source", num_args = 1..)]
+        uri: Option<Vec<String>>,
+
+        /// Play the tracks and episodes of a file, or of standard input if
+        /// it is `-`, one URL or URI per line, skipping blank lines and lines
+        /// starting with `#`.
+        #[arg(long, value_name = "PATH", group = "source")]
+        from_file: Option<PathBuf>,
+
+        /// Play the top result of a search.
+        #[arg(long, group = "source", value_name = "QUERY")]
+        search: Option<String>,
+
+        /// Kind of item to search for.
+        #[arg(
+            long = "type",
+            value_name = "TYPE",
+            value_enum,
+            default_value_t = SearchKind::Track,
+            requires = "search"
+        )]
+        kind: SearchKind,
+
+        /// Pick one of the results of the search instead of the top one.
+        #[arg(long, requires = "search")]
+        pick: bool,
+
+        /// Start at this item of an album or playlist, by its index from 0 or
+        /// its URI.
+        #[arg(long, value_name = "N|URI", value_parser = parse_offset)]
+        offset: Option<Offset>,
+
+        /// Start this far into the first item, like 90 or 1:23.
+        #[arg(long, value_name = "POSITION", value_parser = parse_start)]
+        position: Option<Duration>,
     },
 
     /// Search anything.
-    #[command(arg_required_else_help = true)]
+    #[command(arg_required_else_help = true, group(ArgGroup::new("filter").multiple(false)))]
     Search {
         /// Search for artists.
-        #[arg(long, help_heading = "Filters", exclusive = true)]
+        #[arg(long, help_heading = "Filters", group = "filter")]
         artist: Option<String>,
 
         /// Search for albums.
-        #[arg(long, help_heading = "Filters", exclusive = true)]
+        #[arg(long, help_heading = "Filters", group = "filter")]
         album: Option<String>,
 
         /// Search for tracks.
-        #[arg(long, help_heading = "Filters", exclusive = true)]
+        #[arg(long, help_heading = "Filters", group = "filter")]
         track: Option<String>,
 
         /// Search for playlists.
-        #[arg(long, help_heading = "Filters", exclusive = true)]
+        #[arg(long, help_heading = "Filters", group = "filter")]
         playlist: Option<String>,
 
         /// Search for shows.
-        #[arg(long, help_heading = "Filters", exclusive = true)]
+        #[arg(long, help_heading = "Filters", group = "filter")]
         show: Option<String>,
 
         /// Search for episodes.
-        #[arg(long, help_heading = "Filters", exclusive = true)]
+        #[arg(long, help_heading = "Filters", group = "filter")]
         episode: Option<String>,
 
         /// Limit the number of results.
@@ -200,4 +320,238 @@ pub enum Commands {
         #[arg(long, default_value_t = 1)]
         offset: u32,
     },
+
+    /// List the devices that can be played on, or move the playback to one.
+    Devices {
+        /// Devices command.
+        #[command(subcommand)]
+        command: DevicesCommands,
+    },
+
+    /// Show or add to the items that play next.
+    Queue {
+        /// Queue command.
+        #[command(subcommand)]
+        command: QueueCommands,
+    },
+
+    /// Print a line of JSON for every change in the playback.
+    /// Each event has the values before and after the change and a timestamp.
+    Events {
+        /// Seconds between polls of the playback.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+
+    /// Keep the client authorized in the background and answer commands
+    /// over a Unix socket.
+    /// Status, control, play-from and search use the daemon when it is running.
+    Daemon {
+        /// Seconds to reuse the fetched playback for before fetching it again.
+        #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+        cache: u64,
+    },
+
+    /// Run the hooks from the config whenever the playback changes.
+    /// Details about the playback are passed in RSPOTIFY_* environment
+    /// variables.
+    Hooks {
+        /// Seconds between polls of the playback.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+
+    /// Serve a local HTTP API for controlling the playback, with live
+    /// updates over a WebSocket at /ws. Requests need the api_token from the
+    /// config as a bearer token.
+    Api {
+        /// Address and port to listen on.
+        #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+
+        /// Seconds between polls of the playback while WebSocket clients are
+        /// connected.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+
+    /// Serve the MPD protocol, so that MPD clients like mpc and ncmpcpp can
+    /// control the playback. The current item is shown as a queue of one
+    /// song.
+    Mpd {
+        /// Address and port to listen on.
+        #[arg(long, value_name = "ADDRESS", default_value = "127.0.0.1:6600")]
+        listen: SocketAddr,
+
+        /// Seconds between polls of the playback.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+
+    /// Control the playback over MPRIS on the D-Bus session bus, for media
+    /// keys, playerctl and desktop widgets. Linux only.
+    Mpris {
+        /// Seconds between polls of the playback.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+
+    /// Open a full-screen interface with the playback, search, queue and
+    /// devices.
+    #[command(after_help = format!("Keys: {}", crate::tui::KEYS))]
+    Tui {
+        /// Seconds between polls of the playback.
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = 5,
+            value_parser = value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+
+    /// Authorize rspotify-cli to use your Spotify account.
+    /// The redirect is captured on a local listener on the redirect URI's port.
+    #[command(args_conflicts_with_subcommands = true)]
+    Auth {
+        /// Inspect, refresh or remove the cached token instead.
+        #[command(subcommand)]
+        command: Option<AuthCommands>,
+
+        /// Print the authorization URL instead of opening it in a browser.
+        #[arg(long, conflicts_with = "refresh_token")]
+        no_browser: bool,
+
+        /// Authorize with a refresh token that was obtained elsewhere.
+        #[arg(long, value_name = "TOKEN")]
+        refresh_token: Option<String>,
+    },
+
+    /// Manage account profiles.
+    /// Each profile has its own credentials, cached token and settings.
+    #[command()]
+    Profile {
+        /// Profile command.
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+}
+
+/// Commands for the devices that can be played on.
+#[derive(Debug, Subcommand, Clone)]
+pub enum DevicesCommands {
+    /// List every device with its id, type, volume and whether it is active
+    /// or restricted.
+    List {
+        /// Print the devices in json.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Move the playback to a device.
+    Transfer {
+        /// Id or name of the device, which may be abbreviated.
+        device: String,
+
+        /// Start playing on the device, instead of keeping the current state.
+        #[arg(long)]
+        play: bool,
+    },
+}
+
+/// Commands for the items that play next.
+#[derive(Debug, Subcommand, Clone)]
+pub enum QueueCommands {
+    /// Show the current item and the upcoming ones, with the time left until
+    /// the queue is played through.
+    Show {
+        /// Print the queue in json.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Add a track or episode to the end of the queue.
+    #[command(group(ArgGroup::new("source").required(true)))]
+    Add {
+        /// URI or URL of a track or episode, or anything else to queue the
+        /// top track found by searching for it.
+        #[arg(group = "source")]
+        item: Option<String>,
+
+        /// Queue every line of a file, or of standard input if it is `-`,
+        /// skipping blank lines and lines starting with `#`.
+        #[arg(long, value_name = "PATH", group = "source")]
+        from_file: Option<PathBuf>,
+    },
+}
+
+/// Commands for managing the cached token.
+#[derive(Debug, Subcommand, Clone, Copy)]
+pub enum AuthCommands {
+    /// Show the logged in user, the granted scopes and when the token expires.
+    Status,
+
+    /// Refresh the token even if it has not expired yet.
+    Refresh,
+
+    /// Remove the cached token of the current profile.
+    Logout,
+}
+
+/// Commands for managing account profiles.
+#[derive(Debug, Subcommand, Clone)]
+pub enum ProfileCommands {
+    /// List every profile, marking the default one with `*`.
+    List,
+
+    /// Add a profile.
+    Add {
+        /// Name of the profile.
+        name: String,
+
+        /// Client id for the Spotify API.
+        #[arg(long)]
+        client_id: Option<String>,
+
+        /// Client secret for the Spotify API, not needed when using PKCE.
+        #[arg(long)]
+        client_secret: Option<String>,
+    },
+
+    /// Remove a profile along with its config and cached token.
+    Remove {
+        /// Name of the profile.
+        name: String,
+    },
+
+    /// Set the profile used when `--profile` is not given.
+    Default {
+        /// Name of the profile.
+        name: String,
+    },
 }
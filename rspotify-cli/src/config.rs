@@ -1,23 +1,25 @@
 //! Configuration for the CLI.
 
 use std::{
+    collections::HashMap,
     fs::{create_dir_all, OpenOptions},
     path::PathBuf,
 };
 
 use anyhow::{Context, Result};
 use home::home_dir;
+use rspotify_cli_lib::{
+    init_spotify::{AuthMode, DEFAULT_API_BASE_URL, DEFAULT_AUTH_BASE_URL},
+    token_store::TokenStoreKind,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::error::Error;
+use crate::{error::Error, hooks::Hooks};
 
 /// File types stored in the config directory.
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum ConfigFile {
-    /// Token file.
-    Token,
-
     /// Config file.
     Config,
 }
@@ -28,18 +30,51 @@ pub struct Config {
     /// Client id for the Spotify API.
     pub client_id: String,
 
-    /// Client secret for the Spotify API.
-    pub client_secret: String,
+    /// Client secret for the Spotify API, not needed when using PKCE.
+    pub client_secret: Option<String>,
+
+    /// How to authorize with Spotify, either `auth-code` or `pkce`.
+    #[serde(default)]
+    pub auth_mode: AuthMode,
 
     /// Redirect URI for the Spotify API.
     pub redirect_uri: String,
 
+    /// Where to keep the token, either `file`, `keyring` or `encrypted-file`.
+    #[serde(default)]
+    pub token_store: TokenStoreKind,
+
+    /// Scopes to request, as presets (`read-only`, `playback`, `library` or
+    /// `full`) or individual scope names, `playback` by default. Commands
+    /// needing more offer to authorize again with the missing scope.
+    pub scopes: Vec<String>,
+
     /// Volume increment for the volume increment and decrement commands.
     pub volume_increment: u8,
+
+    /// Named templates for `status --format`.
+    #[serde(default)]
+    pub formats: HashMap<String, String>,
+
+    /// Commands to run on playback events.
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    /// Bearer token required by `rspotify-cli api`.
+    pub api_token: Option<String>,
+
+    /// Base URL of the Spotify Web API.
+    pub api_base_url: String,
+
+    /// Base URL of the Spotify accounts service used for authorization.
+    pub accounts_base_url: String,
 }
 
-/// Get a config file path from the config directory.
-pub fn get_config_path(file_name: ConfigFile) -> Result<PathBuf> {
+/// Name of the profile stored directly in the config directory.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Get the config directory, creating it if it does not exist.
+pub fn get_config_dir() -> Result<PathBuf> {
     let config_dir = match std::env::var("XDG_CONFIG_HOME") {
         Ok(path) => PathBuf::from(path),
         Err(_) => home_dir().context(Error::Config)?.join(".config"),
@@ -50,8 +85,43 @@ pub fn get_config_path(file_name: ConfigFile) -> Result<PathBuf> {
         create_dir_all(config_dir.clone())?;
     }
 
-    let config_file = config_dir.join(match file_name {
-        ConfigFile::Token => "token.json",
+    Ok(config_dir)
+}
+
+/// Get the directory a profile is stored in, whether or not it exists.
+///
+/// The default profile lives directly in the config directory, so configs
+/// from before profiles existed keep working. Other profiles live in
+/// `profiles/<name>`.
+pub fn profile_dir_path(profile: &str) -> Result<PathBuf> {
+    let config_dir = get_config_dir()?;
+    if profile == DEFAULT_PROFILE {
+        return Ok(config_dir);
+    }
+
+    let valid = profile
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if profile.is_empty() || !valid {
+        anyhow::bail!(Error::InvalidProfileName(profile.to_owned()));
+    }
+
+    Ok(config_dir.join("profiles").join(profile))
+}
+
+/// Get the directory of an existing profile.
+pub fn get_profile_dir(profile: &str) -> Result<PathBuf> {
+    let profile_dir = profile_dir_path(profile)?;
+    if !profile_dir.is_dir() {
+        anyhow::bail!(Error::UnknownProfile(profile.to_owned()));
+    }
+
+    Ok(profile_dir)
+}
+
+/// Get a config file path from the directory of a profile.
+pub fn get_config_path(profile: &str, file_name: ConfigFile) -> Result<PathBuf> {
+    let config_file = get_profile_dir(profile)?.join(match file_name {
         ConfigFile::Config => "config.toml",
     });
 
@@ -66,13 +136,16 @@ pub fn get_config_path(file_name: ConfigFile) -> Result<PathBuf> {
     Ok(config_file)
 }
 
-/// Load config from the config file
-pub fn load_config() -> Result<Config> {
-    let config_file = get_config_path(ConfigFile::Config)?;
+/// Load config from the config file of a profile
+pub fn load_config(profile: &str) -> Result<Config> {
+    let config_file = get_config_path(profile, ConfigFile::Config)?;
 
     let config = config::Config::builder()
         .set_default("redirect_uri", "http://localhost:8000/callback")?
+        .set_default("scopes", vec!["playback"])?
         .set_default("volume_increment", 10)?
+        .set_default("api_base_url", DEFAULT_API_BASE_URL)?
+        .set_default("accounts_base_url", DEFAULT_AUTH_BASE_URL)?
         .add_source(config::File::from(config_file.clone()))
         .add_source(config::Environment::with_prefix("SPOTIFY"))
         .build()?
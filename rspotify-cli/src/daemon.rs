@@ -0,0 +1,326 @@
+//! Background daemon that keeps the client authorized and serves commands
+//! over a Unix socket.
+//!
+//! A request is one line of JSON with the command line arguments, and the
+//! response is one line of JSON with the output or the error.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{cli::Commands, config::get_profile_dir};
+
+/// Name of the socket in the profile directory.
+const SOCKET_NAME: &str = "daemon.sock";
+
+/// Request sent to the daemon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Request {
+    /// Command line arguments, without the program name.
+    args: Vec<String>,
+}
+
+/// Response sent back by the daemon.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Response {
+    /// What the command printed.
+    output: String,
+
+    /// Error the command failed with, if any.
+    error: Option<String>,
+}
+
+/// Get the path of the socket of a profile's daemon.
+pub fn socket_path(profile: &str) -> Result<PathBuf> {
+    Ok(get_profile_dir(profile)?.join(SOCKET_NAME))
+}
+
+/// Whether the daemon can run a command.
+pub const fn serves(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Status { watch: false, .. }
+            | Commands::Control { .. }
+            | Commands::PlayFrom {
+                pick: false,
+                from_file: None,
+                ..
+            }
+            | Commands::Search { .. }
+    )
+}
+
+#[cfg(not(unix))]
+pub use fallback::{forward, serve};
+#[cfg(unix)]
+pub use unix::{forward, serve};
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        ffi::OsString,
+        io::Write,
+        os::unix::fs::{DirBuilderExt, PermissionsExt},
+        path::Path,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use anyhow::{Context, Result};
+    use clap::Parser;
+    use rspotify_cli_lib::{
+        backend::PlayerBackend, currently_playing::CurrentlyPlaying, error::Error as LibError,
+    };
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{UnixListener, UnixStream},
+        sync::Mutex,
+    };
+
+    use super::{Request, Response};
+    use crate::{
+        cli::{Cli, Commands},
+        dispatch::{dispatch, idle_output, Settings},
+        error::Error,
+    };
+
+    /// How long the daemon waits for a client to send its request.
+    const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Playback fetched by the daemon, and when.
+    type Cache = Option<(Instant, Arc<CurrentlyPlaying>)>;
+
+    /// State shared by every client.
+    struct State {
+        /// Backend the commands are run with.
+        backend: Arc<dyn PlayerBackend>,
+
+        /// Settings the commands depend on.
+        settings: Settings,
+
+        /// How long the cached playback is used for.
+        max_age: Duration,
+
+        /// Playback shared by the commands, fetched again once too old.
+        cache: Mutex<Cache>,
+    }
+
+    /// Send the command line arguments to the daemon listening on `socket`,
+    /// and return what the command printed.
+    ///
+    /// Returns `None` if no daemon is running.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the daemon could not be talked to, or the command
+    /// failed.
+    pub async fn forward(socket: &Path, args: Vec<OsString>) -> Result<Option<String>> {
+        let Ok(stream) = UnixStream::connect(socket).await else {
+            return Ok(None);
+        };
+        let (reader, mut writer) = stream.into_split();
+
+        let request = Request {
+            args: args
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        };
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+
+        let mut line = String::new();
+        BufReader::new(reader).read_line(&mut line).await?;
+        let response: Response = serde_json::from_str(&line).context(Error::DaemonResponse)?;
+
+        match response.error {
+            Some(error) => anyhow::bail!(Error::Daemon(error)),
+            None => Ok(Some(response.output)),
+        }
+    }
+
+    /// Serve commands on `socket` until interrupted.
+    ///
+    /// The playback is fetched at most once every `max_age`, and again after
+    /// every command that changes it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket can't be created, or a daemon is already
+    /// running.
+    pub async fn serve(
+        backend: Arc<dyn PlayerBackend>,
+        settings: &Settings,
+        socket: &Path,
+        max_age: Duration,
+    ) -> Result<()> {
+        if UnixStream::connect(socket).await.is_ok() {
+            anyhow::bail!(Error::DaemonRunning(socket.display().to_string()));
+        }
+        // a socket left behind by a daemon that did not exit cleanly
+        if socket.exists() {
+            std::fs::remove_file(socket)?;
+        }
+
+        let listener = bind(socket)?;
+        println!("Listening on {}", socket.display());
+
+        let state = Arc::new(State {
+            backend,
+            settings: settings.clone(),
+            max_age,
+            cache: Mutex::new(None),
+        });
+        let result = tokio::select! {
+            result = accept_loop(&listener, &state) => result,
+            result = tokio::signal::ctrl_c() => result.map_err(Into::into),
+        };
+
+        std::fs::remove_file(socket)?;
+        result
+    }
+
+    /// Create a socket at `path` that only its owner can connect to.
+    ///
+    /// The socket is bound in a directory only the owner can enter, and moved
+    /// to `path` once its own permissions are restricted, so that nobody else
+    /// can connect in between.
+    fn bind(path: &Path) -> Result<UnixListener> {
+        let dir = path.with_extension(format!("{}.d", std::process::id()));
+        std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let bound = dir.join("socket");
+        let listener = UnixListener::bind(&bound).and_then(|listener| {
+            std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&bound, path)?;
+            Ok(listener)
+        });
+        let _ = std::fs::remove_file(&bound);
+        std::fs::remove_dir(&dir)?;
+        Ok(listener?)
+    }
+
+    /// Answer every client in its own task.
+    async fn accept_loop(listener: &UnixListener, state: &Arc<State>) -> Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let state = Arc::clone(state);
+            tokio::spawn(async move {
+                if let Err(err) = handle(stream, &state).await {
+                    eprintln!("Unable to answer a client: {err:#}");
+                }
+            });
+        }
+    }
+
+    /// Read a request, run it and write the response.
+    async fn handle(stream: UnixStream, state: &State) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        let mut reader = BufReader::new(reader);
+        // clients checking whether the daemon is running send nothing
+        if tokio::time::timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await?? == 0 {
+            return Ok(());
+        }
+        let request: Request = serde_json::from_str(&line)?;
+
+        let mut output = Vec::new();
+        let response = match run(&request.args, state, &mut output).await {
+            Ok(()) => Response {
+                output: String::from_utf8(output)?,
+                error: None,
+            },
+            Err(err) => Response {
+                output: String::new(),
+                error: Some(format!("{err:#}")),
+            },
+        };
+
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    /// Run a command against the cached playback, refreshing it first if it
+    /// is older than `max_age`.
+    ///
+    /// The cache is locked while the playback is fetched, so that clients
+    /// arriving together share one fetch, but not while the command runs.
+    async fn run(args: &[String], state: &State, out: &mut Vec<u8>) -> Result<()> {
+        let cli = Cli::try_parse_from(
+            std::iter::once("rspotify-cli").chain(args.iter().map(String::as_str)),
+        )?;
+        if !super::serves(&cli.command) {
+            anyhow::bail!(Error::NotServed);
+        }
+
+        let mut cache = state.cache.lock().await;
+        if cache
+            .as_ref()
+            .is_some_and(|(fetched_at, _)| fetched_at.elapsed() > state.max_age)
+        {
+            *cache = None;
+        }
+        let curr = match &*cache {
+            Some((_, curr)) => Arc::clone(curr),
+            None => {
+                let curr = match CurrentlyPlaying::from_backend(Arc::clone(&state.backend)).await {
+                    Err(err) if matches!(err.downcast_ref(), Some(LibError::NoActiveDevice)) => {
+                        let Some(output) = idle_output(&cli.command, &state.settings)? else {
+                            return Err(err.context(Error::Connect));
+                        };
+                        writeln!(out, "{output}")?;
+                        return Ok(());
+                    },
+                    curr => Arc::new(curr.context(Error::Connect)?),
+                };
+                Arc::clone(&cache.insert((Instant::now(), curr)).1)
+            },
+        };
+        drop(cache);
+
+        let changes_playback = matches!(
+            cli.command,
+            Commands::Control { .. } | Commands::PlayFrom { .. }
+        );
+        let result = dispatch(
+            cli.command,
+            cli.device.as_deref(),
+            &curr,
+            &state.settings,
+            out,
+        )
+        .await;
+        if changes_playback {
+            *state.cache.lock().await = None;
+        }
+        result
+    }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+    use std::{ffi::OsString, path::Path, sync::Arc, time::Duration};
+
+    use anyhow::Result;
+    use rspotify_cli_lib::backend::PlayerBackend;
+
+    use crate::{dispatch::Settings, error::Error};
+
+    /// The daemon only runs on Unix, so there is never one to forward to.
+    pub async fn forward(_socket: &Path, _args: Vec<OsString>) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// The daemon only runs on Unix.
+    pub async fn serve(
+        _backend: Arc<dyn PlayerBackend>,
+        _settings: &Settings,
+        _socket: &Path,
+        _max_age: Duration,
+    ) -> Result<()> {
+        anyhow::bail!(Error::DaemonUnsupported)
+    }
+}
@@ -0,0 +1,71 @@
+//! Output of `devices list`.
+
+use rspotify::model::Device;
+
+/// Returns the devices as a table with a header.
+pub fn render_devices(devices: &[Device]) -> String {
+    let yes_no = |value: bool| if value { "yes" } else { "no" }.to_owned();
+    let rows: Vec<[String; 6]> = devices
+        .iter()
+        .map(|device| {
+            [
+                device.id.clone().unwrap_or_default(),
+                device.name.clone(),
+                format!("{:?}", device._type),
+                device
+                    .volume_percent
+                    .map_or_else(String::new, |volume| format!("{volume}%")),
+                yes_no(device.is_active),
+                yes_no(device.is_restricted),
+            ]
+        })
+        .collect();
+    let header = ["ID", "NAME", "TYPE", "VOLUME", "ACTIVE", "RESTRICTED"].map(ToOwned::to_owned);
+
+    let mut widths = [0; 6];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use rspotify_cli_lib::fake_backend::device;
+
+    use super::*;
+
+    #[test]
+    fn table() {
+        let devices = [
+            Device {
+                is_active: true,
+                ..device("a1", "Computer")
+            },
+            Device {
+                volume_percent: None,
+                is_restricted: true,
+                ..device("kitchen-speaker", "Kitchen")
+            },
+        ];
+        assert_eq!(
+            render_devices(&devices),
+            "ID               NAME      TYPE      VOLUME  ACTIVE  RESTRICTED\n\
+             a1               Computer  Computer  50%     yes     no\n\
+             kitchen-speaker  Kitchen   Computer          no      yes\n"
+        );
+    }
+}
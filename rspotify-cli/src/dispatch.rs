@@ -0,0 +1,307 @@
+//! Run CLI commands against the current playback.
+
+use std::{collections::HashMap, io::Write};
+
+use anyhow::{Context, Result};
+use rspotify_cli_lib::currently_playing::{CurrentlyPlaying, SearchType};
+
+use crate::{
+    bar::render_bar_status,
+    cli::{Commands, DevicesCommands, QueueCommands},
+    devices::render_devices,
+    error::Error,
+    hooks::Hooks,
+    play_list::{number, play_items},
+    play_search::play_search,
+    pretty_duration::PrettyDuration,
+    queue::{enqueue_file, queue_json, read_items, render_queue},
+    snapshot::Snapshot,
+    template::render_status,
+    watch::StatusLine,
+};
+
+/// Settings from the config that commands depend on.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// Volume increment for the volume increment and decrement commands.
+    pub volume_increment: u8,
+
+    /// Named templates for `status --format`.
+    pub formats: HashMap<String, String>,
+
+    /// Commands to run on playback events.
+    pub hooks: Hooks,
+
+    /// Bearer token required by the HTTP API.
+    pub api_token: Option<String>,
+}
+
+/// Returns the output of a command while no device is active, or `None` if
+/// the command should fail instead.
+pub fn idle_output(command: &Commands, settings: &Settings) -> Result<Option<String>> {
+    match command {
+        Commands::Status {
+            bar: Some(bar),
+            format,
+            ..
+        } => {
+            let line = StatusLine::new(Some(*bar), format.as_deref(), &settings.formats)?;
+            Ok(Some(line.render(&Snapshot::default())?))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Run a command against the current playback, writing its output to `out`.
+///
+/// Control and play-from commands are sent to `device` instead of the active
+/// one if given.
+pub async fn dispatch(
+    command: Commands,
+    device: Option<&str>,
+    curr: &CurrentlyPlaying,
+    settings: &Settings,
+    out: &mut impl Write,
+) -> Result<()> {
+    let targeted;
+    let curr = match device {
+        Some(device)
+            if matches!(
+                command,
+                Commands::Control { .. } | Commands::PlayFrom { .. }
+            ) =>
+        {
+            targeted = curr.clone().on_device(device).await?;
+            &targeted
+        },
+        _ => curr,
+    };
+
+    // disable formatting to have everything neatly on one line
+    #[rustfmt::skip]
+    match command {
+        // status
+        Commands::Status { json: true, .. } => writeln!(out, "{}", curr.to_json().await.context(Error::MissingMetadata)?)?,
+        Commands::Status { id: true, .. } => writeln!(out, "{}", curr.id().context(Error::MissingMetadata)?)?,
+        Commands::Status { url: true, .. } => writeln!(out, "{}", curr.generate_url().context(Error::MissingMetadata)?)?,
+        Commands::Status { uri: true, .. } => writeln!(out, "{}", curr.generate_uri().context(Error::MissingMetadata)?)?,
+        Commands::Status { title: true, .. } => writeln!(out, "{}", curr.title.as_ref().context(Error::MissingMetadata)?)?,
+        Commands::Status { artist: true, .. } => writeln!(out, "{}", curr.artist.as_ref().context(Error::MissingMetadata)?)?,
+        Commands::Status { progress: true, .. } => writeln!(out, "{}", curr.progress.context(Error::MissingMetadata)?.pretty())?,
+        Commands::Status { duration: true, .. } => writeln!(out, "{}", curr.duration.context(Error::MissingMetadata)?.pretty())?,
+        Commands::Status { is_playing: true, .. } => writeln!(out, "{}", curr.is_playing.context(Error::MissingMetadata)?)?,
+        Commands::Status { repeat_state: true, .. } => writeln!(out, "{:?}", curr.repeat_state.context(Error::MissingMetadata)?)?,
+        Commands::Status { is_shuffled: true, .. } => writeln!(out, "{:?}", curr.is_shuffled.context(Error::MissingMetadata)?)?,
+        Commands::Status { show_device: true, .. } => writeln!(out, "{}", curr.device.as_ref().context(Error::MissingMetadata)?)?,
+        Commands::Status { playing_type: true, .. } => writeln!(out, "{:?}", curr.playing_type.context(Error::MissingMetadata)?)?,
+        Commands::Status { is_liked: true, .. } => writeln!(out, "{}", curr.is_liked().await.context(Error::MissingMetadata)?)?,
+        Commands::Status { bar: Some(bar), format, .. } => writeln!(out, "{}", render_bar_status(bar, curr, format.as_deref(), &settings.formats).await?)?,
+        Commands::Status { format: Some(format), .. } => writeln!(out, "{}", render_status(curr, &format, &settings.formats).await?)?,
+        Commands::Status { .. } => writeln!(out, "{}", curr.display().await.context(Error::MissingMetadata)?)?,
+
+        // control
+        Commands::Control { play: true, .. } => curr.play().await?,
+        Commands::Control { pause: true, .. } => curr.pause().await?,
+        Commands::Control { toggle_play_pause: true, .. } => curr.toggle_play_pause().await?,
+        Commands::Control { like: true, .. } => curr.like().await?,
+        Commands::Control { unlike: true, .. } => curr.unlike().await?,
+        Commands::Control { toggle_like_unlike: true, .. } => curr.toggle_like_unlike().await?,
+        Commands::Control { previous: true, .. } => curr.previous().await?,
+        Commands::Control { next: true, .. } => curr.next().await?,
+        Commands::Control { repeat: Some(repeat), .. } => curr.repeat(repeat).await?,
+        Commands::Control { cycle_repeat: true, .. } => curr.cycle_repeat().await?,
+        Commands::Control { volume: Some(volume), .. } => curr.set_volume(volume).await?,
+        Commands::Control { volume_up: true, .. } => curr.volume_up(settings.volume_increment).await?,
+        Commands::Control { volume_down: true, .. } => curr.volume_down(settings.volume_increment).await?,
+        Commands::Control { shuffle: Some(shuffle), .. } => curr.shuffle(shuffle).await?,
+        Commands::Control { toggle_shuffle: true, .. } => curr.toggle_shuffle().await?,
+        Commands::Control { seek: Some(position), .. } => curr.seek_to(position.target(curr.progress, curr.duration)?).await?,
+        Commands::Control { replay: true, .. } => curr.replay().await?,
+
+        // play from
+        Commands::PlayFrom { url: Some(items), offset, position, .. } | Commands::PlayFrom { uri: Some(items), offset, position, .. } => play_items(curr, number(items), Error::Item, offset, position).await?,
+        Commands::PlayFrom { from_file: Some(path), offset, position, .. } => play_items(curr, read_items(&path)?, Error::Line, offset, position).await?,
+        Commands::PlayFrom { search: Some(query), kind, pick, offset, position, .. } => writeln!(out, "{}", play_search(curr, &query, kind, pick, offset, position).await?)?,
+
+        // search
+        Commands::Search { artist: Some(what), limit, offset, .. } => writeln!(out, "{}", curr.search(what, SearchType::Artist, limit, offset).await?)?,
+        Commands::Search { album: Some(what), limit, offset, .. } => writeln!(out, "{}", curr.search(what, SearchType::Album, limit, offset).await?)?,
+        Commands::Search { track: Some(what), limit, offset, .. } => writeln!(out, "{}", curr.search(what, SearchType::Track, limit, offset).await?)?,
+        Commands::Search { playlist: Some(what), limit, offset, .. } => writeln!(out, "{}", curr.search(what, SearchType::Playlist, limit, offset).await?)?,
+        Commands::Search { show: Some(what), limit, offset, .. } => writeln!(out, "{}", curr.search(what, SearchType::Show, limit, offset).await?)?,
+        Commands::Search { episode: Some(what), limit, offset, .. } => writeln!(out, "{}", curr.search(what, SearchType::Episode, limit, offset).await?)?,
+
+        // devices
+        Commands::Devices { command: DevicesCommands::List { json: true } } => writeln!(out, "{}", serde_json::to_string(&curr.devices().await?)?)?,
+        Commands::Devices { command: DevicesCommands::List { json: false } } => write!(out, "{}", render_devices(&curr.devices().await?))?,
+        Commands::Devices { command: DevicesCommands::Transfer { device, play } } => curr.transfer(&curr.resolve_device(&device).await?, play).await?,
+
+        // queue
+        Commands::Queue { command: QueueCommands::Show { json: true } } => writeln!(out, "{}", queue_json(&curr.queue().await?, curr.progress))?,
+        Commands::Queue { command: QueueCommands::Show { json: false } } => write!(out, "{}", render_queue(&curr.queue().await?, curr.progress))?,
+        Commands::Queue { command: QueueCommands::Add { item: Some(item), .. } } => curr.add_to_queue(&curr.resolve_uri(&item).await?).await?,
+        Commands::Queue { command: QueueCommands::Add { from_file: Some(path), .. } } => enqueue_file(curr, &path).await?,
+
+        #[allow(unreachable_patterns)]
+        _ => unimplemented!(),
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+// templates look like format strings
+#[allow(clippy::literal_string_with_formatting_args)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Duration;
+    use clap::Parser;
+    use rspotify::model::TrackId;
+    use rspotify_cli_lib::fake_backend::{track, Call, FakeBackend};
+
+    use super::*;
+    use crate::cli::Cli;
+
+    const TRACK_ID: &str = "4cOdK2wGLETKBW3PvgPWqT";
+
+    fn backend() -> Arc<FakeBackend> {
+        let id = TrackId::from_id(TRACK_ID).unwrap().into_static();
+        Arc::new(FakeBackend::with_track(track(
+            id,
+            "Title",
+            "Artist",
+            Duration::seconds(200),
+        )))
+    }
+
+    async fn run(backend: &Arc<FakeBackend>, args: &[&str]) -> Result<String> {
+        let cli = Cli::try_parse_from(std::iter::once("rspotify-cli").chain(args.iter().copied()))?;
+        let curr = CurrentlyPlaying::from_backend(backend.clone()).await?;
+        let mut out = Vec::new();
+        let settings = Settings {
+            volume_increment: 10,
+            formats: HashMap::from([("short".to_owned(), "{title}".to_owned())]),
+            ..Default::default()
+        };
+        dispatch(
+            cli.command,
+            cli.device.as_deref(),
+            &curr,
+            &settings,
+            &mut out,
+        )
+        .await?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[tokio::test]
+    async fn status() {
+        let backend = backend();
+        assert_eq!(
+            run(&backend, &["status"]).await.unwrap(),
+            "Title - Artist \u{2661}\n"
+        );
+        assert_eq!(
+            run(&backend, &["status", "--title"]).await.unwrap(),
+            "Title\n"
+        );
+        assert_eq!(
+            run(&backend, &["status", "--duration"]).await.unwrap(),
+            "3:20\n"
+        );
+        assert_eq!(
+            run(&backend, &["status", "--uri"]).await.unwrap(),
+            format!("spotify:track:{TRACK_ID}\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn status_format() {
+        let backend = backend();
+        assert_eq!(
+            run(
+                &backend,
+                &["status", "--format", "{artist}: {title:.4} {liked?+:-}"]
+            )
+            .await
+            .unwrap(),
+            "Artist: Tit\u{2026} -\n"
+        );
+        assert_eq!(
+            run(&backend, &["status", "--format", "short"])
+                .await
+                .unwrap(),
+            "Title\n"
+        );
+        assert!(run(&backend, &["status", "--format", "{nope}"])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn status_without_device() {
+        let backend = Arc::new(FakeBackend::new());
+        assert!(run(&backend, &["status"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn control() {
+        let backend = backend();
+        run(&backend, &["control", "--toggle-play"]).await.unwrap();
+        run(&backend, &["control", "--toggle-play"]).await.unwrap();
+        run(&backend, &["control", "--volume-up"]).await.unwrap();
+        run(&backend, &["control", "--cycle-repeat"]).await.unwrap();
+        run(&backend, &["control", "--toggle-shuffle"])
+            .await
+            .unwrap();
+        run(&backend, &["control", "--like"]).await.unwrap();
+        assert_eq!(
+            backend.calls(),
+            vec![
+                Call::Play,
+                Call::Pause,
+                Call::Volume(60),
+                Call::Repeat(rspotify_cli_lib::repeat_state::RepeatState::Context),
+                Call::Shuffle(true),
+                Call::Like(format!("spotify:track:{TRACK_ID}")),
+            ]
+        );
+        assert_eq!(
+            run(&backend, &["status", "--is-liked"]).await.unwrap(),
+            "true\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn play_from() {
+        let backend = backend();
+        run(
+            &backend,
+            &[
+                "play-from",
+                "--url",
+                "https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT",
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            backend.calls(),
+            vec![Call::PlayUris(vec![format!("spotify:track:{TRACK_ID}")])]
+        );
+    }
+
+    #[tokio::test]
+    async fn search() {
+        let backend = backend();
+        assert_eq!(
+            run(&backend, &["search", "--track", "foo"]).await.unwrap(),
+            "[]\n"
+        );
+        assert_eq!(
+            backend.calls(),
+            vec![Call::Search("foo".to_owned(), SearchType::Track)]
+        );
+    }
+}
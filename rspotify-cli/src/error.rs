@@ -14,4 +14,65 @@ pub enum Error {
     Config,
     #[error("One or more config field is missing in the config file: {0}")]
     IncompleteConfig(String),
+    #[error(
+        "The passphrase of the encrypted token file is required, set RSPOTIFY_TOKEN_PASSPHRASE"
+    )]
+    MissingPassphrase,
+    #[error("No hooks are set, add them to the [hooks] section of config.toml")]
+    NoHooks,
+    #[error("No API token is set, add api_token to config.toml")]
+    NoApiToken,
+    #[error("{0}")]
+    Daemon(String),
+    #[error("Invalid response from the daemon")]
+    DaemonResponse,
+    #[error("A daemon is already listening on {0}")]
+    DaemonRunning(String),
+    #[error("The daemon is only supported on Unix")]
+    DaemonUnsupported,
+    #[error(
+        "The daemon can only run status, control, play-from without --pick or --from-file, and \
+         search"
+    )]
+    NotServed,
+    #[error("Could not register on the D-Bus session bus")]
+    SessionBus,
+    #[error("MPRIS is only supported on Linux")]
+    MprisUnsupported,
+    #[error("Unable to read {0}")]
+    ReadFile(String),
+    #[error("Invalid item on line {0}")]
+    Line(usize),
+    #[error("Invalid item {0}")]
+    Item(usize),
+    #[error("Nothing to play")]
+    NoItems,
+    #[error("{0} items are invalid:\n{1}")]
+    InvalidItems(usize, String),
+    #[error("Invalid duration {0}, expected m:ss or h:mm:ss")]
+    InvalidDuration(String),
+    #[error("Invalid position {0}, expected a time like 1:23, an offset like +15s or -30, or a percentage like 50%")]
+    InvalidPosition(String),
+    #[error("Invalid start position {0}, expected a time like 90 or 1:23")]
+    InvalidStart(String),
+    #[error(
+        "Invalid offset {0}, expected the index of an item from 0 or the URI of a track or episode"
+    )]
+    InvalidOffset(String),
+    #[error("Position {0} is past the end of the item at {1}")]
+    PositionPastEnd(String, String),
+    #[error("No {0} found for {1}")]
+    NoSearchHits(String, String),
+    #[error("Nothing was picked")]
+    NothingPicked,
+    #[error("Invalid status template: {0}")]
+    Template(String),
+    #[error("Unknown profile {0}, add it with `rspotify-cli profile add {0}`")]
+    UnknownProfile(String),
+    #[error("Profile {0} already exists")]
+    ProfileExists(String),
+    #[error("Invalid profile name {0}, only letters, digits, `-` and `_` are allowed")]
+    InvalidProfileName(String),
+    #[error("The default profile can't be removed")]
+    RemoveDefaultProfile,
 }
@@ -0,0 +1,333 @@
+//! Playback events, found by comparing successive snapshots.
+
+use std::{io::Write, sync::Arc, time::Duration as StdDuration};
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rspotify_cli_lib::backend::PlayerBackend;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::time::Instant;
+
+use crate::snapshot::{Poller, Snapshot};
+
+/// How far the progress may be from where it should be before it counts as
+/// a seek.
+const SEEK_TOLERANCE: Duration = Duration::seconds(3);
+
+/// Kind of change in the playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Another item is loaded, or nothing is anymore.
+    TrackChanged,
+
+    /// The item was paused.
+    Paused,
+
+    /// The item was resumed.
+    Resumed,
+
+    /// The progress jumped within the item.
+    Seeked,
+
+    /// The volume of the device changed.
+    VolumeChanged,
+
+    /// Another device became active, or none is anymore.
+    DeviceChanged,
+
+    /// Shuffle was turned on or off.
+    ShuffleChanged,
+
+    /// The repeat state changed.
+    RepeatChanged,
+
+    /// The track was added to the liked songs.
+    Liked,
+
+    /// The track was removed from the liked songs.
+    Unliked,
+}
+
+/// A change in the playback, with the values before and after it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Event {
+    /// Kind of change.
+    pub event: EventKind,
+
+    /// When the change was noticed, in RFC 3339 format.
+    pub timestamp: String,
+
+    /// Value before the change.
+    pub before: Value,
+
+    /// Value after the change.
+    pub after: Value,
+}
+
+impl Event {
+    /// Create an event noticed at `timestamp`.
+    fn new(event: EventKind, timestamp: DateTime<Utc>, before: Value, after: Value) -> Self {
+        Self {
+            event,
+            timestamp: timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            before,
+            after,
+        }
+    }
+}
+
+/// Returns the item of a snapshot as JSON, or null if nothing is loaded.
+fn item(snapshot: &Snapshot) -> Value {
+    snapshot.uri.as_ref().map_or(Value::Null, |uri| {
+        json!({
+            "uri": uri,
+            "title": snapshot.title,
+            "artist": snapshot.artist,
+        })
+    })
+}
+
+/// Find the events between two snapshots taken `elapsed` apart.
+pub fn diff(
+    before: &Snapshot,
+    after: &Snapshot,
+    elapsed: StdDuration,
+    timestamp: DateTime<Utc>,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut push = |event, before: Value, after: Value| {
+        events.push(Event::new(event, timestamp, before, after));
+    };
+    let same_item = before.uri.is_some() && before.uri == after.uri;
+
+    if before.uri != after.uri {
+        push(EventKind::TrackChanged, item(before), item(after));
+    }
+
+    match (before.is_playing, after.is_playing) {
+        (Some(true), Some(false)) => push(EventKind::Paused, json!(true), json!(false)),
+        (Some(false), Some(true)) => push(EventKind::Resumed, json!(false), json!(true)),
+        _ => {},
+    }
+
+    if let (true, Some(progress), Some(actual)) = (same_item, before.progress, after.progress) {
+        let mut expected = progress;
+        if before.is_playing == Some(true) {
+            expected += Duration::from_std(elapsed).unwrap_or_else(|_| Duration::zero());
+        }
+        if let Some(duration) = before.duration {
+            expected = expected.min(duration);
+        }
+        if (actual - expected).abs() > SEEK_TOLERANCE {
+            push(
+                EventKind::Seeked,
+                json!(expected.num_seconds()),
+                json!(actual.num_seconds()),
+            );
+        }
+    }
+
+    if let (Some(old), Some(new)) = (before.volume, after.volume) {
+        if old != new {
+            push(EventKind::VolumeChanged, json!(old), json!(new));
+        }
+    }
+
+    if before.device != after.device {
+        push(
+            EventKind::DeviceChanged,
+            json!(before.device),
+            json!(after.device),
+        );
+    }
+
+    if let (Some(old), Some(new)) = (before.is_shuffled, after.is_shuffled) {
+        if old != new {
+            push(EventKind::ShuffleChanged, json!(old), json!(new));
+        }
+    }
+
+    if let (Some(old), Some(new)) = (before.repeat_state, after.repeat_state) {
+        if old != new {
+            push(EventKind::RepeatChanged, json!(old), json!(new));
+        }
+    }
+
+    if let (true, Some(old), Some(new)) = (same_item, before.is_liked, after.is_liked) {
+        match (old, new) {
+            (false, true) => push(EventKind::Liked, json!(false), json!(true)),
+            (true, false) => push(EventKind::Unliked, json!(true), json!(false)),
+            _ => {},
+        }
+    }
+
+    events
+}
+
+/// Poll the playback every `interval` and call `handle` with every event and
+/// the snapshot it led to.
+///
+/// The first snapshot only sets the baseline, so nothing is reported for the
+/// state at startup. Failed polls are reported and retried.
+pub async fn for_each_event(
+    backend: Arc<dyn PlayerBackend>,
+    interval: StdDuration,
+    mut handle: impl FnMut(&Event, &Snapshot) -> Result<()>,
+) -> Result<()> {
+    let mut poller = Poller::new(backend, interval, true);
+    let mut previous: Option<(Snapshot, Instant)> = None;
+
+    loop {
+        let (snapshot, wait) = poller.poll_retrying().await;
+        let polled_at = Instant::now();
+
+        if let Some((before, before_at)) = &previous {
+            for event in diff(
+                before,
+                &snapshot,
+                polled_at.duration_since(*before_at),
+                Utc::now(),
+            ) {
+                handle(&event, &snapshot)?;
+            }
+        }
+        previous = Some((snapshot, polled_at));
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Print every event as a line of JSON.
+pub async fn print_events(
+    backend: Arc<dyn PlayerBackend>,
+    interval: StdDuration,
+    out: &mut impl Write,
+) -> Result<()> {
+    for_each_event(backend, interval, |event, _| {
+        writeln!(out, "{}", serde_json::to_string(event)?)?;
+        out.flush()?;
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rspotify_cli_lib::{fake_backend::FakeBackend, repeat_state::RepeatState};
+
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            uri: Some("spotify:track:a".to_owned()),
+            title: Some("Title".to_owned()),
+            artist: Some("Artist".to_owned()),
+            progress: Some(Duration::seconds(10)),
+            duration: Some(Duration::seconds(200)),
+            volume: Some(50),
+            is_playing: Some(true),
+            repeat_state: Some(RepeatState::Off),
+            is_shuffled: Some(false),
+            device: Some("Laptop".to_owned()),
+            is_liked: Some(false),
+            ..Default::default()
+        }
+    }
+
+    fn kinds(before: &Snapshot, after: &Snapshot) -> Vec<EventKind> {
+        diff(before, after, StdDuration::from_secs(5), Utc::now())
+            .into_iter()
+            .map(|event| event.event)
+            .collect()
+    }
+
+    #[test]
+    fn no_change() {
+        let after = Snapshot {
+            progress: Some(Duration::seconds(15)),
+            ..snapshot()
+        };
+        assert_eq!(kinds(&snapshot(), &after), []);
+    }
+
+    #[test]
+    fn changes() {
+        let after = Snapshot {
+            progress: Some(Duration::seconds(15)),
+            volume: Some(60),
+            is_playing: Some(false),
+            repeat_state: Some(RepeatState::Track),
+            is_shuffled: Some(true),
+            device: Some("Phone".to_owned()),
+            is_liked: Some(true),
+            ..snapshot()
+        };
+        assert_eq!(
+            kinds(&snapshot(), &after),
+            [
+                EventKind::Paused,
+                EventKind::VolumeChanged,
+                EventKind::DeviceChanged,
+                EventKind::ShuffleChanged,
+                EventKind::RepeatChanged,
+                EventKind::Liked,
+            ]
+        );
+    }
+
+    #[test]
+    fn track_changed() {
+        let after = Snapshot {
+            uri: Some("spotify:track:b".to_owned()),
+            title: Some("Other".to_owned()),
+            progress: Some(Duration::seconds(100)),
+            is_liked: Some(true),
+            ..snapshot()
+        };
+        let events = diff(&snapshot(), &after, StdDuration::from_secs(5), Utc::now());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, EventKind::TrackChanged);
+        assert_eq!(events[0].before["title"], "Title");
+        assert_eq!(events[0].after["uri"], "spotify:track:b");
+
+        let events = diff(
+            &snapshot(),
+            &Snapshot::default(),
+            StdDuration::from_secs(5),
+            Utc::now(),
+        );
+        assert_eq!(events[0].after, Value::Null);
+        assert_eq!(events[1].event, EventKind::DeviceChanged);
+    }
+
+    #[test]
+    fn seeked() {
+        let after = Snapshot {
+            progress: Some(Duration::seconds(60)),
+            ..snapshot()
+        };
+        let events = diff(&snapshot(), &after, StdDuration::from_secs(5), Utc::now());
+        assert_eq!(events[0].event, EventKind::Seeked);
+        assert_eq!(events[0].before, json!(15));
+        assert_eq!(events[0].after, json!(60));
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["event"], "seeked");
+        assert!(json["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_failed_polls() {
+        let backend = Arc::new(FakeBackend::new());
+        backend.state().failing_fetches = 3;
+        let result = tokio::time::timeout(
+            StdDuration::from_secs(20),
+            for_each_event(backend.clone(), StdDuration::from_secs(5), |_, _| Ok(())),
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(backend.state().failing_fetches, 0);
+    }
+}
@@ -0,0 +1,226 @@
+//! Shell commands run on playback events.
+
+use std::{process::Stdio, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use rspotify_cli_lib::backend::PlayerBackend;
+use serde::{Deserialize, Serialize};
+use tokio::process::{Child, Command};
+
+use crate::{
+    events::{for_each_event, Event, EventKind},
+    pretty_duration::PrettyDuration,
+    snapshot::Snapshot,
+};
+
+/// Commands to run on playback events, from the `[hooks]` section of the
+/// config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hooks {
+    /// Run when another item is loaded.
+    pub on_track_change: Option<String>,
+
+    /// Run when the playback is paused.
+    pub on_pause: Option<String>,
+
+    /// Run when the playback is resumed.
+    pub on_play: Option<String>,
+
+    /// Run when the current track is liked.
+    pub on_like: Option<String>,
+
+    /// Run when another device becomes active.
+    pub on_device_change: Option<String>,
+}
+
+impl Hooks {
+    /// Returns the command to run for an event, if any.
+    fn command(&self, event: EventKind) -> Option<&str> {
+        match event {
+            EventKind::TrackChanged => self.on_track_change.as_deref(),
+            EventKind::Paused => self.on_pause.as_deref(),
+            EventKind::Resumed => self.on_play.as_deref(),
+            EventKind::Liked => self.on_like.as_deref(),
+            EventKind::DeviceChanged => self.on_device_change.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether no hook is set.
+    pub const fn is_empty(&self) -> bool {
+        self.on_track_change.is_none()
+            && self.on_pause.is_none()
+            && self.on_play.is_none()
+            && self.on_like.is_none()
+            && self.on_device_change.is_none()
+    }
+}
+
+/// Returns the environment variables describing an event and the playback
+/// after it. Unknown values are empty.
+fn env_vars(event: &Event, snapshot: &Snapshot) -> Vec<(&'static str, String)> {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+
+    vec![
+        (
+            "RSPOTIFY_EVENT",
+            serde_json::to_value(event.event)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_owned))
+                .unwrap_or_default(),
+        ),
+        ("RSPOTIFY_BEFORE", event.before.to_string()),
+        ("RSPOTIFY_AFTER", event.after.to_string()),
+        ("RSPOTIFY_ID", text(&snapshot.id)),
+        ("RSPOTIFY_URI", text(&snapshot.uri)),
+        ("RSPOTIFY_URL", text(&snapshot.url)),
+        ("RSPOTIFY_TITLE", text(&snapshot.title)),
+        ("RSPOTIFY_ARTIST", text(&snapshot.artist)),
+        (
+            "RSPOTIFY_PROGRESS",
+            snapshot
+                .progress
+                .map(PrettyDuration::pretty)
+                .unwrap_or_default(),
+        ),
+        (
+            "RSPOTIFY_DURATION",
+            snapshot
+                .duration
+                .map(PrettyDuration::pretty)
+                .unwrap_or_default(),
+        ),
+        ("RSPOTIFY_DEVICE", text(&snapshot.device)),
+        (
+            "RSPOTIFY_VOLUME",
+            snapshot.volume.map(|v| v.to_string()).unwrap_or_default(),
+        ),
+        (
+            "RSPOTIFY_IS_PLAYING",
+            snapshot
+                .is_playing
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+        ),
+        (
+            "RSPOTIFY_IS_LIKED",
+            snapshot.is_liked.map(|v| v.to_string()).unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Start a hook in the shell without waiting for it.
+fn spawn(command: &str, event: &Event, snapshot: &Snapshot) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+
+    shell
+        .arg(command)
+        .envs(env_vars(event, snapshot))
+        .stdin(Stdio::null())
+        .spawn()
+}
+
+/// Poll the playback every `interval` and run the hooks of every event.
+///
+/// A hook that can't be started is reported without stopping the others.
+pub async fn run_hooks(
+    backend: Arc<dyn PlayerBackend>,
+    hooks: &Hooks,
+    interval: Duration,
+) -> Result<()> {
+    for_each_event(backend, interval, |event, snapshot| {
+        if let Some(command) = hooks.command(event.event) {
+            if let Err(err) = spawn(command, event, snapshot) {
+                eprintln!("Unable to run hook `{command}`: {err}");
+            }
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+
+    use super::*;
+
+    fn event(kind: EventKind) -> Event {
+        Event {
+            event: kind,
+            timestamp: Utc::now().to_rfc3339(),
+            before: json!(true),
+            after: json!(false),
+        }
+    }
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            uri: Some("spotify:track:a".to_owned()),
+            title: Some("Title".to_owned()),
+            artist: Some("Artist".to_owned()),
+            volume: Some(50),
+            is_playing: Some(false),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let hooks = Hooks {
+            on_pause: Some("pause".to_owned()),
+            on_play: Some("play".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(hooks.command(EventKind::Paused), Some("pause"));
+        assert_eq!(hooks.command(EventKind::Resumed), Some("play"));
+        assert_eq!(hooks.command(EventKind::TrackChanged), None);
+        assert_eq!(hooks.command(EventKind::Seeked), None);
+        assert!(!hooks.is_empty());
+        assert!(Hooks::default().is_empty());
+    }
+
+    #[test]
+    fn environment() {
+        let vars = env_vars(&event(EventKind::Paused), &snapshot());
+        let var = |name| vars.iter().find(|(n, _)| *n == name).unwrap().1.as_str();
+        assert_eq!(var("RSPOTIFY_EVENT"), "paused");
+        assert_eq!(var("RSPOTIFY_BEFORE"), "true");
+        assert_eq!(var("RSPOTIFY_TITLE"), "Title");
+        assert_eq!(var("RSPOTIFY_URI"), "spotify:track:a");
+        assert_eq!(var("RSPOTIFY_VOLUME"), "50");
+        assert_eq!(var("RSPOTIFY_IS_PLAYING"), "false");
+        assert_eq!(var("RSPOTIFY_IS_LIKED"), "");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn runs_in_shell() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out");
+        let command = format!(
+            "echo \"$RSPOTIFY_EVENT $RSPOTIFY_TITLE\" > {}",
+            path.display()
+        );
+
+        let status = spawn(&command, &event(EventKind::Paused), &snapshot())
+            .unwrap()
+            .wait()
+            .await
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "paused Title\n");
+    }
+}
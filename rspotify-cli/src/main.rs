@@ -3,101 +3,251 @@
 #![forbid(unsafe_code)]
 #![warn(clippy::all, clippy::nursery, clippy::expect_used, clippy::unwrap_used)]
 
+pub mod api;
+pub mod auth;
+pub mod bar;
 pub mod cli;
 pub mod config;
+pub mod daemon;
+pub mod devices;
+pub mod dispatch;
 pub mod error;
+pub mod events;
+pub mod hooks;
+pub mod mpd;
+pub mod mpris;
+pub mod play_list;
+pub mod play_search;
+pub mod position;
 pub mod pretty_duration;
+pub mod profile;
+pub mod push;
+pub mod queue;
+pub mod snapshot;
+pub mod template;
+pub mod token_store;
+pub mod tui;
+pub mod watch;
+pub mod websocket;
+
+use std::{
+    io::{stdin, stdout, IsTerminal, Write},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use clap::Parser;
 use rspotify_cli_lib::{
-    currently_playing::{CurrentlyPlaying, SearchType},
-    init_spotify::init_spotify,
+    auth::{authorize, authorize_with_refresh_token},
+    currently_playing::CurrentlyPlaying,
+    error::Error as LibError,
+    init_spotify::{build_spotify, init_spotify, ClientConfig},
+    scopes::resolve_scopes,
 };
 
 use crate::{
+    auth::auth_command,
     cli::{Cli, Commands},
-    config::{get_config_path, load_config, Config, ConfigFile},
+    config::{load_config, Config},
+    daemon::{forward, serve, serves, socket_path},
+    dispatch::{dispatch, idle_output, Settings},
     error::Error,
-    pretty_duration::PrettyDuration,
+    events::print_events,
+    hooks::run_hooks,
+    profile::{default_profile, profile_command},
+    token_store::open_token_store,
+    watch::{watch, StatusLine},
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+
+    if let Commands::Profile { command } = cli.command {
+        return profile_command(command, &mut stdout());
+    }
+
+    let profile = match cli.profile {
+        Some(profile) => profile,
+        None => default_profile()?,
+    };
+
+    // a running daemon answers without loading the config or the token
+    if serves(&cli.command) {
+        let args = std::env::args_os().skip(1).collect();
+        if let Some(output) = forward(&socket_path(&profile)?, args).await? {
+            print!("{output}");
+            return Ok(());
+        }
+    }
+
     let Config {
         client_id,
         client_secret,
+        auth_mode,
         redirect_uri,
+        token_store,
+        scopes,
         volume_increment,
-    } = load_config()?;
+        formats,
+        hooks,
+        api_token,
+        api_base_url,
+        accounts_base_url,
+    } = load_config(&profile)?;
 
-    let spotify = init_spotify(
-        get_config_path(ConfigFile::Token).context(Error::Config)?,
+    let mut client_config = ClientConfig {
+        token_store: open_token_store(token_store, &profile)?,
         client_id,
         client_secret,
+        auth_mode,
         redirect_uri,
-    )
-    .await?;
+        scopes: resolve_scopes(&scopes)?,
+        api_base_url,
+        auth_base_url: accounts_base_url,
+    };
 
-    let Ok(curr) = CurrentlyPlaying::new(spotify).await else {
-        anyhow::bail!(Error::Connect);
+    let settings = Settings {
+        volume_increment,
+        formats,
+        hooks,
+        api_token,
     };
 
-    // disable formatting to have everything neatly on one line
-    #[rustfmt::skip]
     match cli.command {
-        // status
-        Commands::Status { json: true, .. } => println!("{}", curr.to_json().await.context(Error::MissingMetadata)?),
-        Commands::Status { id: true, .. } => println!("{}", curr.id().context(Error::MissingMetadata)?),
-        Commands::Status { url: true, .. } => println!("{}", curr.generate_url().context(Error::MissingMetadata)?),
-        Commands::Status { uri: true, .. } => println!("{}", curr.generate_uri().context(Error::MissingMetadata)?),
-        Commands::Status { title: true, .. } => println!("{}", curr.title.context(Error::MissingMetadata)?),
-        Commands::Status { artist: true, .. } => println!("{}", curr.artist.context(Error::MissingMetadata)?),
-        Commands::Status { progress: true, .. } => println!("{}", curr.progress.context(Error::MissingMetadata)?.pretty()),
-        Commands::Status { duration: true, .. } => println!("{}", curr.duration.context(Error::MissingMetadata)?.pretty()),
-        Commands::Status { is_playing: true, .. } => println!("{}", curr.is_playing.context(Error::MissingMetadata)?),
-        Commands::Status { repeat_state: true, .. } => println!("{:?}", curr.repeat_state.context(Error::MissingMetadata)?),
-        Commands::Status { is_shuffled: true, .. } => println!("{:?}", curr.is_shuffled.context(Error::MissingMetadata)?),
-        Commands::Status { device: true, .. } => println!("{}", curr.device.context(Error::MissingMetadata)?),
-        Commands::Status { playing_type: true, .. } => println!("{:?}", curr.playing_type.context(Error::MissingMetadata)?),
-        Commands::Status { is_liked: true, .. } => println!("{}", curr.is_liked().await.context(Error::MissingMetadata)?),
-        Commands::Status { .. } => println!("{}", curr.display().await.context(Error::MissingMetadata)?),
-
-        // control
-        Commands::Control { play: true, .. } => curr.play().await?,
-        Commands::Control { pause: true, .. } => curr.pause().await?,
-        Commands::Control { toggle_play_pause: true, .. } => curr.toggle_play_pause().await?,
-        Commands::Control { like: true, .. } => curr.like().await?,
-        Commands::Control { unlike: true, .. } => curr.unlike().await?,
-        Commands::Control { toggle_like_unlike: true, .. } => curr.toggle_like_unlike().await?,
-        Commands::Control { previous: true, .. } => curr.previous().await?,
-        Commands::Control { next: true, .. } => curr.next().await?,
-        Commands::Control { repeat: Some(repeat), .. } => curr.repeat(repeat).await?,
-        Commands::Control { cycle_repeat: true, .. } => curr.cycle_repeat().await?,
-        Commands::Control { volume: Some(volume), .. } => curr.set_volume(volume).await?,
-        Commands::Control { volume_up: true, .. } => curr.volume_up(volume_increment).await?,
-        Commands::Control { volume_down: true, .. } => curr.volume_down(volume_increment).await?,
-        Commands::Control { shuffle: Some(shuffle), .. } => curr.shuffle(shuffle).await?,
-        Commands::Control { toggle_shuffle: true, .. } => curr.toggle_shuffle().await?,
-        Commands::Control { seek: Some(position), .. } => curr.seek(position).await?,
-        Commands::Control { replay: true, .. } => curr.replay().await?,
-
-        // play from
-        Commands::PlayFrom { url: Some(url), .. } => curr.play_from_url(url).await?,
-        Commands::PlayFrom { uri: Some(uri), .. } => curr.play_from_uri(uri).await?,
-
-        // search
-        Commands::Search { artist: Some(what), limit, offset, .. } => println!( "{}", curr.search(what, SearchType::Artist, limit, offset).await?),
-        Commands::Search { album: Some(what), limit, offset, .. } => println!( "{}", curr.search(what, SearchType::Album, limit, offset).await?),
-        Commands::Search { track: Some(what), limit, offset, .. } => println!( "{}", curr.search(what, SearchType::Track, limit, offset).await?),
-        Commands::Search { playlist: Some(what), limit, offset, .. } => println!( "{}", curr.search(what, SearchType::Playlist, limit, offset).await?),
-        Commands::Search { show: Some(what), limit, offset, .. } => println!( "{}", curr.search(what, SearchType::Show, limit, offset).await?),
-        Commands::Search { episode: Some(what), limit, offset, .. } => println!( "{}", curr.search(what, SearchType::Episode, limit, offset).await?),
-
-        #[allow(unreachable_patterns)]
-        _ => unimplemented!(),
-    };
+        Commands::Auth {
+            command: Some(command),
+            ..
+        } => auth_command(command, client_config, &mut stdout()).await?,
+        Commands::Auth {
+            command: None,
+            no_browser,
+            refresh_token,
+        } => {
+            let mut spotify = build_spotify(client_config)?;
+            match refresh_token {
+                Some(refresh_token) => {
+                    authorize_with_refresh_token(&spotify, refresh_token).await?;
+                },
+                None => authorize(&mut spotify, !no_browser).await?,
+            }
+            println!("Successfully authorized");
+        },
+        command => {
+            let device = cli.device.as_deref();
+            if let Err(err) = run(
+                command.clone(),
+                device,
+                client_config.clone(),
+                &settings,
+                &profile,
+            )
+            .await
+            {
+                let Some(LibError::MissingScope(scope)) = err.downcast_ref::<LibError>() else {
+                    return Err(err);
+                };
+                if !confirm_reauthorize(&err)? {
+                    return Err(err);
+                }
+
+                client_config.scopes.insert(scope.clone());
+                let mut spotify = build_spotify(client_config.clone())?;
+                authorize(&mut spotify, true).await?;
+                run(command, device, client_config, &settings, &profile).await?;
+            }
+        },
+    }
 
     Ok(())
 }
+
+/// Connect to Spotify and run a command against the current playback, or
+/// against `device` instead of the active one.
+async fn run(
+    command: Commands,
+    device: Option<&str>,
+    client_config: ClientConfig,
+    settings: &Settings,
+    profile: &str,
+) -> Result<()> {
+    let spotify = init_spotify(client_config).await?;
+    let backend = spotify.into_backend();
+
+    // long running commands poll the playback themselves
+    match &command {
+        Commands::Status {
+            watch: true,
+            interval,
+            bar,
+            format,
+            ..
+        } => {
+            let line = StatusLine::new(*bar, format.as_deref(), &settings.formats)?;
+            return watch(
+                backend,
+                &line,
+                Duration::from_secs(*interval),
+                &mut stdout(),
+            )
+            .await;
+        },
+        Commands::Events { interval } => {
+            return print_events(backend, Duration::from_secs(*interval), &mut stdout()).await;
+        },
+        Commands::Daemon { cache } => {
+            let socket = socket_path(profile)?;
+            return serve(backend, settings, &socket, Duration::from_secs(*cache)).await;
+        },
+        Commands::Hooks { interval } => {
+            if settings.hooks.is_empty() {
+                anyhow::bail!(Error::NoHooks);
+            }
+            return run_hooks(backend, &settings.hooks, Duration::from_secs(*interval)).await;
+        },
+        Commands::Api { listen, interval } => {
+            return api::serve(backend, settings, *listen, Duration::from_secs(*interval)).await;
+        },
+        Commands::Mpd { listen, interval } => {
+            return mpd::serve(backend, *listen, Duration::from_secs(*interval)).await;
+        },
+        Commands::Mpris { interval } => {
+            return mpris::serve(backend, Duration::from_secs(*interval)).await;
+        },
+        Commands::Tui { interval } => {
+            let interval = Duration::from_secs(*interval);
+            return tui::run(backend, settings.volume_increment, interval).await;
+        },
+        _ => {},
+    }
+
+    let curr = match CurrentlyPlaying::from_backend(backend).await {
+        // bars show nothing rather than an error while no device is active
+        Err(err) if matches!(err.downcast_ref(), Some(LibError::NoActiveDevice)) => {
+            let Some(output) = idle_output(&command, settings)? else {
+                return Err(err.context(Error::Connect));
+            };
+            println!("{output}");
+            return Ok(());
+        },
+        curr => curr.context(Error::Connect)?,
+    };
+
+    dispatch(command, device, &curr, settings, &mut stdout()).await
+}
+
+/// Ask whether to re-authorize with the scope that `err` reports as missing.
+///
+/// Never asks when standard input is not a terminal.
+fn confirm_reauthorize(err: &anyhow::Error) -> Result<bool> {
+    if !stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    eprint!("{err:#}\nRe-authorize now? [y/N] ");
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
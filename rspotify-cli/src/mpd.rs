@@ -0,0 +1,816 @@
+//! Front-end speaking the MPD protocol, so that MPD clients like `mpc` and
+//! `ncmpcpp` can control the playback.
+//!
+//! The current item is shown as a queue of one song. Only the commands about
+//! the playback, its options and searching are understood; see
+//! <https://mpd.readthedocs.io/en/latest/protocol.html>.
+
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use rspotify::{model::FullTrack, prelude::Id};
+use rspotify_cli_lib::{
+    backend::PlayerBackend, currently_playing::CurrentlyPlaying, repeat_state::RepeatState,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{watch, Notify},
+};
+
+use crate::{
+    events::{diff, EventKind},
+    snapshot::{Polled, Poller, Snapshot},
+};
+
+/// First line sent to every client, with the protocol version implemented.
+const GREETING: &str = "OK MPD 0.23.0";
+
+/// Most results returned by `search`.
+const SEARCH_LIMIT: u32 = 20;
+
+/// Subsystems that clients may wait for with `idle`. Only `player`, `mixer`
+/// and `options` ever change.
+const SUBSYSTEMS: [&str; 14] = [
+    "database",
+    "update",
+    "stored_playlist",
+    "playlist",
+    "player",
+    "mixer",
+    "output",
+    "options",
+    "partition",
+    "sticker",
+    "subscription",
+    "message",
+    "neighbor",
+    "mount",
+];
+
+/// Error codes of `ACK` lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AckCode {
+    /// Bad arguments.
+    Arg = 2,
+
+    /// Unknown command.
+    Unknown = 5,
+
+    /// Failure while running the command.
+    System = 52,
+}
+
+/// A failed command, sent back as an `ACK` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ack {
+    /// Kind of failure.
+    code: AckCode,
+
+    /// Message for the user.
+    message: String,
+}
+
+impl Ack {
+    /// Bad arguments.
+    fn arg(message: impl Into<String>) -> Self {
+        Self {
+            code: AckCode::Arg,
+            message: message.into(),
+        }
+    }
+
+    /// Failure while talking to Spotify.
+    fn system(err: &anyhow::Error) -> Self {
+        Self {
+            code: AckCode::System,
+            message: format!("{err:#}"),
+        }
+    }
+
+    /// Format the `ACK` line for the `index`-th command of a list.
+    fn line(&self, index: usize, command: &str) -> String {
+        format!(
+            "ACK [{}@{index}] {{{command}}} {}\n",
+            self.code as u8, self.message
+        )
+    }
+}
+
+/// Where `seekcur` goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Seek {
+    /// To a position.
+    To(Duration),
+
+    /// By an offset from the progress.
+    By(Duration),
+}
+
+/// A command understood by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Request {
+    Status,
+    CurrentSong,
+    Play,
+    Pause(Option<bool>),
+    Next,
+    Previous,
+    SetVol(u8),
+    Random(bool),
+    Repeat(bool),
+    Single(bool),
+    SeekCur(Seek),
+    Search(String),
+    Idle(Vec<String>),
+    NoIdle,
+    Ping,
+    Close,
+    CommandListBegin { list_ok: bool },
+    CommandListEnd,
+}
+
+/// Split a command line into its command and arguments, which may be quoted
+/// with backslash escapes.
+fn tokenize(line: &str) -> Result<Vec<String>, Ack> {
+    let mut tokens = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => token.extend(chars.next()),
+                    Some(c) => token.push(c),
+                    None => return Err(Ack::arg("Missing closing '\"'")),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                token.push(c);
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse a `0` or `1` argument.
+fn parse_bool(arg: &str) -> Result<bool, Ack> {
+    match arg {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(Ack::arg(format!("Boolean (0/1) expected: {arg}"))),
+    }
+}
+
+/// Parse a number of seconds, with an optional fraction.
+fn parse_seconds(arg: &str) -> Result<Duration, Ack> {
+    let millis = arg
+        .parse::<f64>()
+        .map_err(|_| Ack::arg(format!("Number expected: {arg}")))?
+        * 1000.0;
+    // `as` saturates, so anything that doesn't fit in an i64 is rejected first
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    if millis.is_finite() && millis.abs() < i64::MAX as f64 {
+        if let Some(duration) = Duration::try_milliseconds(millis.round() as i64) {
+            return Ok(duration);
+        }
+    }
+    Err(Ack::arg(format!("Number out of range: {arg}")))
+}
+
+/// Build the Spotify query of `search` from its tag and value pairs.
+fn search_query(args: &[String]) -> Result<String, Ack> {
+    if args.first().is_some_and(|arg| arg.starts_with('(')) {
+        return Err(Ack::arg("Filter expressions are not supported"));
+    }
+    if args.is_empty() || args.len() % 2 != 0 {
+        return Err(Ack::arg("Incorrect arguments"));
+    }
+
+    let terms = args
+        .chunks(2)
+        .map(|pair| match pair[0].to_lowercase().as_str() {
+            "any" => Ok(pair[1].clone()),
+            "title" => Ok(format!("track:{}", pair[1])),
+            "artist" | "albumartist" => Ok(format!("artist:{}", pair[1])),
+            "album" => Ok(format!("album:{}", pair[1])),
+            tag => Err(Ack::arg(format!("Unsupported tag: {tag}"))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(terms.join(" "))
+}
+
+/// Parse the tokens of a command line.
+fn parse(tokens: &[String]) -> Result<Request, Ack> {
+    let Some((command, args)) = tokens.split_first() else {
+        return Err(Ack {
+            code: AckCode::Unknown,
+            message: "No command given".to_owned(),
+        });
+    };
+    let arg = || {
+        args.first()
+            .map(String::as_str)
+            .ok_or_else(|| Ack::arg("Missing argument"))
+    };
+
+    Ok(match command.as_str() {
+        "status" => Request::Status,
+        "currentsong" => Request::CurrentSong,
+        "play" => Request::Play,
+        "pause" if args.is_empty() => Request::Pause(None),
+        "pause" => Request::Pause(Some(parse_bool(arg()?)?)),
+        "next" => Request::Next,
+        "previous" => Request::Previous,
+        "setvol" => Request::SetVol(
+            arg()?
+                .parse()
+                .ok()
+                .filter(|volume| *volume <= 100)
+                .ok_or_else(|| Ack::arg("Invalid volume value"))?,
+        ),
+        "random" => Request::Random(parse_bool(arg()?)?),
+        "repeat" => Request::Repeat(parse_bool(arg()?)?),
+        // Spotify can't stop after the current track, so oneshot repeats it
+        "single" if arg()? == "oneshot" => Request::Single(true),
+        "single" => Request::Single(parse_bool(arg()?)?),
+        "seekcur" => {
+            let arg = arg()?;
+            Request::SeekCur(match arg.strip_prefix('+') {
+                Some(offset) => Seek::By(parse_seconds(offset)?),
+                None if arg.starts_with('-') => Seek::By(parse_seconds(arg)?),
+                None => Seek::To(parse_seconds(arg)?),
+            })
+        },
+        "search" => Request::Search(search_query(args)?),
+        "idle" => {
+            if let Some(name) = args.iter().find(|arg| !SUBSYSTEMS.contains(&arg.as_str())) {
+                return Err(Ack::arg(format!("Unrecognized idle event: {name}")));
+            }
+            Request::Idle(args.to_vec())
+        },
+        "noidle" => Request::NoIdle,
+        "ping" => Request::Ping,
+        "close" => Request::Close,
+        "command_list_begin" => Request::CommandListBegin { list_ok: false },
+        "command_list_ok_begin" => Request::CommandListBegin { list_ok: true },
+        "command_list_end" => Request::CommandListEnd,
+        _ => {
+            return Err(Ack {
+                code: AckCode::Unknown,
+                message: format!("unknown command \"{command}\""),
+            })
+        },
+    })
+}
+
+/// Format a duration as seconds with milliseconds.
+fn seconds(duration: Duration) -> String {
+    format!(
+        "{}.{:03}",
+        duration.num_seconds(),
+        duration.num_milliseconds() % 1000
+    )
+}
+
+/// Returns the response to `status`.
+fn status(snapshot: &Snapshot) -> String {
+    let flag = |value: bool| u8::from(value);
+    let repeat_state = snapshot.repeat_state.unwrap_or(RepeatState::Off);
+    let state = match (&snapshot.title, snapshot.is_playing) {
+        (None, _) => "stop",
+        (Some(_), Some(true)) => "play",
+        (Some(_), _) => "pause",
+    };
+
+    let mut lines = vec![
+        format!("volume: {}", snapshot.volume.map_or(-1, i16::from)),
+        format!("repeat: {}", flag(repeat_state != RepeatState::Off)),
+        format!("random: {}", flag(snapshot.is_shuffled == Some(true))),
+        format!("single: {}", flag(repeat_state == RepeatState::Track)),
+        "consume: 0".to_owned(),
+        "playlist: 0".to_owned(),
+        format!("playlistlength: {}", flag(snapshot.title.is_some())),
+        format!("state: {state}"),
+    ];
+    if snapshot.title.is_some() {
+        let progress = snapshot.progress.unwrap_or_else(Duration::zero);
+        let duration = snapshot.duration.unwrap_or_else(Duration::zero);
+        lines.extend([
+            "song: 0".to_owned(),
+            "songid: 0".to_owned(),
+            format!(
+                "time: {}:{}",
+                progress.num_seconds(),
+                duration.num_seconds()
+            ),
+            format!("elapsed: {}", seconds(progress)),
+            format!("duration: {}", seconds(duration)),
+        ]);
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Returns the response to `currentsong`, empty while nothing is loaded.
+fn current_song(snapshot: &Snapshot) -> String {
+    let (Some(uri), Some(title)) = (&snapshot.uri, &snapshot.title) else {
+        return String::new();
+    };
+
+    let mut lines = vec![format!("file: {uri}"), format!("Title: {title}")];
+    if let Some(artist) = &snapshot.artist {
+        lines.push(format!("Artist: {artist}"));
+    }
+    if let Some(duration) = snapshot.duration {
+        lines.push(format!("Time: {}", duration.num_seconds()));
+        lines.push(format!("duration: {}", seconds(duration)));
+    }
+    lines.extend(["Pos: 0".to_owned(), "Id: 0".to_owned()]);
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Returns a track as a song in `search` results.
+fn song(track: &FullTrack) -> String {
+    let mut lines = Vec::new();
+    if let Some(id) = &track.id {
+        lines.push(format!("file: {}", id.uri()));
+    }
+    lines.push(format!("Title: {}", track.name));
+    lines.extend(
+        track
+            .artists
+            .iter()
+            .map(|artist| format!("Artist: {}", artist.name)),
+    );
+    lines.push(format!("Album: {}", track.album.name));
+    lines.push(format!("Time: {}", track.duration.num_seconds()));
+    lines.push(format!("duration: {}", seconds(track.duration)));
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Returns the repeat state after turning `repeat` or `single` on or off,
+/// where single is repeating the current track.
+const fn repeat_state(current: Option<RepeatState>, request: &Request) -> Option<RepeatState> {
+    let is_single = matches!(current, Some(RepeatState::Track));
+    match request {
+        Request::Repeat(false) => Some(RepeatState::Off),
+        Request::Repeat(true) | Request::Single(true) if is_single => Some(RepeatState::Track),
+        Request::Repeat(true) => Some(RepeatState::Context),
+        Request::Single(true) => Some(RepeatState::Track),
+        Request::Single(false) if is_single => Some(RepeatState::Context),
+        Request::Single(false) => current,
+        _ => None,
+    }
+}
+
+/// Returns the subsystems changed between two snapshots that a client waits
+/// for, or all of them if it waits for none in particular.
+fn changed_subsystems(before: &Polled, after: &Polled, subsystems: &[String]) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    let events = diff(
+        &before.snapshot,
+        &after.snapshot,
+        after.at.duration_since(before.at),
+        Utc::now(),
+    );
+    for event in events {
+        let subsystem = match event.event {
+            EventKind::VolumeChanged => "mixer",
+            EventKind::ShuffleChanged | EventKind::RepeatChanged => "options",
+            EventKind::Liked | EventKind::Unliked => continue,
+            _ => "player",
+        };
+        let waited_for = subsystems.is_empty() || subsystems.iter().any(|name| name == subsystem);
+        if waited_for && !changed.contains(&subsystem) {
+            changed.push(subsystem);
+        }
+    }
+    changed
+}
+
+/// State shared by every client.
+#[derive(Clone)]
+struct Shared {
+    /// Backend the commands are run with.
+    backend: Arc<dyn PlayerBackend>,
+
+    /// Last polled playback.
+    playback: watch::Receiver<Polled>,
+
+    /// Asks for the playback to be polled again right away.
+    refresh: Arc<Notify>,
+}
+
+impl Shared {
+    /// Run a command that changes the playback, then poll it again.
+    async fn control(&self, request: &Request) -> Result<(), Ack> {
+        let curr = CurrentlyPlaying::from_backend(Arc::clone(&self.backend))
+            .await
+            .map_err(|err| Ack::system(&err))?;
+
+        let result = match request {
+            Request::Play => curr.play().await,
+            Request::Pause(Some(true)) => curr.pause().await,
+            Request::Pause(Some(false)) => curr.play().await,
+            Request::Pause(None) => curr.toggle_play_pause().await,
+            Request::Next => curr.next().await,
+            Request::Previous => curr.previous().await,
+            Request::SetVol(volume) => curr.set_volume(*volume).await,
+            Request::Random(state) => curr.shuffle(*state).await,
+            Request::SeekCur(Seek::To(position)) => curr.seek_to(*position).await,
+            Request::SeekCur(Seek::By(offset)) => curr.seek_relative(*offset).await,
+            request => match repeat_state(curr.repeat_state, request) {
+                Some(state) => curr.repeat(state).await,
+                None => Ok(()),
+            },
+        };
+
+        self.refresh.notify_one();
+        result.map_err(|err| Ack::system(&err))
+    }
+
+    /// Run a command other than `idle` and the command lists, returning its
+    /// response without the final `OK`.
+    async fn execute(&self, request: &Request) -> Result<String, Ack> {
+        match request {
+            Request::Status => {
+                let polled = self.playback.borrow().clone();
+                Ok(status(&polled.snapshot.interpolate(polled.at.elapsed())))
+            },
+            Request::CurrentSong => Ok(current_song(&self.playback.borrow().snapshot)),
+            Request::Search(query) => {
+                let curr = CurrentlyPlaying::from_backend(Arc::clone(&self.backend))
+                    .await
+                    .map_err(|err| Ack::system(&err))?;
+                let tracks = curr
+                    .search_tracks(query, SEARCH_LIMIT)
+                    .await
+                    .map_err(|err| Ack::system(&err))?;
+                Ok(tracks.iter().map(song).collect())
+            },
+            Request::Ping => Ok(String::new()),
+            Request::Idle(_)
+            | Request::NoIdle
+            | Request::Close
+            | Request::CommandListBegin { .. }
+            | Request::CommandListEnd => Err(Ack::arg("Not allowed here")),
+            request => self.control(request).await.map(|()| String::new()),
+        }
+    }
+}
+
+/// Answer a client until it disconnects.
+async fn handle(stream: TcpStream, mut shared: Shared) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer.write_all(format!("{GREETING}\n").as_bytes()).await?;
+
+    // commands queued between command_list_begin and command_list_end
+    let mut list: Option<(bool, Vec<String>)> = None;
+
+    while let Some(line) = lines.next_line().await? {
+        let request = tokenize(&line).and_then(|tokens| parse(&tokens));
+        let name = line
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+
+        if let Some((list_ok, queued)) = &mut list {
+            if request != Ok(Request::CommandListEnd) {
+                queued.push(line);
+                continue;
+            }
+
+            let mut response = String::new();
+            let mut failed = false;
+            for (index, line) in queued.iter().enumerate() {
+                let name = line.split_whitespace().next().unwrap_or_default();
+                let result = match tokenize(line).and_then(|tokens| parse(&tokens)) {
+                    Ok(request) => shared.execute(&request).await,
+                    Err(ack) => Err(ack),
+                };
+                match result {
+                    Ok(output) => {
+                        response.push_str(&output);
+                        if *list_ok {
+                            response.push_str("list_OK\n");
+                        }
+                    },
+                    Err(ack) => {
+                        response.push_str(&ack.line(index, name));
+                        failed = true;
+                        break;
+                    },
+                }
+            }
+            if !failed {
+                response.push_str("OK\n");
+            }
+            writer.write_all(response.as_bytes()).await?;
+            list = None;
+            continue;
+        }
+
+        let response = match request {
+            Ok(Request::Close) => return Ok(()),
+            Ok(Request::CommandListBegin { list_ok }) => {
+                list = Some((list_ok, Vec::new()));
+                continue;
+            },
+            // noidle without idle is ignored
+            Ok(Request::NoIdle) => continue,
+            Ok(Request::Idle(subsystems)) => {
+                let before = shared.playback.borrow_and_update().clone();
+                let mut response = None;
+                while response.is_none() {
+                    tokio::select! {
+                        changed = shared.playback.changed() => {
+                            changed?;
+                            let after = shared.playback.borrow_and_update().clone();
+                            let changed = changed_subsystems(&before, &after, &subsystems);
+                            if !changed.is_empty() {
+                                response = Some(
+                                    changed
+                                        .iter()
+                                        .map(|name| format!("changed: {name}\n"))
+                                        .chain(["OK\n".to_owned()])
+                                        .collect::<String>(),
+                                );
+                            }
+                        },
+                        line = lines.next_line() => match line? {
+                            Some(line) if line.trim() == "noidle" => {
+                                response = Some("OK\n".to_owned());
+                            },
+                            // only noidle is allowed while idle
+                            _ => return Ok(()),
+                        },
+                    }
+                }
+                response.unwrap_or_default()
+            },
+            Ok(request) => match shared.execute(&request).await {
+                Ok(output) => format!("{output}OK\n"),
+                Err(ack) => ack.line(0, &name),
+            },
+            Err(ack) => ack.line(0, &name),
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Serve MPD clients on `address` until interrupted, polling the playback
+/// every `interval`.
+///
+/// # Errors
+///
+/// Returns an error if the address can't be listened on, or the playback
+/// can't be fetched at startup.
+pub async fn serve(
+    backend: Arc<dyn PlayerBackend>,
+    address: SocketAddr,
+    interval: StdDuration,
+) -> Result<()> {
+    let mut poller = Poller::new(Arc::clone(&backend), interval, false);
+    let (snapshot, _) = poller.poll().await?;
+    let (sender, receiver) = watch::channel(Polled {
+        snapshot,
+        at: Instant::now(),
+    });
+    let refresh = Arc::new(Notify::new());
+    tokio::spawn(poller.publish(sender, Arc::clone(&refresh)));
+
+    let shared = Shared {
+        backend,
+        playback: receiver,
+        refresh,
+    };
+    let listener = TcpListener::bind(address).await?;
+    println!("Listening on {}", listener.local_addr()?);
+
+    let accept = async {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle(stream, shared).await {
+                    eprintln!("Unable to answer a client: {err:#}");
+                }
+            });
+        }
+    };
+    tokio::select! {
+        result = accept => result,
+        result = tokio::signal::ctrl_c() => result.map_err(Into::into),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rspotify::model::TrackId;
+    use rspotify_cli_lib::fake_backend::{track, Call, FakeBackend};
+
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            uri: Some("spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+            title: Some("Title".to_owned()),
+            artist: Some("Artist".to_owned()),
+            progress: Some(Duration::milliseconds(61_500)),
+            duration: Some(Duration::seconds(200)),
+            volume: Some(50),
+            is_playing: Some(true),
+            repeat_state: Some(RepeatState::Track),
+            is_shuffled: Some(false),
+            ..Default::default()
+        }
+    }
+
+    fn shared(backend: Arc<FakeBackend>) -> Shared {
+        let (_, playback) = watch::channel(Polled {
+            snapshot: Snapshot::default(),
+            at: Instant::now(),
+        });
+        Shared {
+            backend,
+            playback,
+            refresh: Arc::new(Notify::new()),
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            tokenize(r#"search artist "The \"Band\"" any x"#).unwrap(),
+            ["search", "artist", "The \"Band\"", "any", "x"]
+        );
+        assert!(tokenize("search \"open").is_err());
+    }
+
+    #[test]
+    fn requests() {
+        let request = |line: &str| parse(&tokenize(line).unwrap());
+        assert_eq!(request("pause"), Ok(Request::Pause(None)));
+        assert_eq!(request("pause 1"), Ok(Request::Pause(Some(true))));
+        assert_eq!(request("setvol 30"), Ok(Request::SetVol(30)));
+        assert!(request("setvol 101").is_err());
+        assert_eq!(
+            request("seekcur +1.5"),
+            Ok(Request::SeekCur(Seek::By(Duration::milliseconds(1500))))
+        );
+        assert_eq!(
+            request("seekcur -10"),
+            Ok(Request::SeekCur(Seek::By(Duration::seconds(-10))))
+        );
+        assert_eq!(
+            request("seekcur 30"),
+            Ok(Request::SeekCur(Seek::To(Duration::seconds(30))))
+        );
+        assert!(request("seekcur 1e300").is_err());
+        assert!(request("seekcur -1e300").is_err());
+        assert!(request("seekcur inf").is_err());
+        assert_eq!(
+            request("search Artist Band title Song"),
+            Ok(Request::Search("artist:Band track:Song".to_owned()))
+        );
+        assert!(request("search artist").is_err());
+        assert!(request("idle player nothing").is_err());
+
+        let ack = request("crossfade 5").unwrap_err();
+        assert_eq!(
+            ack.line(0, "crossfade"),
+            "ACK [5@0] {crossfade} unknown command \"crossfade\"\n"
+        );
+    }
+
+    #[test]
+    fn responses() {
+        assert_eq!(
+            status(&snapshot()),
+            "volume: 50\nrepeat: 1\nrandom: 0\nsingle: 1\nconsume: 0\nplaylist: 0\n\
+             playlistlength: 1\nstate: play\nsong: 0\nsongid: 0\ntime: 61:200\n\
+             elapsed: 61.500\nduration: 200.000\n"
+        );
+        assert!(status(&Snapshot::default()).contains("state: stop\n"));
+        assert_eq!(
+            current_song(&snapshot()),
+            "file: spotify:track:4cOdK2wGLETKBW3PvgPWqT\nTitle: Title\nArtist: Artist\n\
+             Time: 200\nduration: 200.000\nPos: 0\nId: 0\n"
+        );
+        assert_eq!(current_song(&Snapshot::default()), "");
+    }
+
+    #[test]
+    fn repeat_states() {
+        let off = Some(RepeatState::Off);
+        let track = Some(RepeatState::Track);
+        assert_eq!(
+            repeat_state(off, &Request::Repeat(true)),
+            Some(RepeatState::Context)
+        );
+        assert_eq!(
+            repeat_state(off, &Request::Single(true)),
+            Some(RepeatState::Track)
+        );
+        assert_eq!(
+            repeat_state(track, &Request::Single(false)),
+            Some(RepeatState::Context)
+        );
+        assert_eq!(
+            repeat_state(track, &Request::Repeat(false)),
+            Some(RepeatState::Off)
+        );
+        assert_eq!(repeat_state(off, &Request::Next), None);
+    }
+
+    #[test]
+    fn subsystems() {
+        let before = Polled {
+            snapshot: snapshot(),
+            at: Instant::now(),
+        };
+        let after = Polled {
+            snapshot: Snapshot {
+                is_playing: Some(false),
+                volume: Some(60),
+                ..snapshot()
+            },
+            at: before.at,
+        };
+        assert_eq!(
+            changed_subsystems(&before, &after, &[]),
+            ["player", "mixer"]
+        );
+        assert_eq!(
+            changed_subsystems(&before, &after, &["mixer".to_owned()]),
+            ["mixer"]
+        );
+        assert!(changed_subsystems(&before, &before, &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn controls() {
+        let id = TrackId::from_id("4cOdK2wGLETKBW3PvgPWqT")
+            .unwrap()
+            .into_static();
+        let backend = Arc::new(FakeBackend::with_track(track(
+            id,
+            "Title",
+            "Artist",
+            Duration::seconds(200),
+        )));
+        let shared = shared(Arc::clone(&backend));
+
+        for line in [
+            "pause 0",
+            "setvol 30",
+            "random 1",
+            "single 1",
+            "seekcur 500",
+        ] {
+            let request = parse(&tokenize(line).unwrap()).unwrap();
+            assert_eq!(shared.execute(&request).await, Ok(String::new()));
+        }
+        assert_eq!(
+            backend.calls(),
+            [
+                Call::Play,
+                Call::Volume(30),
+                Call::Shuffle(true),
+                Call::Repeat(RepeatState::Track),
+                Call::Seek(Duration::seconds(200)),
+            ]
+        );
+
+        // an offset too large to add to the progress fails without a seek
+        let request = parse(&tokenize("seekcur +9223372036854740").unwrap()).unwrap();
+        assert_eq!(
+            shared.execute(&request).await.unwrap_err().code,
+            AckCode::System
+        );
+        assert_eq!(backend.calls().len(), 5);
+
+        let ack = shared
+            .execute(&Request::Idle(Vec::new()))
+            .await
+            .unwrap_err();
+        assert_eq!(ack.code, AckCode::Arg);
+    }
+}
@@ -0,0 +1,544 @@
+//! MPRIS bridge, so that media keys, `playerctl` and desktop widgets can
+//! control the playback over D-Bus.
+//!
+//! See <https://specifications.freedesktop.org/mpris-spec/latest/>.
+
+#[cfg(not(target_os = "linux"))]
+pub use fallback::serve;
+#[cfg(target_os = "linux")]
+pub use linux::serve;
+
+/// Well-known name owned on the session bus.
+pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.rspotify";
+
+/// Path of the object implementing the MPRIS interfaces.
+pub const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        collections::HashMap,
+        sync::Arc,
+        time::{Duration as StdDuration, Instant},
+    };
+
+    use anyhow::{Context, Result};
+    use chrono::{Duration, Utc};
+    use rspotify_cli_lib::{
+        backend::PlayerBackend, currently_playing::CurrentlyPlaying, repeat_state::RepeatState,
+    };
+    use tokio::runtime::Handle;
+    use zbus::{
+        dbus_interface, fdo,
+        zvariant::{ObjectPath, OwnedValue, Value},
+        ConnectionBuilder, SignalContext,
+    };
+
+    use super::{BUS_NAME, OBJECT_PATH};
+    use crate::{
+        error::Error,
+        events::{diff, Event, EventKind},
+        snapshot::{Poller, Snapshot},
+    };
+
+    /// Track id reported while nothing is loaded.
+    const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+    /// Returns the D-Bus object path identifying the loaded item, like
+    /// `/org/mpris/MediaPlayer2/rspotify/track/<id>` for `spotify:track:<id>`.
+    fn item_path(snapshot: &Snapshot) -> String {
+        snapshot
+            .uri
+            .as_ref()
+            .and_then(|uri| uri.strip_prefix("spotify:"))
+            .map_or_else(
+                || NO_TRACK.to_owned(),
+                |item| {
+                    format!(
+                        "/org/mpris/MediaPlayer2/rspotify/{}",
+                        item.replace(':', "/")
+                    )
+                },
+            )
+    }
+
+    /// Returns a duration in microseconds, as used by MPRIS.
+    fn micros(duration: Duration) -> i64 {
+        duration.num_microseconds().unwrap_or(i64::MAX)
+    }
+
+    /// Returns the MPRIS playback status of a snapshot.
+    const fn playback_status(snapshot: &Snapshot) -> &'static str {
+        match (&snapshot.title, snapshot.is_playing) {
+            (None, _) => "Stopped",
+            (Some(_), Some(true)) => "Playing",
+            (Some(_), _) => "Paused",
+        }
+    }
+
+    /// Returns the MPRIS loop status of a repeat state.
+    const fn loop_status(repeat_state: Option<RepeatState>) -> &'static str {
+        match repeat_state {
+            Some(RepeatState::Track) => "Track",
+            Some(RepeatState::Context) => "Playlist",
+            Some(RepeatState::Off) | None => "None",
+        }
+    }
+
+    /// Parse an MPRIS loop status into a repeat state.
+    fn parse_loop_status(status: &str) -> fdo::Result<RepeatState> {
+        match status {
+            "None" => Ok(RepeatState::Off),
+            "Track" => Ok(RepeatState::Track),
+            "Playlist" => Ok(RepeatState::Context),
+            _ => Err(fdo::Error::InvalidArgs(format!(
+                "Invalid loop status {status}"
+            ))),
+        }
+    }
+
+    /// Change requested over D-Bus, applied to a freshly fetched playback.
+    #[derive(Debug, Clone)]
+    enum Action {
+        Play,
+        Pause,
+        PlayPause,
+        Next,
+        Previous,
+        Seek(Duration),
+        SetPosition(Duration),
+        Volume(u8),
+        Shuffle(bool),
+        Repeat(RepeatState),
+        OpenUri(String),
+    }
+
+    /// Fetch the playback and apply an action to it.
+    async fn apply(backend: Arc<dyn PlayerBackend>, action: Action) -> Result<()> {
+        let curr = CurrentlyPlaying::from_backend(backend)
+            .await
+            .context(Error::Connect)?;
+
+        match action {
+            Action::Play => curr.play().await,
+            Action::Pause => curr.pause().await,
+            Action::PlayPause => curr.toggle_play_pause().await,
+            Action::Next => curr.next().await,
+            Action::Previous => curr.previous().await,
+            Action::Seek(offset) => {
+                let position = curr.progress.unwrap_or_else(Duration::zero) + offset;
+                // seeking past the end moves to the next track
+                if curr.duration.is_some_and(|duration| position > duration) {
+                    curr.next().await
+                } else {
+                    curr.seek_to(position).await
+                }
+            },
+            Action::SetPosition(position) => curr.seek_to(position).await,
+            Action::Volume(volume) => curr.set_volume(volume).await,
+            Action::Shuffle(state) => curr.shuffle(state).await,
+            Action::Repeat(state) => curr.repeat(state).await,
+            Action::OpenUri(uri) if uri.starts_with("http") => curr.play_from_url(uri).await,
+            Action::OpenUri(uri) => curr.play_from_uri(uri).await,
+        }
+    }
+
+    /// The `org.mpris.MediaPlayer2` interface.
+    struct Root;
+
+    // the interface macro does not accept const methods
+    #[allow(clippy::missing_const_for_fn)]
+    #[dbus_interface(name = "org.mpris.MediaPlayer2")]
+    impl Root {
+        /// There is no window to raise.
+        fn raise(&self) {}
+
+        /// Quitting is left to whoever started the bridge.
+        fn quit(&self) {}
+
+        #[dbus_interface(property)]
+        fn can_quit(&self) -> bool {
+            false
+        }
+
+        #[dbus_interface(property)]
+        fn can_raise(&self) -> bool {
+            false
+        }
+
+        #[dbus_interface(property)]
+        fn has_track_list(&self) -> bool {
+            false
+        }
+
+        #[dbus_interface(property)]
+        fn identity(&self) -> &str {
+            "rspotify-cli"
+        }
+
+        #[dbus_interface(property)]
+        fn supported_uri_schemes(&self) -> Vec<&str> {
+            vec!["spotify", "https"]
+        }
+
+        #[dbus_interface(property)]
+        fn supported_mime_types(&self) -> Vec<&str> {
+            Vec::new()
+        }
+    }
+
+    /// The `org.mpris.MediaPlayer2.Player` interface, answering from the last
+    /// polled snapshot.
+    struct Player {
+        /// Backend the actions are applied with.
+        backend: Arc<dyn PlayerBackend>,
+
+        /// Runtime the backend runs on, as zbus dispatches calls on its own
+        /// executor.
+        runtime: Handle,
+
+        /// Last polled playback.
+        snapshot: Snapshot,
+
+        /// When the snapshot was polled.
+        polled_at: Instant,
+    }
+
+    impl Player {
+        /// Apply an action on the runtime of the backend.
+        async fn apply(&self, action: Action) -> fdo::Result<()> {
+            self.runtime
+                .spawn(apply(Arc::clone(&self.backend), action))
+                .await
+                .map_err(|err| fdo::Error::Failed(err.to_string()))?
+                .map_err(|err| fdo::Error::Failed(format!("{err:#}")))
+        }
+    }
+
+    // the interface macro does not accept const methods
+    #[allow(clippy::missing_const_for_fn)]
+    #[dbus_interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl Player {
+        async fn next(&self) -> fdo::Result<()> {
+            self.apply(Action::Next).await
+        }
+
+        async fn previous(&self) -> fdo::Result<()> {
+            self.apply(Action::Previous).await
+        }
+
+        async fn pause(&self) -> fdo::Result<()> {
+            self.apply(Action::Pause).await
+        }
+
+        async fn play_pause(&self) -> fdo::Result<()> {
+            self.apply(Action::PlayPause).await
+        }
+
+        /// Spotify can't stop, so this pauses.
+        async fn stop(&self) -> fdo::Result<()> {
+            self.apply(Action::Pause).await
+        }
+
+        async fn play(&self) -> fdo::Result<()> {
+            self.apply(Action::Play).await
+        }
+
+        /// Seek by `offset` microseconds.
+        async fn seek(&self, offset: i64) -> fdo::Result<()> {
+            self.apply(Action::Seek(Duration::microseconds(offset)))
+                .await
+        }
+
+        /// Seek to `position` microseconds, ignored if `track_id` is not the
+        /// loaded item anymore.
+        async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+            if track_id.as_str() != item_path(&self.snapshot) || position < 0 {
+                return Ok(());
+            }
+            self.apply(Action::SetPosition(Duration::microseconds(position)))
+                .await
+        }
+
+        async fn open_uri(&self, uri: String) -> fdo::Result<()> {
+            self.apply(Action::OpenUri(uri)).await
+        }
+
+        /// Sent when the progress jumps.
+        #[dbus_interface(signal)]
+        async fn seeked(ctxt: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+        #[dbus_interface(property)]
+        fn playback_status(&self) -> &str {
+            playback_status(&self.snapshot)
+        }
+
+        #[dbus_interface(property)]
+        fn loop_status(&self) -> &str {
+            loop_status(self.snapshot.repeat_state)
+        }
+
+        #[dbus_interface(property)]
+        async fn set_loop_status(&mut self, status: String) -> fdo::Result<()> {
+            let state = parse_loop_status(&status)?;
+            self.apply(Action::Repeat(state)).await?;
+            self.snapshot.repeat_state = Some(state);
+            Ok(())
+        }
+
+        #[dbus_interface(property)]
+        fn rate(&self) -> f64 {
+            1.0
+        }
+
+        #[dbus_interface(property)]
+        fn minimum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[dbus_interface(property)]
+        fn maximum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[dbus_interface(property)]
+        fn shuffle(&self) -> bool {
+            self.snapshot.is_shuffled.unwrap_or_default()
+        }
+
+        #[dbus_interface(property)]
+        async fn set_shuffle(&mut self, state: bool) -> fdo::Result<()> {
+            self.apply(Action::Shuffle(state)).await?;
+            self.snapshot.is_shuffled = Some(state);
+            Ok(())
+        }
+
+        #[dbus_interface(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            metadata(&self.snapshot)
+        }
+
+        /// Volume between 0 and 1.
+        #[dbus_interface(property)]
+        fn volume(&self) -> f64 {
+            f64::from(self.snapshot.volume.unwrap_or_default()) / 100.0
+        }
+
+        #[dbus_interface(property)]
+        async fn set_volume(&mut self, volume: f64) -> fdo::Result<()> {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let volume = (volume.clamp(0.0, 1.0) * 100.0).round() as u8;
+            self.apply(Action::Volume(volume)).await?;
+            self.snapshot.volume = Some(volume);
+            Ok(())
+        }
+
+        /// Progress in microseconds, advanced locally since the last poll.
+        #[dbus_interface(property)]
+        fn position(&self) -> i64 {
+            self.snapshot
+                .interpolate(self.polled_at.elapsed())
+                .progress
+                .map_or(0, micros)
+        }
+
+        #[dbus_interface(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn can_go_previous(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn can_play(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn can_pause(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn can_seek(&self) -> bool {
+            true
+        }
+
+        #[dbus_interface(property)]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+
+    /// Returns the MPRIS metadata of a snapshot.
+    fn metadata(snapshot: &Snapshot) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        let mut insert = |key: &str, value: Value<'_>| {
+            metadata.insert(key.to_owned(), OwnedValue::from(value));
+        };
+
+        if let Ok(path) = ObjectPath::try_from(item_path(snapshot)) {
+            insert("mpris:trackid", path.into());
+        }
+        if let Some(duration) = snapshot.duration {
+            insert("mpris:length", micros(duration).into());
+        }
+        if let Some(title) = &snapshot.title {
+            insert("xesam:title", title.as_str().into());
+        }
+        if let Some(artist) = &snapshot.artist {
+            insert("xesam:artist", vec![artist.as_str()].into());
+        }
+        if let Some(url) = &snapshot.url {
+            insert("xesam:url", url.as_str().into());
+        }
+        metadata
+    }
+
+    /// Own [`BUS_NAME`] on the session bus and keep its properties in sync
+    /// with the playback, polled every `interval`. Failed polls are reported
+    /// and retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the session bus can't be reached or the name is
+    /// taken.
+    pub async fn serve(backend: Arc<dyn PlayerBackend>, interval: StdDuration) -> Result<()> {
+        let mut poller = Poller::new(Arc::clone(&backend), interval, false);
+        let (snapshot, mut wait) = poller.poll_retrying().await;
+
+        let player = Player {
+            backend,
+            runtime: Handle::current(),
+            snapshot,
+            polled_at: Instant::now(),
+        };
+        let connection = ConnectionBuilder::session()
+            .and_then(|builder| builder.name(BUS_NAME))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, Root))
+            .and_then(|builder| builder.serve_at(OBJECT_PATH, player))
+            .context(Error::SessionBus)?
+            .build()
+            .await
+            .context(Error::SessionBus)?;
+        println!("Registered {BUS_NAME} on the session bus");
+
+        let player = connection
+            .object_server()
+            .interface::<_, Player>(OBJECT_PATH)
+            .await?;
+        let ctxt = player.signal_context();
+
+        loop {
+            tokio::time::sleep(wait).await;
+            let (snapshot, next_wait) = poller.poll_retrying().await;
+            wait = next_wait;
+
+            let events = {
+                let mut iface = player.get_mut().await;
+                let events = diff(
+                    &iface.snapshot,
+                    &snapshot,
+                    iface.polled_at.elapsed(),
+                    Utc::now(),
+                );
+                iface.snapshot = snapshot;
+                iface.polled_at = Instant::now();
+                events
+            };
+            publish(&*player.get().await, ctxt, &events).await?;
+        }
+    }
+
+    /// Signal the properties changed by some events.
+    async fn publish(player: &Player, ctxt: &SignalContext<'_>, events: &[Event]) -> Result<()> {
+        for event in events {
+            match event.event {
+                EventKind::TrackChanged => {
+                    player.metadata_changed(ctxt).await?;
+                    player.playback_status_changed(ctxt).await?;
+                },
+                EventKind::Paused | EventKind::Resumed | EventKind::DeviceChanged => {
+                    player.playback_status_changed(ctxt).await?;
+                },
+                EventKind::Seeked => Player::seeked(ctxt, player.position()).await?,
+                EventKind::VolumeChanged => player.volume_changed(ctxt).await?,
+                EventKind::ShuffleChanged => player.shuffle_changed(ctxt).await?,
+                EventKind::RepeatChanged => player.loop_status_changed(ctxt).await?,
+                EventKind::Liked | EventKind::Unliked => {},
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn snapshot() -> Snapshot {
+            Snapshot {
+                id: Some("spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+                uri: Some("spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+                url: Some("https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+                title: Some("Title".to_owned()),
+                artist: Some("Artist".to_owned()),
+                duration: Some(Duration::seconds(200)),
+                is_playing: Some(false),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn statuses() {
+            assert_eq!(playback_status(&Snapshot::default()), "Stopped");
+            assert_eq!(playback_status(&snapshot()), "Paused");
+            for state in [RepeatState::Off, RepeatState::Track, RepeatState::Context] {
+                assert_eq!(parse_loop_status(loop_status(Some(state))).unwrap(), state);
+            }
+            assert!(parse_loop_status("Forever").is_err());
+        }
+
+        #[test]
+        fn metadata_of_track() {
+            let metadata = metadata(&snapshot());
+            let value = |key: &str| Value::from(metadata[key].clone());
+            assert_eq!(
+                value("mpris:trackid"),
+                Value::from(
+                    ObjectPath::try_from(
+                        "/org/mpris/MediaPlayer2/rspotify/track/4cOdK2wGLETKBW3PvgPWqT"
+                    )
+                    .unwrap()
+                )
+            );
+            assert_eq!(value("mpris:length"), Value::from(200_000_000_i64));
+            assert_eq!(value("xesam:title"), Value::from("Title"));
+            assert_eq!(value("xesam:artist"), Value::from(vec!["Artist"]));
+
+            let metadata = super::metadata(&Snapshot::default());
+            assert_eq!(metadata.len(), 1);
+            assert_eq!(
+                Value::from(metadata["mpris:trackid"].clone()),
+                Value::from(ObjectPath::try_from(NO_TRACK).unwrap())
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod fallback {
+    use std::{sync::Arc, time::Duration};
+
+    use anyhow::Result;
+    use rspotify_cli_lib::backend::PlayerBackend;
+
+    use crate::error::Error;
+
+    /// MPRIS is only available on Linux.
+    pub async fn serve(_backend: Arc<dyn PlayerBackend>, _interval: Duration) -> Result<()> {
+        anyhow::bail!(Error::MprisUnsupported)
+    }
+}
@@ -0,0 +1,145 @@
+//! Ad-hoc lists of items for `play-from`.
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use rspotify::model::Offset;
+use rspotify_cli_lib::{
+    currently_playing::CurrentlyPlaying,
+    url_convert::{to_playable_id, to_playable_uri},
+};
+
+use crate::error::Error;
+
+/// Number items given on the command line from 1, like the lines of a file.
+pub fn number(items: Vec<String>) -> Vec<(usize, String)> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| (i + 1, item))
+        .collect()
+}
+
+/// Convert every numbered item, reporting each invalid one by `label` with
+/// its number, all of them together.
+fn convert_all<T>(
+    items: &[(usize, String)],
+    label: fn(usize) -> Error,
+    convert: fn(&str) -> Result<T>,
+) -> Result<Vec<T>> {
+    let mut converted = Vec::new();
+    let mut errors = Vec::new();
+    for (number, item) in items {
+        match convert(item).with_context(|| label(*number)) {
+            Ok(item) => converted.push(item),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(converted),
+        1 => Err(errors.remove(0)),
+        count => {
+            let errors = errors.iter().map(|err| format!("{err:#}"));
+            anyhow::bail!(Error::InvalidItems(
+                count,
+                errors.collect::<Vec<_>>().join("\n")
+            ))
+        },
+    }
+}
+
+/// Play numbered URLs or URIs as one list of tracks and episodes, or a single
+/// one of any kind, such as an album.
+///
+/// Every item is converted before anything is played, and the invalid ones
+/// are reported by `label` with their numbers.
+pub async fn play_items(
+    curr: &CurrentlyPlaying,
+    items: Vec<(usize, String)>,
+    label: fn(usize) -> Error,
+    offset: Option<Offset>,
+    position: Option<Duration>,
+) -> Result<()> {
+    match &items[..] {
+        [] => anyhow::bail!(Error::NoItems),
+        [_] => {
+            let uri = convert_all(&items, label, to_playable_uri)?.remove(0);
+            curr.play_from_uri_at(&uri, offset, position).await
+        },
+        _ => {
+            let ids = convert_all(&items, label, to_playable_id)?;
+            curr.play_items(ids, offset, position).await
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rspotify::model::TrackId;
+    use rspotify_cli_lib::fake_backend::{track, Call, FakeBackend};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn plays_one_list() {
+        let id = TrackId::from_id("4cOdK2wGLETKBW3PvgPWqT").unwrap();
+        let playing = track(id, "Title", "Artist", Duration::seconds(200));
+        let backend = Arc::new(FakeBackend::with_track(playing));
+        let curr = CurrentlyPlaying::from_backend(backend.clone())
+            .await
+            .unwrap();
+        let items = vec![
+            (1, "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+            (
+                3,
+                "https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ".to_owned(),
+            ),
+        ];
+        play_items(&curr, items, Error::Line, None, None)
+            .await
+            .unwrap();
+
+        let items = number(vec![
+            "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned(),
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy".to_owned(),
+        ]);
+        let err = play_items(&curr, items, Error::Item, None, None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            format!("{err:#}"),
+            "Invalid item 2: Only tracks and episodes can be played in a list, not \
+             spotify:album:4aawyAB9vmqN3uQ7FjRGTy"
+        );
+
+        // every invalid line is reported
+        let items = vec![
+            (1, "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+            (2, "spotify:track:not an id".to_owned()),
+            (4, "some song".to_owned()),
+        ];
+        let err = play_items(&curr, items, Error::Line, None, None)
+            .await
+            .unwrap_err();
+        let err = format!("{err:#}");
+        assert!(err.starts_with("2 items are invalid:\nInvalid item on line 2: "));
+        assert!(err.contains("\nInvalid item on line 4: "));
+
+        // a single item is checked too
+        let items = vec![(3, "spotify:album:not an id".to_owned())];
+        let err = play_items(&curr, items, Error::Line, None, None)
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").starts_with("Invalid item on line 3: "));
+
+        assert_eq!(
+            backend.calls(),
+            vec![Call::PlayUris(vec![
+                "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned(),
+                "spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned()
+            ])]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Playing from a search, `play-from --search`.
+
+use std::io::{stderr, stdin, BufRead, Write};
+
+use anyhow::Result;
+use chrono::Duration;
+use clap::ValueEnum;
+use rspotify::model::Offset;
+use rspotify_cli_lib::currently_playing::{CurrentlyPlaying, SearchHit, SearchType};
+
+use crate::error::Error;
+
+/// How many results `--pick` offers.
+const PICK_LIMIT: u32 = 10;
+
+/// Kind of item to search for.
+#[derive(ValueEnum, Clone, Debug, Copy, PartialEq, Eq)]
+pub enum SearchKind {
+    /// Play a track.
+    Track,
+
+    /// Play an album.
+    Album,
+
+    /// Play the top tracks of an artist.
+    Artist,
+
+    /// Play a playlist.
+    Playlist,
+
+    /// Play a show.
+    Show,
+}
+
+impl From<SearchKind> for SearchType {
+    fn from(kind: SearchKind) -> Self {
+        match kind {
+            SearchKind::Track => Self::Track,
+            SearchKind::Album => Self::Album,
+            SearchKind::Artist => Self::Artist,
+            SearchKind::Playlist => Self::Playlist,
+            SearchKind::Show => Self::Show,
+        }
+    }
+}
+
+/// List `hits` to `prompt` and read the number of one from `input`, asking
+/// again until the answer is a number of the list.
+///
+/// # Errors
+///
+/// Returns an error if the answer is `q`, or the input ends first.
+fn pick<'a>(
+    hits: &'a [SearchHit],
+    input: &mut impl BufRead,
+    prompt: &mut impl Write,
+) -> Result<&'a SearchHit> {
+    for (i, hit) in hits.iter().enumerate() {
+        writeln!(prompt, "{:>2}. {}", i + 1, hit.description)?;
+    }
+    loop {
+        write!(prompt, "Play which? [1-{}, q to cancel] ", hits.len())?;
+        prompt.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            anyhow::bail!(Error::NothingPicked);
+        }
+        let answer = answer.trim();
+        if answer.eq_ignore_ascii_case("q") {
+            anyhow::bail!(Error::NothingPicked);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=hits.len()).contains(&number) => return Ok(&hits[number - 1]),
+            _ => writeln!(prompt, "Invalid choice {answer}")?,
+        }
+    }
+}
+
+/// Play the top result of a search for `query`, or the one picked from a list
+/// of results on the terminal, and return what is played.
+pub async fn play_search(
+    curr: &CurrentlyPlaying,
+    query: &str,
+    kind: SearchKind,
+    is_picked: bool,
+    offset: Option<Offset>,
+    position: Option<Duration>,
+) -> Result<String> {
+    let limit = if is_picked { PICK_LIMIT } else { 1 };
+    let kind = SearchType::from(kind);
+    let hits = curr.search_hits(query, kind, limit).await?;
+    let hit = match &hits[..] {
+        [] => anyhow::bail!(Error::NoSearchHits(
+            <&str>::from(kind).to_owned(),
+            query.to_owned()
+        )),
+        _ if is_picked => pick(&hits, &mut stdin().lock(), &mut stderr())?,
+        [hit, ..] => hit,
+    };
+    curr.play_from_uri_at(&hit.uri, offset, position).await?;
+    Ok(format!("Playing {} ({})", hit.description, hit.uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits() -> Vec<SearchHit> {
+        ["Title - Artist", "Other - Artist"]
+            .iter()
+            .enumerate()
+            .map(|(i, description)| SearchHit {
+                uri: format!("spotify:track:{i}"),
+                description: (*description).to_owned(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn picks_by_number() {
+        let hits = hits();
+        let mut prompt = Vec::new();
+        let hit = pick(&hits, &mut &b"3\nnope\n2\n"[..], &mut prompt).unwrap();
+        assert_eq!(hit.description, "Other - Artist");
+        assert_eq!(
+            String::from_utf8(prompt).unwrap(),
+            " 1. Title - Artist\n 2. Other - Artist\nPlay which? [1-2, q to cancel] Invalid \
+             choice 3\nPlay which? [1-2, q to cancel] Invalid choice nope\nPlay which? [1-2, q \
+             to cancel] "
+        );
+    }
+
+    #[test]
+    fn cancels() {
+        let hits = hits();
+        assert!(pick(&hits, &mut &b"q\n"[..], &mut Vec::new()).is_err());
+        assert!(pick(&hits, &mut &b""[..], &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Positions accepted by `control --seek`, and where `play-from` starts.
+
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use rspotify::model::{parse_uri, Offset, Type};
+use rspotify_cli_lib::{error::Error as LibError, url_convert::url_to_uri};
+
+use crate::{
+    error::Error,
+    pretty_duration::{parse_pretty, PrettyDuration},
+};
+
+/// Where to seek to in the current item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// To a time from the start, `1:23`, `1:02:03` or `90` seconds.
+    To(Duration),
+
+    /// By an offset from the progress, `+15s` or `-30`.
+    By(Duration),
+
+    /// To a percentage of the duration, `50%`.
+    Percent(u8),
+}
+
+/// Parse a number of seconds with an optional `s` suffix, or a time.
+fn parse_time(text: &str) -> Option<Duration> {
+    if text.contains(':') {
+        return parse_pretty(text).ok();
+    }
+    let seconds = text.strip_suffix('s').unwrap_or(text);
+    if seconds.is_empty() || !seconds.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    seconds.parse().ok().and_then(Duration::try_seconds)
+}
+
+impl FromStr for Position {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let position = match (text.chars().next(), text.strip_suffix('%')) {
+            (Some('+'), _) => parse_time(&text[1..]).map(Self::By),
+            (Some('-'), _) => parse_time(&text[1..]).map(|offset| Self::By(-offset)),
+            (_, Some(percent)) => percent
+                .parse()
+                .ok()
+                .filter(|percent| *percent <= 100)
+                .map(Self::Percent),
+            _ => parse_time(text).map(Self::To),
+        };
+        position.ok_or_else(|| Error::InvalidPosition(text.to_owned()).into())
+    }
+}
+
+impl Position {
+    /// Returns the position from the start of an item of `duration`, of which
+    /// `progress` has been played.
+    ///
+    /// Seeking back past the start goes to the start.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the position is past the end, or if it depends on
+    /// a progress or duration that is unknown.
+    pub fn target(
+        self,
+        progress: Option<Duration>,
+        duration: Option<Duration>,
+    ) -> Result<Duration> {
+        let target = match self {
+            Self::To(position) => position,
+            Self::By(offset) => progress
+                .ok_or(LibError::NoActiveDevice)?
+                .checked_add(&offset)
+                .ok_or_else(|| Error::InvalidPosition(format!("{:+}s", offset.num_seconds())))?,
+            Self::Percent(percent) => {
+                duration.ok_or(LibError::NoActiveDevice)? * percent.into() / 100
+            },
+        };
+        let target = target.max(Duration::zero());
+        if let Some(duration) = duration.filter(|duration| target > *duration) {
+            anyhow::bail!(Error::PositionPastEnd(target.pretty(), duration.pretty()));
+        }
+        Ok(target)
+    }
+}
+
+/// Parse where to start in the first item, a time from its start.
+pub fn parse_start(text: &str) -> Result<Duration> {
+    match text.parse() {
+        Ok(Position::To(position)) => Ok(position),
+        _ => anyhow::bail!(Error::InvalidStart(text.to_owned())),
+    }
+}
+
+/// Parse which item of a context to start at, its index from 0 or the URI or
+/// URL of a track or episode.
+pub fn parse_offset(text: &str) -> Result<Offset> {
+    let invalid = || Error::InvalidOffset(text.to_owned());
+    if let Ok(index) = text.parse::<u32>() {
+        // rspotify sends the index as the milliseconds of the duration
+        return Ok(Offset::Position(Duration::milliseconds(index.into())));
+    }
+    let uri = if text.starts_with("http") {
+        url_to_uri(text).context(invalid())?
+    } else {
+        text.to_owned()
+    };
+    match parse_uri(&uri) {
+        Ok((Type::Track | Type::Episode, _)) => Ok(Offset::Uri(uri)),
+        _ => anyhow::bail!(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Position {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(parse("10"), Position::To(Duration::seconds(10)));
+        assert_eq!(parse("300s"), Position::To(Duration::seconds(300)));
+        assert_eq!(parse("1:23"), Position::To(Duration::seconds(83)));
+        assert_eq!(parse("1:02:03"), Position::To(Duration::seconds(3723)));
+        assert_eq!(parse("+15s"), Position::By(Duration::seconds(15)));
+        assert_eq!(parse("-30"), Position::By(Duration::seconds(-30)));
+        assert_eq!(parse("-1:30"), Position::By(Duration::seconds(-90)));
+        assert_eq!(parse("50%"), Position::Percent(50));
+        for invalid in [
+            "",
+            "s",
+            "+",
+            "--1",
+            "1.5",
+            "1:60",
+            "101%",
+            "-5%",
+            "abc",
+            "99999999999999999",
+            "-99999999999999999s",
+            "99999999999999:00:00",
+        ] {
+            assert!(invalid.parse::<Position>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn targets() {
+        let progress = Some(Duration::seconds(61));
+        let duration = Some(Duration::seconds(200));
+        let target = |text| parse(text).target(progress, duration);
+
+        assert_eq!(target("1:23").unwrap(), Duration::seconds(83));
+        assert_eq!(target("+15s").unwrap(), Duration::seconds(76));
+        assert_eq!(target("-1:30").unwrap(), Duration::zero());
+        assert_eq!(target("50%").unwrap(), Duration::seconds(100));
+        assert_eq!(target("100%").unwrap(), Duration::seconds(200));
+        assert_eq!(
+            target("4:00").unwrap_err().to_string(),
+            "Position 4:00 is past the end of the item at 3:20"
+        );
+        assert!(target("+2:20").is_err());
+
+        // an offset too large to add to the progress
+        let too_far = Position::By(Duration::max_value());
+        assert!(too_far.target(progress, None).is_err());
+
+        assert_eq!(
+            parse("1:02:03").target(None, None).unwrap(),
+            Duration::seconds(3723)
+        );
+        assert!(parse("+15").target(None, None).is_err());
+        assert!(parse("50%").target(None, None).is_err());
+    }
+
+    #[test]
+    fn starts() {
+        assert_eq!(parse_start("90").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_start("1:23").unwrap(), Duration::seconds(83));
+        assert!(parse_start("+15").is_err());
+        assert!(parse_start("50%").is_err());
+        assert!(parse_start("9999999999999999").is_err());
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(
+            parse_offset("2").unwrap(),
+            Offset::Position(Duration::milliseconds(2))
+        );
+        assert_eq!(
+            parse_offset("spotify:track:4cOdK2wGLETKBW3PvgPWqT").unwrap(),
+            Offset::Uri("spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned())
+        );
+        assert_eq!(
+            parse_offset("https://open.spotify.com/episode/512ojhOuo1ktJprKbVcKyQ").unwrap(),
+            Offset::Uri("spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned())
+        );
+        for invalid in [
+            "-1",
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+            "https://example.com",
+            "x",
+        ] {
+            assert!(parse_offset(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -1,7 +1,10 @@
-//! Utility to convert duration to nicely formatted string.
+//! Utility to convert duration to nicely formatted string, and back.
 
+use anyhow::Result;
 use chrono::Duration;
 
+use crate::error::Error;
+
 /// Convert duration to nicely formatted string.
 pub trait PrettyDuration {
     /// Convert duration to nicely formatted string.
@@ -22,12 +25,74 @@ impl PrettyDuration for Duration {
     }
 }
 
+/// Parse a duration formatted by [`PrettyDuration::pretty`], `m:ss` or
+/// `h:mm:ss`.
+pub fn parse_pretty(text: &str) -> Result<Duration> {
+    let invalid = || Error::InvalidDuration(text.to_owned());
+    let parts = text
+        .split(':')
+        .map(|part| {
+            if part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            part.parse::<i64>().ok()
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+
+    let (hours, minutes, seconds) = match parts[..] {
+        [minutes, seconds] => (0, minutes, seconds),
+        [hours, minutes, seconds] if minutes < 60 => (hours, minutes, seconds),
+        _ => anyhow::bail!(invalid()),
+    };
+    if seconds >= 60 {
+        anyhow::bail!(invalid());
+    }
+    hours
+        .checked_mul(60)
+        .and_then(|total| total.checked_add(minutes))
+        .and_then(|total| total.checked_mul(60))
+        .and_then(|total| total.checked_add(seconds))
+        .and_then(Duration::try_seconds)
+        .ok_or_else(|| invalid().into())
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::Duration;
 
     use super::*;
 
+    #[test]
+    fn round_trip() {
+        for seconds in [0, 1, 59, 60, 61, 599, 610, 3599, 3600, 3661, 36610, 360_000] {
+            let duration = Duration::seconds(seconds);
+            assert_eq!(parse_pretty(&duration.pretty()).unwrap(), duration);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_pretty("1:23").unwrap(), Duration::seconds(83));
+        assert_eq!(parse_pretty("75:00").unwrap(), Duration::seconds(4500));
+        assert_eq!(parse_pretty("1:02:03").unwrap(), Duration::seconds(3723));
+        for invalid in [
+            "",
+            "12",
+            "1:60",
+            "1:60:00",
+            "1::00",
+            "-1:00",
+            "+1:00",
+            "1:2:3:4",
+            "a:00",
+            "99999999999999:00:00",
+            "9999999999999999999:00",
+        ] {
+            assert!(parse_pretty(invalid).is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn hour_min_sec() {
         let duration = Duration::seconds(3661); // 1 hour, 1 minute, 1 second
@@ -0,0 +1,143 @@
+//! Named account profiles, each with its own config and cached token.
+
+use std::{
+    fs::{self, create_dir_all},
+    io::Write,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    cli::ProfileCommands,
+    config::{get_config_dir, get_profile_dir, profile_dir_path, DEFAULT_PROFILE},
+    error::Error,
+    token_store::keyring_store,
+};
+
+/// File in the config directory holding the name of the default profile.
+const DEFAULT_PROFILE_FILE: &str = "default-profile";
+
+/// Get the profile used when none is given on the command line.
+pub fn default_profile() -> Result<String> {
+    let path = get_config_dir()?.join(DEFAULT_PROFILE_FILE);
+    let name = fs::read_to_string(path).unwrap_or_default();
+    let name = name.trim();
+
+    Ok(if name.is_empty() {
+        DEFAULT_PROFILE.to_owned()
+    } else {
+        name.to_owned()
+    })
+}
+
+/// Set the profile used when none is given on the command line.
+pub fn set_default_profile(profile: &str) -> Result<()> {
+    get_profile_dir(profile)?;
+
+    let path = get_config_dir()?.join(DEFAULT_PROFILE_FILE);
+    if profile == DEFAULT_PROFILE {
+        if path.exists() {
+            fs::remove_file(path).context(Error::Config)?;
+        }
+    } else {
+        fs::write(path, profile).context(Error::Config)?;
+    }
+
+    Ok(())
+}
+
+/// List every profile, starting with the default one.
+pub fn list_profiles() -> Result<Vec<String>> {
+    let mut profiles = Vec::new();
+
+    let profiles_dir = get_config_dir()?.join("profiles");
+    if profiles_dir.is_dir() {
+        for entry in fs::read_dir(profiles_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Some(name) = entry.file_name().to_str() {
+                    profiles.push(name.to_owned());
+                }
+            }
+        }
+    }
+    profiles.sort();
+    profiles.insert(0, DEFAULT_PROFILE.to_owned());
+
+    Ok(profiles)
+}
+
+/// Add a profile, optionally writing its client credentials to its config
+/// file.
+pub fn add_profile(
+    profile: &str,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Result<()> {
+    let profile_dir = profile_dir_path(profile)?;
+    if profile == DEFAULT_PROFILE || profile_dir.exists() {
+        anyhow::bail!(Error::ProfileExists(profile.to_owned()));
+    }
+
+    let mut config = toml::Table::new();
+    if let Some(client_id) = client_id {
+        config.insert("client_id".to_owned(), client_id.into());
+    }
+    if let Some(client_secret) = client_secret {
+        config.insert("client_secret".to_owned(), client_secret.into());
+    }
+
+    create_dir_all(&profile_dir).context(Error::Config)?;
+    fs::write(profile_dir.join("config.toml"), toml::to_string(&config)?).context(Error::Config)
+}
+
+/// Remove a profile along with its config and token.
+pub fn remove_profile(profile: &str) -> Result<()> {
+    if profile == DEFAULT_PROFILE {
+        anyhow::bail!(Error::RemoveDefaultProfile);
+    }
+
+    fs::remove_dir_all(get_profile_dir(profile)?).context(Error::Config)?;
+
+    let keyring = keyring_store(profile);
+    if keyring.is_available() {
+        keyring.remove()?;
+    }
+
+    if default_profile()? == profile {
+        set_default_profile(DEFAULT_PROFILE)?;
+    }
+
+    Ok(())
+}
+
+/// Run a profile command.
+pub fn profile_command(command: ProfileCommands, out: &mut impl Write) -> Result<()> {
+    match command {
+        ProfileCommands::List => {
+            let default = default_profile()?;
+            for profile in list_profiles()? {
+                let marker = if profile == default { '*' } else { ' ' };
+                writeln!(out, "{marker} {profile}")?;
+            }
+        },
+        ProfileCommands::Add {
+            name,
+            client_id,
+            client_secret,
+        } => {
+            add_profile(&name, client_id, client_secret)?;
+            writeln!(out, "Added profile {name}")?;
+        },
+        ProfileCommands::Remove { name } => {
+            remove_profile(&name)?;
+            writeln!(out, "Removed profile {name}")?;
+        },
+        ProfileCommands::Default { name } => {
+            set_default_profile(&name)?;
+            writeln!(out, "Default profile is now {name}")?;
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! Live updates pushed to WebSocket clients, like browser overlays.
+//!
+//! Every client gets a `state` message with the same fields as
+//! `status --json` when it connects and whenever the playback changes, and a
+//! `tick` message with the locally advanced progress every second. The
+//! playback is polled once for all clients.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use serde_json::json;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, watch},
+};
+
+use crate::{
+    events::diff,
+    snapshot::{Polled, Snapshot},
+    websocket::{read_frame, write_frame, Frame, Opcode},
+};
+
+/// How often ticks are sent.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Returns the message sent when the playback changes.
+fn state_message(snapshot: &Snapshot) -> String {
+    json!({ "type": "state", "data": snapshot.to_json() }).to_string()
+}
+
+/// Returns the message sent every second.
+fn tick_message(snapshot: &Snapshot) -> String {
+    json!({
+        "type": "tick",
+        "progress": snapshot.progress.map(|progress| progress.num_seconds()),
+        "duration": snapshot.duration.map(|duration| duration.num_seconds()),
+        "is_playing": snapshot.is_playing,
+    })
+    .to_string()
+}
+
+/// Push updates to a connected client until it goes away.
+///
+/// `playback` must only see snapshots polled after the client connected.
+///
+/// # Errors
+///
+/// Returns an error if the client can't be written to.
+pub async fn push(
+    stream: impl AsyncRead + AsyncWrite + Send + 'static,
+    mut playback: watch::Receiver<Polled>,
+) -> Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // frames are read in their own task, as reading one can't be cancelled
+    let (frames_tx, mut frames) = mpsc::channel(8);
+    let read_task = tokio::spawn(async move {
+        while let Ok(frame) = read_frame(&mut reader).await {
+            if frames_tx.send(frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        playback.changed().await?;
+        let mut last = playback.borrow_and_update().clone();
+        write_frame(
+            &mut writer,
+            Opcode::Text,
+            state_message(&last.snapshot).as_bytes(),
+        )
+        .await?;
+
+        let mut tick = tokio::time::interval(TICK);
+        tick.tick().await;
+        loop {
+            tokio::select! {
+                changed = playback.changed() => {
+                    changed?;
+                    let polled = playback.borrow_and_update().clone();
+                    let elapsed = polled.at.duration_since(last.at);
+                    if !diff(&last.snapshot, &polled.snapshot, elapsed, Utc::now()).is_empty() {
+                        let message = state_message(&polled.snapshot);
+                        write_frame(&mut writer, Opcode::Text, message.as_bytes()).await?;
+                    }
+                    last = polled;
+                },
+                _ = tick.tick() => {
+                    let message = tick_message(&last.snapshot.interpolate(last.at.elapsed()));
+                    write_frame(&mut writer, Opcode::Text, message.as_bytes()).await?;
+                },
+                frame = frames.recv() => match frame {
+                    Some(Frame { opcode: Opcode::Ping, payload }) => {
+                        write_frame(&mut writer, Opcode::Pong, &payload).await?;
+                    },
+                    Some(Frame { opcode: Opcode::Close, payload }) => {
+                        write_frame(&mut writer, Opcode::Close, &payload).await?;
+                        return Ok(());
+                    },
+                    Some(_) => {},
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+    .await;
+
+    read_task.abort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration as ChronoDuration;
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn messages() {
+        let snapshot = Snapshot {
+            title: Some("Title".to_owned()),
+            progress: Some(ChronoDuration::seconds(61)),
+            is_playing: Some(true),
+            ..Default::default()
+        };
+
+        let state: Value = serde_json::from_str(&state_message(&snapshot)).unwrap();
+        assert_eq!(state["type"], "state");
+        assert_eq!(state["data"]["title"], "Title");
+        let state: Value = serde_json::from_str(&state_message(&Snapshot::default())).unwrap();
+        assert_eq!(state["data"], Value::Null);
+
+        assert_eq!(
+            tick_message(&snapshot),
+            r#"{"type":"tick","progress":61,"duration":null,"is_playing":true}"#
+        );
+    }
+}
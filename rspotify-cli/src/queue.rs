@@ -0,0 +1,209 @@
+//! Output of `queue show`, and the items read by `queue add --from-file`.
+
+use std::{fs, io, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use rspotify::{
+    model::{CurrentUserQueue, PlayableItem},
+    prelude::Id,
+};
+use rspotify_cli_lib::currently_playing::CurrentlyPlaying;
+use serde_json::{json, Value};
+
+use crate::{error::Error, pretty_duration::PrettyDuration};
+
+/// Returns the title and artist or show of an item.
+pub fn describe(item: &PlayableItem) -> String {
+    match item {
+        PlayableItem::Track(track) => {
+            let artist = track.artists.first().map_or("", |artist| &artist.name);
+            format!("{} - {artist}", track.name)
+        },
+        PlayableItem::Episode(episode) => format!("{} - {}", episode.name, episode.show.name),
+    }
+}
+
+/// Returns the length of an item.
+const fn duration(item: &PlayableItem) -> Duration {
+    match item {
+        PlayableItem::Track(track) => track.duration,
+        PlayableItem::Episode(episode) => episode.duration,
+    }
+}
+
+/// Returns how long until the queue is played through, given how much of the
+/// current item has been played.
+fn remaining(queue: &CurrentUserQueue, progress: Option<Duration>) -> Duration {
+    let current = queue
+        .currently_playing
+        .as_ref()
+        .map_or_else(Duration::zero, |item| {
+            (duration(item) - progress.unwrap_or_else(Duration::zero)).max(Duration::zero())
+        });
+    queue
+        .queue
+        .iter()
+        .map(duration)
+        .fold(current, |total, d| total + d)
+}
+
+/// Returns the current item and the queue, one item per line, followed by the
+/// remaining time.
+pub fn render_queue(queue: &CurrentUserQueue, progress: Option<Duration>) -> String {
+    let mut lines = Vec::new();
+    if let Some(item) = &queue.currently_playing {
+        lines.push(format!(
+            "Now playing: {} ({})",
+            describe(item),
+            duration(item).pretty()
+        ));
+    }
+    for (i, item) in queue.queue.iter().enumerate() {
+        lines.push(format!(
+            "{}. {} ({})",
+            i + 1,
+            describe(item),
+            duration(item).pretty()
+        ));
+    }
+    lines.push(format!(
+        "Remaining: {}",
+        remaining(queue, progress).pretty()
+    ));
+
+    let mut output = lines.join("\n");
+    output.push('\n');
+    output
+}
+
+/// Returns the fields of an item that `queue show --json` prints.
+fn item_json(item: &PlayableItem) -> Value {
+    let (uri, title, artist) = match item {
+        PlayableItem::Track(track) => (
+            track.id.as_ref().map(Id::uri),
+            &track.name,
+            track.artists.first().map(|artist| &artist.name),
+        ),
+        PlayableItem::Episode(episode) => (
+            Some(episode.id.uri()),
+            &episode.name,
+            Some(&episode.show.name),
+        ),
+    };
+    json!({
+        "uri": uri,
+        "title": title,
+        "artist": artist,
+        "duration": duration(item).num_seconds(),
+    })
+}
+
+/// Returns the current item, the queue and the remaining time in seconds as
+/// JSON.
+pub fn queue_json(queue: &CurrentUserQueue, progress: Option<Duration>) -> Value {
+    json!({
+        "currently_playing": queue.currently_playing.as_ref().map(item_json),
+        "queue": queue.queue.iter().map(item_json).collect::<Vec<_>>(),
+        "remaining": remaining(queue, progress).num_seconds(),
+    })
+}
+
+/// Returns the items of a file, or of standard input if the path is `-`, with
+/// their line numbers, one per line, skipping blank lines and lines starting
+/// with `#`.
+pub fn read_items(path: &Path) -> Result<Vec<(usize, String)>> {
+    let contents = if path == Path::new("-") {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    }
+    .context(Error::ReadFile(path.display().to_string()))?;
+    Ok(contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.to_owned()))
+        .collect())
+}
+
+/// Queue every item of a file, in order.
+///
+/// Every item is resolved before anything is queued, so that a bad line
+/// doesn't leave the queue half filled.
+pub async fn enqueue_file(curr: &CurrentlyPlaying, path: &Path) -> Result<()> {
+    let mut uris = Vec::new();
+    for (number, item) in read_items(path)? {
+        let uri = curr
+            .resolve_uri(&item)
+            .await
+            .with_context(|| Error::Line(number))?;
+        uris.push(uri);
+    }
+    for uri in uris {
+        curr.add_to_queue(&uri).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use rspotify::model::TrackId;
+    use rspotify_cli_lib::fake_backend::track;
+
+    use super::*;
+
+    fn item(id: &str, title: &str, seconds: i64) -> PlayableItem {
+        let id = TrackId::from_id(id).unwrap().into_static();
+        PlayableItem::Track(track(id, title, "Artist", Duration::seconds(seconds)))
+    }
+
+    #[test]
+    fn render() {
+        let queue = CurrentUserQueue {
+            currently_playing: Some(item("4cOdK2wGLETKBW3PvgPWqT", "Title", 200)),
+            queue: vec![
+                item("2takcwOaAZWiXQijPHIx7B", "Next", 100),
+                item("3n3Ppam7vgaVa1iaRUc9Lp", "Last", 65),
+            ],
+        };
+        assert_eq!(
+            render_queue(&queue, Some(Duration::seconds(61))),
+            "Now playing: Title - Artist (3:20)\n1. Next - Artist (1:40)\n2. Last - Artist \
+             (1:05)\nRemaining: 5:04\n"
+        );
+
+        let json = queue_json(&queue, Some(Duration::seconds(61)));
+        assert_eq!(
+            json["queue"][0]["uri"],
+            "spotify:track:2takcwOaAZWiXQijPHIx7B"
+        );
+        assert_eq!(json["remaining"], 304);
+
+        let empty = CurrentUserQueue {
+            currently_playing: None,
+            queue: vec![],
+        };
+        assert_eq!(render_queue(&empty, None), "Remaining: 0:00\n");
+    }
+
+    #[test]
+    fn items() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            "spotify:track:4cOdK2wGLETKBW3PvgPWqT\n\n  # comment\n  some song \n"
+        )
+        .unwrap();
+        assert_eq!(
+            read_items(file.path()).unwrap(),
+            [
+                (1, "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()),
+                (4, "some song".to_owned())
+            ]
+        );
+    }
+}
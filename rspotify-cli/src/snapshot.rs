@@ -0,0 +1,268 @@
+//! Point-in-time copy of the current playback.
+
+use std::{
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+
+use anyhow::Result;
+use chrono::Duration;
+use rspotify::model::{CurrentlyPlayingType, PlayableId};
+use rspotify_cli_lib::{
+    backend::PlayerBackend, currently_playing::CurrentlyPlaying, error::Error as LibError,
+    repeat_state::RepeatState,
+};
+use serde_json::{json, Value};
+use tokio::sync::{watch, Notify};
+
+/// Longest wait between polls while no device is active.
+const MAX_BACKOFF: StdDuration = StdDuration::from_secs(60);
+
+/// Every field of [`CurrentlyPlaying`], with the liked status resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Id of the item.
+    pub id: Option<String>,
+
+    /// URL of the item.
+    pub url: Option<String>,
+
+    /// URI of the item.
+    pub uri: Option<String>,
+
+    /// Title of the item.
+    pub title: Option<String>,
+
+    /// Artist of the track, or show of the episode.
+    pub artist: Option<String>,
+
+    /// How much of the item has been played.
+    pub progress: Option<Duration>,
+
+    /// Total length of the item.
+    pub duration: Option<Duration>,
+
+    /// Volume of the active device.
+    pub volume: Option<u8>,
+
+    /// Whether the item is playing.
+    pub is_playing: Option<bool>,
+
+    /// Repeat state.
+    pub repeat_state: Option<RepeatState>,
+
+    /// Whether shuffle is on.
+    pub is_shuffled: Option<bool>,
+
+    /// Name of the active device.
+    pub device: Option<String>,
+
+    /// Type of the item.
+    pub playing_type: Option<CurrentlyPlayingType>,
+
+    /// Whether the track is liked, `None` for episodes or when not fetched.
+    pub is_liked: Option<bool>,
+}
+
+impl Snapshot {
+    /// Copy the current playback, fetching the liked status of tracks if
+    /// `with_liked` is set.
+    pub async fn capture(curr: &CurrentlyPlaying, with_liked: bool) -> Result<Self> {
+        let is_liked = if with_liked && matches!(curr.id, Some(PlayableId::Track(_))) {
+            Some(curr.is_liked().await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            id: curr.id().ok(),
+            url: curr.generate_url().ok(),
+            uri: curr.generate_uri().ok(),
+            title: curr.title.clone(),
+            artist: curr.artist.clone(),
+            progress: curr.progress,
+            duration: curr.duration,
+            volume: curr.volume,
+            is_playing: curr.is_playing,
+            repeat_state: curr.repeat_state,
+            is_shuffled: curr.is_shuffled,
+            device: curr.device.clone(),
+            playing_type: curr.playing_type,
+            is_liked,
+        })
+    }
+
+    /// Returns the same fields as [`CurrentlyPlaying::to_json`], or null while
+    /// nothing is loaded.
+    pub fn to_json(&self) -> Value {
+        if self.title.is_none() {
+            return Value::Null;
+        }
+        json!({
+            "id": self.id,
+            "title": self.title,
+            "artist": self.artist,
+            "progress": self.progress.map(|progress| progress.num_seconds()),
+            "duration": self.duration.map(|duration| duration.num_seconds()),
+            "is_playing": self.is_playing,
+            "repeat_state": self.repeat_state,
+            "is_shuffle": self.is_shuffled,
+            "device": self.device,
+            "playing_type": self.playing_type,
+            "is_liked": self.is_liked,
+        })
+    }
+
+    /// Advance the progress of a playing item by `elapsed`, up to its
+    /// duration.
+    pub fn interpolate(&self, elapsed: StdDuration) -> Self {
+        let mut snapshot = self.clone();
+        if snapshot.is_playing == Some(true) {
+            let elapsed = Duration::from_std(elapsed).unwrap_or_else(|_| Duration::zero());
+            snapshot.progress = snapshot.progress.map(|progress| {
+                let progress = progress + elapsed;
+                snapshot
+                    .duration
+                    .map_or(progress, |duration| progress.min(duration))
+            });
+        }
+        snapshot
+    }
+}
+
+/// A snapshot and when it was taken.
+#[derive(Debug, Clone)]
+pub struct Polled {
+    /// The playback.
+    pub snapshot: Snapshot,
+
+    /// When it was taken.
+    pub at: Instant,
+}
+
+/// Takes snapshots of the playback at an interval, backing off while no
+/// device is active.
+pub struct Poller {
+    /// Backend to poll.
+    backend: Arc<dyn PlayerBackend>,
+
+    /// Wait between polls while something is loaded.
+    interval: StdDuration,
+
+    /// Wait before the next poll while nothing is loaded.
+    backoff: StdDuration,
+
+    /// Whether to fetch the liked status of tracks.
+    with_liked: bool,
+}
+
+impl Poller {
+    /// Poll `backend` every `interval`.
+    pub fn new(backend: Arc<dyn PlayerBackend>, interval: StdDuration, with_liked: bool) -> Self {
+        Self {
+            backend,
+            interval,
+            backoff: interval,
+            with_liked,
+        }
+    }
+
+    /// Take a snapshot, which is empty when no device is active, and return
+    /// it with how long to wait before the next poll.
+    ///
+    /// The wait doubles up to a minute for every poll without a device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the playback can't be fetched.
+    pub async fn poll(&mut self) -> Result<(Snapshot, StdDuration)> {
+        let snapshot = match CurrentlyPlaying::from_backend(Arc::clone(&self.backend)).await {
+            Ok(curr) => Snapshot::capture(&curr, self.with_liked).await?,
+            Err(err) if matches!(err.downcast_ref(), Some(LibError::NoActiveDevice)) => {
+                Snapshot::default()
+            },
+            Err(err) => return Err(err),
+        };
+
+        let wait = if snapshot.title.is_none() {
+            let wait = self.backoff;
+            self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+            wait
+        } else {
+            self.backoff = self.interval;
+            self.interval
+        };
+
+        Ok((snapshot, wait))
+    }
+
+    /// Take a snapshot like [`Poller::poll`], reporting errors and retrying
+    /// every interval until one succeeds.
+    pub async fn poll_retrying(&mut self) -> (Snapshot, StdDuration) {
+        loop {
+            match self.poll().await {
+                Ok(polled) => return polled,
+                Err(err) => {
+                    eprintln!("Unable to fetch the playback: {err:#}");
+                    tokio::time::sleep(self.interval).await;
+                },
+            }
+        }
+    }
+
+    /// Keep polling and publish every snapshot on `sender`.
+    ///
+    /// `refresh` asks for a poll right away, and wakes the poller up while
+    /// nobody is subscribed to `sender`. Errors are reported and retried.
+    pub async fn publish(mut self, sender: watch::Sender<Polled>, refresh: Arc<Notify>) {
+        loop {
+            if sender.receiver_count() == 0 {
+                refresh.notified().await;
+            }
+
+            let wait = match self.poll().await {
+                Ok((snapshot, wait)) => {
+                    sender.send_replace(Polled {
+                        snapshot,
+                        at: Instant::now(),
+                    });
+                    wait
+                },
+                Err(err) => {
+                    eprintln!("Unable to fetch the playback: {err:#}");
+                    self.interval
+                },
+            };
+            tokio::select! {
+                () = tokio::time::sleep(wait) => {},
+                () = refresh.notified() => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_progress() {
+        let snapshot = Snapshot {
+            progress: Some(Duration::seconds(10)),
+            duration: Some(Duration::seconds(12)),
+            is_playing: Some(true),
+            ..Default::default()
+        };
+        let later = snapshot.interpolate(StdDuration::from_secs(1));
+        assert_eq!(later.progress, Some(Duration::seconds(11)));
+        let later = snapshot.interpolate(StdDuration::from_secs(5));
+        assert_eq!(later.progress, Some(Duration::seconds(12)));
+
+        let paused = Snapshot {
+            is_playing: Some(false),
+            ..snapshot
+        };
+        let later = paused.interpolate(StdDuration::from_secs(5));
+        assert_eq!(later.progress, Some(Duration::seconds(10)));
+    }
+}
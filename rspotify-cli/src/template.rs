@@ -0,0 +1,410 @@
+//! Templates for formatting the status.
+//!
+//! A template is text with fields in braces:
+//!
+//! - `{title}` is replaced with the value of the field, or nothing if the field
+//!   is unknown.
+//! - `{title:<20.15}` pads to 20 characters and truncates to 15 characters. The
+//!   alignment is `<` (default), `>` or `^`, and both the width and the `.`
+//!   precision are optional.
+//! - `{liked?♥:♡}` renders the text before the `:` if the field is set,
+//!   otherwise the text after it. Both branches are templates themselves, and
+//!   the `:` branch is optional.
+//! - `{{` and `}}` are literal braces.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use rspotify_cli_lib::{currently_playing::CurrentlyPlaying, repeat_state::RepeatState};
+
+use crate::{error::Error, pretty_duration::PrettyDuration, snapshot::Snapshot};
+
+/// A field that can be used in a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Id of the item.
+    Id,
+    /// URL of the item.
+    Url,
+    /// URI of the item.
+    Uri,
+    /// Title of the item.
+    Title,
+    /// Artist of the track, or show of the episode.
+    Artist,
+    /// How much of the item has been played.
+    Progress,
+    /// Total length of the item.
+    Duration,
+    /// Volume of the active device.
+    Volume,
+    /// Whether the item is playing.
+    Playing,
+    /// Repeat state, set unless it is off.
+    Repeat,
+    /// Whether shuffle is on.
+    Shuffle,
+    /// Name of the active device.
+    Device,
+    /// Type of the item.
+    Type,
+    /// Whether the track is liked.
+    Liked,
+}
+
+impl Field {
+    /// Look up a field by name, accepting the names of the `status` flags too.
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "id" => Self::Id,
+            "url" => Self::Url,
+            "uri" => Self::Uri,
+            "title" => Self::Title,
+            "artist" => Self::Artist,
+            "progress" => Self::Progress,
+            "duration" => Self::Duration,
+            "volume" => Self::Volume,
+            "playing" | "is_playing" => Self::Playing,
+            "repeat" | "repeat_state" => Self::Repeat,
+            "shuffle" | "is_shuffled" => Self::Shuffle,
+            "device" => Self::Device,
+            "type" | "playing_type" => Self::Type,
+            "liked" | "is_liked" => Self::Liked,
+            _ => return None,
+        })
+    }
+
+    /// Returns the value of the field as text, or `None` if it is unknown.
+    fn value(self, snapshot: &Snapshot) -> Option<String> {
+        match self {
+            Self::Id => snapshot.id.clone(),
+            Self::Url => snapshot.url.clone(),
+            Self::Uri => snapshot.uri.clone(),
+            Self::Title => snapshot.title.clone(),
+            Self::Artist => snapshot.artist.clone(),
+            Self::Progress => snapshot.progress.map(PrettyDuration::pretty),
+            Self::Duration => snapshot.duration.map(PrettyDuration::pretty),
+            Self::Volume => snapshot.volume.map(|v| v.to_string()),
+            Self::Playing => snapshot.is_playing.map(|v| v.to_string()),
+            Self::Repeat => snapshot.repeat_state.map(|v| format!("{v:?}")),
+            Self::Shuffle => snapshot.is_shuffled.map(|v| v.to_string()),
+            Self::Device => snapshot.device.clone(),
+            Self::Type => snapshot.playing_type.map(|v| format!("{v:?}")),
+            Self::Liked => snapshot.is_liked.map(|v| v.to_string()),
+        }
+    }
+
+    /// Whether the field counts as set in a conditional.
+    fn is_set(self, snapshot: &Snapshot) -> bool {
+        match self {
+            Self::Playing => snapshot.is_playing == Some(true),
+            Self::Repeat => snapshot.repeat_state.is_some_and(|r| r != RepeatState::Off),
+            Self::Shuffle => snapshot.is_shuffled == Some(true),
+            Self::Liked => snapshot.is_liked == Some(true),
+            _ => self.value(snapshot).is_some_and(|v| !v.is_empty()),
+        }
+    }
+}
+
+/// How a field is aligned within its width.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Align {
+    /// Pad on the right.
+    #[default]
+    Left,
+    /// Pad on the left.
+    Right,
+    /// Pad on both sides.
+    Center,
+}
+
+/// Padding and truncation of a field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Spec {
+    /// Alignment within the width.
+    align: Align,
+
+    /// Minimum width in characters.
+    width: usize,
+
+    /// Maximum length in characters, longer values end with `…`.
+    max: Option<usize>,
+}
+
+impl Spec {
+    /// Parse a spec like `>20.15`.
+    fn parse(spec: &str) -> Result<Self> {
+        let invalid = || Error::Template(format!("invalid format spec `{spec}`"));
+
+        let (align, rest) = match spec.chars().next() {
+            Some('<') => (Align::Left, &spec[1..]),
+            Some('>') => (Align::Right, &spec[1..]),
+            Some('^') => (Align::Center, &spec[1..]),
+            _ => (Align::Left, spec),
+        };
+        let (width, max) = match rest.split_once('.') {
+            Some((width, max)) => (width, Some(max.parse().map_err(|_| invalid())?)),
+            None => (rest, None),
+        };
+        let width = if width.is_empty() {
+            0
+        } else {
+            width.parse().map_err(|_| invalid())?
+        };
+
+        Ok(Self { align, width, max })
+    }
+
+    /// Truncate and pad a value.
+    fn apply(self, value: &str) -> String {
+        let mut value: String = match self.max {
+            Some(max) if value.chars().count() > max => {
+                let mut truncated: String = value.chars().take(max.saturating_sub(1)).collect();
+                if max > 0 {
+                    truncated.push('\u{2026}');
+                }
+                truncated
+            },
+            _ => value.to_owned(),
+        };
+
+        let padding = self.width.saturating_sub(value.chars().count());
+        let (left, right) = match self.align {
+            Align::Left => (0, padding),
+            Align::Right => (padding, 0),
+            Align::Center => (padding / 2, padding - padding / 2),
+        };
+        value.insert_str(0, &" ".repeat(left));
+        value.push_str(&" ".repeat(right));
+        value
+    }
+}
+
+/// A piece of a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    /// Text copied as is.
+    Literal(String),
+
+    /// Value of a field.
+    Field(Field, Spec),
+
+    /// One of two templates, depending on whether a field is set.
+    Conditional(Field, Template, Template),
+}
+
+/// A parsed status template.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Template {
+    /// Pieces of the template, in order.
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parse a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a brace is not closed or a field is unknown.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.char_indices().peekable();
+
+        while let Some((start, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => literal.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut depth = 1;
+                    let end = chars
+                        .by_ref()
+                        .find(|&(_, c)| {
+                            match c {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {},
+                            }
+                            depth == 0
+                        })
+                        .map(|(end, _)| end)
+                        .ok_or_else(|| Error::Template("unclosed `{`".to_owned()))?;
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Self::parse_tag(&template[start + 1..end])?);
+                },
+                '}' => anyhow::bail!(Error::Template("unmatched `}`".to_owned())),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Parse the named format from the config, or `format` itself if there is
+    /// no format with that name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid.
+    pub fn named(format: &str, formats: &HashMap<String, String>) -> Result<Self> {
+        Self::parse(formats.get(format).map_or(format, String::as_str))
+    }
+
+    /// Parse the inside of a `{...}` tag.
+    fn parse_tag(tag: &str) -> Result<Part> {
+        let field = |name: &str| {
+            Field::from_name(name.trim())
+                .ok_or_else(|| Error::Template(format!("unknown field `{}`", name.trim())))
+        };
+
+        if let Some((name, branches)) = tag.split_once('?') {
+            let (then, otherwise) = split_top_level(branches, ':').unwrap_or((branches, ""));
+            return Ok(Part::Conditional(
+                field(name)?,
+                Self::parse(then)?,
+                Self::parse(otherwise)?,
+            ));
+        }
+
+        Ok(match tag.split_once(':') {
+            Some((name, spec)) => Part::Field(field(name)?, Spec::parse(spec)?),
+            None => Part::Field(field(tag)?, Spec::default()),
+        })
+    }
+
+    /// Whether the template refers to a field.
+    pub fn uses(&self, field: Field) -> bool {
+        self.parts.iter().any(|part| match part {
+            Part::Literal(_) => false,
+            Part::Field(f, _) => *f == field,
+            Part::Conditional(f, then, otherwise) => {
+                *f == field || then.uses(field) || otherwise.uses(field)
+            },
+        })
+    }
+
+    /// Render the template with the values of a snapshot.
+    pub fn render(&self, snapshot: &Snapshot) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.clone(),
+                Part::Field(field, spec) => spec.apply(&field.value(snapshot).unwrap_or_default()),
+                Part::Conditional(field, then, otherwise) => {
+                    if field.is_set(snapshot) {
+                        then.render(snapshot)
+                    } else {
+                        otherwise.render(snapshot)
+                    }
+                },
+            })
+            .collect()
+    }
+}
+
+/// Split at the first `separator` that is not inside braces.
+fn split_top_level(text: &str, separator: char) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            c if c == separator && depth == 0 => return Some((&text[..i], &text[i + 1..])),
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Render the current playback with a template, or with the named format
+/// from the config.
+pub async fn render_status(
+    curr: &CurrentlyPlaying,
+    format: &str,
+    formats: &HashMap<String, String>,
+) -> Result<String> {
+    let template = Template::named(format, formats)?;
+    let snapshot = Snapshot::capture(curr, template.uses(Field::Liked)).await?;
+    Ok(template.render(&snapshot))
+}
+
+#[cfg(test)]
+// templates look like format strings
+#[allow(clippy::literal_string_with_formatting_args)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            title: Some("Never Gonna Give You Up".to_owned()),
+            artist: Some("Rick Astley".to_owned()),
+            progress: Some(Duration::seconds(61)),
+            duration: Some(Duration::seconds(213)),
+            is_playing: Some(true),
+            repeat_state: Some(RepeatState::Off),
+            is_liked: Some(false),
+            ..Default::default()
+        }
+    }
+
+    fn render(template: &str) -> String {
+        Template::parse(template).unwrap().render(&snapshot())
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            render("{artist} \u{2014} {title} [{progress}/{duration}]"),
+            "Rick Astley \u{2014} Never Gonna Give You Up [1:01/3:33]"
+        );
+        assert_eq!(render("{is_playing} {repeat_state}"), "true Off");
+        assert_eq!(render("{device}|{volume}"), "|");
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(render("{liked?\u{2665}:\u{2661}}"), "\u{2661}");
+        assert_eq!(
+            render("{playing?\u{25b6}:\u{23f8}} {title:.5}"),
+            "\u{25b6} Neve\u{2026}"
+        );
+        assert_eq!(
+            render("{repeat?R}{device?on {device}:no device}"),
+            "no device"
+        );
+        assert_eq!(render("{playing?{artist}: x}"), "Rick Astley");
+    }
+
+    #[test]
+    fn padding_and_truncation() {
+        assert_eq!(render("[{artist:15}]"), "[Rick Astley    ]");
+        assert_eq!(render("[{artist:>15}]"), "[    Rick Astley]");
+        assert_eq!(render("[{artist:^15}]"), "[  Rick Astley  ]");
+        assert_eq!(render("[{title:<8.8}]"), "[Never G\u{2026}]");
+        assert_eq!(render("[{artist:.20}]"), "[Rick Astley]");
+    }
+
+    #[test]
+    fn escapes_and_errors() {
+        assert_eq!(render("{{title}}"), "{title}");
+        assert!(Template::parse("{title").is_err());
+        assert!(Template::parse("title}").is_err());
+        assert!(Template::parse("{album}").is_err());
+        assert!(Template::parse("{title:x}").is_err());
+    }
+
+    #[test]
+    fn uses() {
+        let template = Template::parse("{title}{playing?{liked?\u{2665}}}").unwrap();
+        assert!(template.uses(Field::Liked));
+        assert!(!template.uses(Field::Artist));
+    }
+}
@@ -0,0 +1,81 @@
+//! Choosing where the token of a profile is kept.
+
+use std::{env, io::IsTerminal};
+
+use anyhow::{Context, Result};
+use rspotify_cli_lib::token_store::{Passphrase, TokenStore, TokenStoreKind};
+
+use crate::{config::get_profile_dir, error::Error};
+
+/// Service the keyring entries are stored under.
+const KEYRING_SERVICE: &str = "rspotify-cli";
+
+/// Name of the plaintext token file in the profile directory.
+const TOKEN_FILE: &str = "token.json";
+
+/// Name of the encrypted token file in the profile directory.
+const ENCRYPTED_TOKEN_FILE: &str = "token.enc";
+
+/// Environment variable holding the passphrase of the encrypted token file.
+const PASSPHRASE_VAR: &str = "RSPOTIFY_TOKEN_PASSPHRASE";
+
+/// Returns the keyring entry of a profile.
+pub fn keyring_store(profile: &str) -> TokenStore {
+    TokenStore::Keyring {
+        service: KEYRING_SERVICE.to_owned(),
+        account: profile.to_owned(),
+    }
+}
+
+/// Open the token store of a profile, and move the token of the plaintext
+/// cache into it.
+///
+/// Falls back to the encrypted file when the keyring can't be reached.
+pub fn open_token_store(kind: TokenStoreKind, profile: &str) -> Result<TokenStore> {
+    let json_path = get_profile_dir(profile)
+        .context(Error::Config)?
+        .join(TOKEN_FILE);
+
+    let store = match kind {
+        TokenStoreKind::File => return Ok(TokenStore::File(json_path)),
+        TokenStoreKind::Keyring => {
+            let keyring = keyring_store(profile);
+            if keyring.is_available() {
+                keyring
+            } else {
+                eprintln!("The keyring is not available, using an encrypted file instead");
+                encrypted_file_store(profile)?
+            }
+        },
+        TokenStoreKind::EncryptedFile => encrypted_file_store(profile)?,
+    };
+
+    if store.migrate_from(&json_path)? {
+        eprintln!(
+            "Moved the token from {} to {}",
+            json_path.display(),
+            store.describe()
+        );
+    }
+
+    Ok(store)
+}
+
+/// Returns the encrypted token file of a profile, asking for the passphrase
+/// unless it is set in the environment.
+fn encrypted_file_store(profile: &str) -> Result<TokenStore> {
+    let passphrase = match env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) if std::io::stdin().is_terminal() => {
+            rpassword::prompt_password("Token passphrase: ").context(Error::MissingPassphrase)?
+        },
+        Err(_) => anyhow::bail!(Error::MissingPassphrase),
+    };
+
+    Ok(TokenStore::EncryptedFile {
+        path: get_profile_dir(profile)
+            .context(Error::Config)?
+            .join(ENCRYPTED_TOKEN_FILE),
+        passphrase: Passphrase(passphrase),
+    })
+}
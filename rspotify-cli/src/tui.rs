@@ -0,0 +1,683 @@
+//! Full-screen terminal interface with the playback, search, queue and
+//! devices on one screen.
+
+use std::{
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Gauge, List, ListState, Paragraph, Tabs},
+    Frame,
+};
+use rspotify::{
+    model::{Device, FullTrack, PlayableItem},
+    prelude::Id,
+};
+use rspotify_cli_lib::{backend::PlayerBackend, currently_playing::CurrentlyPlaying};
+use tokio::sync::{mpsc, Notify};
+
+use crate::{
+    error::Error,
+    pretty_duration::PrettyDuration,
+    queue::describe,
+    snapshot::{Polled, Poller, Snapshot},
+};
+
+/// Keys shown at the bottom of the screen and in the help of `tui`.
+pub const KEYS: &str = "space play/pause  n/p next/previous  l like  r repeat  s shuffle  \
+                        +/- volume  0-9 set volume  \u{2190}/\u{2192} seek  R replay  \
+                        tab pane  / search  enter select  q quit";
+
+/// How often the progress bar is redrawn.
+const REDRAW: StdDuration = StdDuration::from_millis(250);
+
+/// How far the arrow keys seek.
+const SEEK_STEP: Duration = Duration::seconds(5);
+
+/// Number of search results to show.
+const SEARCH_LIMIT: u32 = 20;
+
+/// Pane shown below the playback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    /// Search for tracks and play one.
+    Search,
+
+    /// Items queued after the current one.
+    Queue,
+
+    /// Devices to move the playback to.
+    Devices,
+}
+
+impl Pane {
+    /// Every pane, in tab order.
+    const ALL: [Self; 3] = [Self::Search, Self::Queue, Self::Devices];
+
+    /// Returns the title of the tab.
+    const fn title(self) -> &'static str {
+        match self {
+            Self::Search => "Search",
+            Self::Queue => "Queue",
+            Self::Devices => "Devices",
+        }
+    }
+
+    /// Returns the position of the tab.
+    const fn index(self) -> usize {
+        match self {
+            Self::Search => 0,
+            Self::Queue => 1,
+            Self::Devices => 2,
+        }
+    }
+
+    /// Returns the pane `offset` tabs away, wrapping around.
+    const fn cycle(self, offset: usize) -> Self {
+        Self::ALL[(self.index() + offset) % Self::ALL.len()]
+    }
+}
+
+/// Request made by a key, run against a freshly fetched playback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    PlayPause,
+    Next,
+    Previous,
+    ToggleLike,
+    CycleRepeat,
+    ToggleShuffle,
+    VolumeUp,
+    VolumeDown,
+    Volume(u8),
+    Seek(Duration),
+    Replay,
+    PlayUri(String),
+    Transfer(String),
+    Search(String),
+    LoadQueue,
+    LoadDevices,
+}
+
+/// Result of polling or of an action.
+#[derive(Debug)]
+enum Outcome {
+    /// The playback was polled.
+    Polled(Snapshot),
+
+    /// The playback was changed.
+    Done,
+
+    /// Tracks found by a search.
+    Results(Vec<FullTrack>),
+
+    /// Items queued after the current one.
+    Queue(Vec<PlayableItem>),
+
+    /// Devices that can be played on.
+    Devices(Vec<Device>),
+
+    /// Polling or an action failed.
+    Failed(anyhow::Error),
+}
+
+/// Everything shown on the screen.
+#[derive(Debug)]
+struct App {
+    /// Last polled playback.
+    polled: Polled,
+
+    /// Pane below the playback.
+    pane: Pane,
+
+    /// Whether keys are typed into the search query.
+    editing: bool,
+
+    /// Search query.
+    query: String,
+
+    /// Tracks found by the last search.
+    results: Vec<FullTrack>,
+
+    /// Items queued after the current one.
+    queue: Vec<PlayableItem>,
+
+    /// Devices that can be played on.
+    devices: Vec<Device>,
+
+    /// Selected row of the pane.
+    selected: usize,
+
+    /// Error of the last action, shown instead of the keys.
+    message: Option<String>,
+
+    /// Whether to leave.
+    quit: bool,
+}
+
+impl App {
+    /// Create an app with nothing loaded.
+    fn new() -> Self {
+        Self {
+            polled: Polled {
+                snapshot: Snapshot::default(),
+                at: Instant::now(),
+            },
+            pane: Pane::Search,
+            editing: false,
+            query: String::new(),
+            results: Vec::new(),
+            queue: Vec::new(),
+            devices: Vec::new(),
+            selected: 0,
+            message: None,
+            quit: false,
+        }
+    }
+
+    /// Returns the playback with the progress advanced since it was polled.
+    fn snapshot(&self) -> Snapshot {
+        self.polled.snapshot.interpolate(self.polled.at.elapsed())
+    }
+
+    /// Returns the number of rows in the pane.
+    fn rows(&self) -> usize {
+        match self.pane {
+            Pane::Search => self.results.len(),
+            Pane::Queue => self.queue.len(),
+            Pane::Devices => self.devices.len(),
+        }
+    }
+
+    /// Show another pane, returning the action that loads it.
+    fn show(&mut self, pane: Pane) -> Option<Action> {
+        self.pane = pane;
+        self.selected = 0;
+        self.reload()
+    }
+
+    /// Returns the action that loads the pane again.
+    const fn reload(&self) -> Option<Action> {
+        match self.pane {
+            Pane::Search => None,
+            Pane::Queue => Some(Action::LoadQueue),
+            Pane::Devices => Some(Action::LoadDevices),
+        }
+    }
+
+    /// Returns the action for the selected row.
+    fn activate(&self) -> Option<Action> {
+        match self.pane {
+            Pane::Search => self
+                .results
+                .get(self.selected)
+                .and_then(|track| track.id.as_ref())
+                .map(|id| Action::PlayUri(id.uri())),
+            Pane::Queue => None,
+            Pane::Devices => self
+                .devices
+                .get(self.selected)
+                .and_then(|device| device.id.clone())
+                .map(Action::Transfer),
+        }
+    }
+
+    /// Handle a key typed into the search query.
+    fn edit(&mut self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char(c) => self.query.push(c),
+            KeyCode::Backspace => {
+                self.query.pop();
+            },
+            KeyCode::Enter => {
+                self.editing = false;
+                self.selected = 0;
+                let query = self.query.trim();
+                return (!query.is_empty()).then(|| Action::Search(query.to_owned()));
+            },
+            KeyCode::Esc => self.editing = false,
+            _ => {},
+        }
+        None
+    }
+
+    /// Handle a key, returning the action it asks for.
+    fn on_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return None;
+        }
+        if self.editing {
+            return self.edit(key);
+        }
+
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char(' ') => return Some(Action::PlayPause),
+            KeyCode::Char('n') => return Some(Action::Next),
+            KeyCode::Char('p') => return Some(Action::Previous),
+            KeyCode::Char('l') => return Some(Action::ToggleLike),
+            KeyCode::Char('r') => return Some(Action::CycleRepeat),
+            KeyCode::Char('s') => return Some(Action::ToggleShuffle),
+            KeyCode::Char('+' | '=') => return Some(Action::VolumeUp),
+            KeyCode::Char('-') => return Some(Action::VolumeDown),
+            KeyCode::Char('0') => return Some(Action::Volume(100)),
+            KeyCode::Char(digit @ '1'..='9') => {
+                let tens = digit.to_digit(10).and_then(|tens| u8::try_from(tens).ok());
+                return tens.map(|tens| Action::Volume(tens * 10));
+            },
+            KeyCode::Left => return Some(Action::Seek(-SEEK_STEP)),
+            KeyCode::Right => return Some(Action::Seek(SEEK_STEP)),
+            KeyCode::Char('R') => return Some(Action::Replay),
+            KeyCode::Tab => return self.show(self.pane.cycle(1)),
+            KeyCode::BackTab => return self.show(self.pane.cycle(Pane::ALL.len() - 1)),
+            KeyCode::Char('/') => {
+                self.show(Pane::Search);
+                self.editing = true;
+            },
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.rows().saturating_sub(1));
+            },
+            KeyCode::Enter => return self.activate(),
+            _ => {},
+        }
+        None
+    }
+
+    /// Handle the result of polling or of an action, returning the action
+    /// that reloads the pane if the playback changed.
+    fn on_outcome(&mut self, outcome: Outcome) -> Option<Action> {
+        match outcome {
+            Outcome::Polled(snapshot) => {
+                self.polled = Polled {
+                    snapshot,
+                    at: Instant::now(),
+                };
+            },
+            Outcome::Done => return self.reload(),
+            Outcome::Results(results) => self.results = results,
+            Outcome::Queue(queue) => self.queue = queue,
+            Outcome::Devices(devices) => self.devices = devices,
+            Outcome::Failed(err) => self.message = Some(format!("{err:#}")),
+        }
+        self.selected = self.selected.min(self.rows().saturating_sub(1));
+        None
+    }
+}
+
+/// Draw the playback with its progress bar.
+fn draw_playback(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let block = Block::bordered().title(" Now playing ");
+    let [title, details, progress] =
+        Layout::vertical([Constraint::Length(1); 3]).areas(block.inner(area));
+    frame.render_widget(block, area);
+
+    let (Some(name), Some(duration)) = (&snapshot.title, snapshot.duration) else {
+        frame.render_widget(Paragraph::new("Nothing is playing"), title);
+        return;
+    };
+    let liked = match snapshot.is_liked {
+        Some(true) => " \u{2665}",
+        Some(false) => " \u{2661}",
+        None => "",
+    };
+    let artist = snapshot.artist.as_deref().unwrap_or_default();
+    frame.render_widget(
+        Paragraph::new(format!("{name} - {artist}{liked}"))
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        title,
+    );
+
+    let state = if snapshot.is_playing == Some(true) {
+        "Playing"
+    } else {
+        "Paused"
+    };
+    let on_off = |state: Option<bool>| if state == Some(true) { "on" } else { "off" };
+    let repeat = snapshot
+        .repeat_state
+        .map_or_else(String::new, |repeat| format!("{repeat:?}").to_lowercase());
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{state} on {}  volume {}%  shuffle {}  repeat {repeat}",
+            snapshot.device.as_deref().unwrap_or_default(),
+            snapshot.volume.unwrap_or_default(),
+            on_off(snapshot.is_shuffled),
+        )),
+        details,
+    );
+
+    let played = snapshot.progress.unwrap_or_else(Duration::zero);
+    #[allow(clippy::cast_precision_loss)]
+    let ratio = if duration > Duration::zero() {
+        played.num_milliseconds() as f64 / duration.num_milliseconds() as f64
+    } else {
+        0.0
+    };
+    frame.render_widget(
+        Gauge::default().ratio(ratio.clamp(0.0, 1.0)).label(format!(
+            "{} / {}",
+            played.pretty(),
+            duration.pretty()
+        )),
+        progress,
+    );
+}
+
+/// Draw the selected pane.
+fn draw_pane(frame: &mut Frame, area: Rect, app: &App) {
+    let block = Block::bordered();
+    let mut inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let rows: Vec<String> = match app.pane {
+        Pane::Search => {
+            let [input, results] =
+                Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+            let cursor = if app.editing { "\u{2581}" } else { "" };
+            frame.render_widget(Paragraph::new(format!("/ {}{cursor}", app.query)), input);
+            inner = results;
+            app.results
+                .iter()
+                .map(|track| {
+                    format!(
+                        "{} ({})",
+                        describe(&PlayableItem::Track(track.clone())),
+                        track.duration.pretty()
+                    )
+                })
+                .collect()
+        },
+        Pane::Queue => app.queue.iter().map(describe).collect(),
+        Pane::Devices => app
+            .devices
+            .iter()
+            .map(|device| {
+                let active = if device.is_active { " (active)" } else { "" };
+                format!("{} - {:?}{active}", device.name, device._type)
+            })
+            .collect(),
+    };
+
+    let mut state = ListState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(
+        List::new(rows).highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        inner,
+        &mut state,
+    );
+}
+
+/// Draw the whole screen.
+fn draw(frame: &mut Frame, app: &App) {
+    let [playback, tabs, pane, footer] = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Length(1),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_playback(frame, playback, &app.snapshot());
+    frame.render_widget(
+        Tabs::new(Pane::ALL.map(Pane::title))
+            .select(app.pane.index())
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        tabs,
+    );
+    draw_pane(frame, pane, app);
+    let footer_text = app.message.as_deref().unwrap_or(KEYS);
+    frame.render_widget(Paragraph::new(Line::from(footer_text)), footer);
+}
+
+/// Fetch the playback and run an action against it.
+async fn perform(
+    backend: Arc<dyn PlayerBackend>,
+    action: Action,
+    volume_increment: u8,
+) -> Result<Outcome> {
+    let curr = CurrentlyPlaying::from_backend(backend)
+        .await
+        .context(Error::Connect)?;
+
+    match action {
+        Action::PlayPause => curr.toggle_play_pause().await?,
+        Action::Next => curr.next().await?,
+        Action::Previous => curr.previous().await?,
+        Action::ToggleLike => curr.toggle_like_unlike().await?,
+        Action::CycleRepeat => curr.cycle_repeat().await?,
+        Action::ToggleShuffle => curr.toggle_shuffle().await?,
+        Action::VolumeUp => curr.volume_up(volume_increment).await?,
+        Action::VolumeDown => curr.volume_down(volume_increment).await?,
+        Action::Volume(volume) => curr.set_volume(volume).await?,
+        Action::Seek(offset) => curr.seek_relative(offset).await?,
+        Action::Replay => curr.replay().await?,
+        Action::PlayUri(uri) => curr.play_from_uri(uri).await?,
+        Action::Transfer(id) => curr.transfer(&id, curr.is_playing == Some(true)).await?,
+        Action::Search(query) => {
+            return Ok(Outcome::Results(
+                curr.search_tracks(&query, SEARCH_LIMIT).await?,
+            ));
+        },
+        Action::LoadQueue => return Ok(Outcome::Queue(curr.queue().await?.queue)),
+        Action::LoadDevices => return Ok(Outcome::Devices(curr.devices().await?)),
+    }
+    Ok(Outcome::Done)
+}
+
+/// Keep polling the playback, right away when `refresh` is notified.
+async fn poll(
+    mut poller: Poller,
+    interval: StdDuration,
+    outcomes: mpsc::UnboundedSender<Outcome>,
+    refresh: Arc<Notify>,
+) {
+    loop {
+        let (outcome, wait) = match poller.poll().await {
+            Ok((snapshot, wait)) => (Outcome::Polled(snapshot), wait),
+            Err(err) => (Outcome::Failed(err), interval),
+        };
+        if outcomes.send(outcome).is_err() {
+            return;
+        }
+        tokio::select! {
+            () = tokio::time::sleep(wait) => {},
+            () = refresh.notified() => {},
+        }
+    }
+}
+
+/// Run the interface until it is quit, polling the playback every `interval`.
+///
+/// # Errors
+///
+/// Returns an error if the terminal can't be drawn on.
+pub async fn run(
+    backend: Arc<dyn PlayerBackend>,
+    volume_increment: u8,
+    interval: StdDuration,
+) -> Result<()> {
+    let (outcomes_tx, mut outcomes) = mpsc::unbounded_channel();
+    let refresh = Arc::new(Notify::new());
+    let poller = Poller::new(Arc::clone(&backend), interval, true);
+    let poller = tokio::spawn(poll(
+        poller,
+        interval,
+        outcomes_tx.clone(),
+        Arc::clone(&refresh),
+    ));
+
+    // reading a terminal event blocks, so it has its own thread
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if events_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let mut app = App::new();
+    let mut redraw = tokio::time::interval(REDRAW);
+    let result = async {
+        while !app.quit {
+            terminal.draw(|frame| draw(frame, &app))?;
+            let action = tokio::select! {
+                event = events.recv() => match event {
+                    Some(Event::Key(key)) => app.on_key(key),
+                    Some(_) => None,
+                    None => break,
+                },
+                Some(outcome) = outcomes.recv() => {
+                    if matches!(outcome, Outcome::Done) {
+                        refresh.notify_one();
+                    }
+                    app.on_outcome(outcome)
+                },
+                _ = redraw.tick() => None,
+            };
+
+            if let Some(action) = action {
+                let backend = Arc::clone(&backend);
+                let outcomes = outcomes_tx.clone();
+                tokio::spawn(async move {
+                    let outcome = perform(backend, action, volume_increment)
+                        .await
+                        .unwrap_or_else(Outcome::Failed);
+                    let _ = outcomes.send(outcome);
+                });
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    ratatui::restore();
+    poller.abort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+    use rspotify::model::TrackId;
+    use rspotify_cli_lib::fake_backend::{device, track};
+
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.on_key(KeyEvent::from(code))
+    }
+
+    fn result() -> FullTrack {
+        let id = TrackId::from_id("4cOdK2wGLETKBW3PvgPWqT").unwrap();
+        track(id, "Title", "Artist", Duration::seconds(200))
+    }
+
+    #[test]
+    fn keys() {
+        let mut app = App::new();
+        assert_eq!(press(&mut app, KeyCode::Char(' ')), Some(Action::PlayPause));
+        assert_eq!(
+            press(&mut app, KeyCode::Char('3')),
+            Some(Action::Volume(30))
+        );
+        assert_eq!(
+            press(&mut app, KeyCode::Char('0')),
+            Some(Action::Volume(100))
+        );
+        assert_eq!(
+            press(&mut app, KeyCode::Left),
+            Some(Action::Seek(Duration::seconds(-5)))
+        );
+
+        // typing a query doesn't control the playback
+        assert_eq!(press(&mut app, KeyCode::Char('/')), None);
+        for c in "so n".chars() {
+            assert_eq!(press(&mut app, KeyCode::Char(c)), None);
+        }
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Some(Action::Search("so n".to_owned()))
+        );
+        app.on_outcome(Outcome::Results(vec![result()]));
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Some(Action::PlayUri(
+                "spotify:track:4cOdK2wGLETKBW3PvgPWqT".to_owned()
+            ))
+        );
+
+        assert_eq!(press(&mut app, KeyCode::Tab), Some(Action::LoadQueue));
+        assert_eq!(press(&mut app, KeyCode::Tab), Some(Action::LoadDevices));
+        app.on_outcome(Outcome::Devices(vec![
+            device("computer", "Computer"),
+            device("phone", "Phone"),
+        ]));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(
+            press(&mut app, KeyCode::Enter),
+            Some(Action::Transfer("phone".to_owned()))
+        );
+        assert_eq!(app.on_outcome(Outcome::Done), Some(Action::LoadDevices));
+        assert_eq!(press(&mut app, KeyCode::BackTab), Some(Action::LoadQueue));
+
+        assert!(!app.quit);
+        press(&mut app, KeyCode::Char('q'));
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn draws() {
+        let mut app = App::new();
+        app.on_outcome(Outcome::Polled(Snapshot {
+            title: Some("Title".to_owned()),
+            artist: Some("Artist".to_owned()),
+            progress: Some(Duration::seconds(61)),
+            duration: Some(Duration::seconds(200)),
+            device: Some("Computer".to_owned()),
+            is_playing: Some(false),
+            is_liked: Some(true),
+            ..Default::default()
+        }));
+        app.on_outcome(Outcome::Results(vec![result()]));
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 12)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Title - Artist \u{2665}"), "{screen}");
+        assert!(screen.contains("Paused on Computer"), "{screen}");
+        assert!(screen.contains("1:01 / 3:20"), "{screen}");
+        assert!(screen.contains("Title - Artist (3:20)"), "{screen}");
+
+        app.on_outcome(Outcome::Failed(anyhow::anyhow!("No active device found")));
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("No active device found"), "{screen}");
+    }
+}
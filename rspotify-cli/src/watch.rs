@@ -0,0 +1,172 @@
+//! Keep printing the status as it changes.
+
+use std::{collections::HashMap, io::Write, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use rspotify_cli_lib::backend::PlayerBackend;
+use tokio::time::Instant;
+
+use crate::{
+    bar::{render_bar, Bar, DEFAULT_BAR_FORMAT},
+    snapshot::{Poller, Snapshot},
+    template::{Field, Template},
+};
+
+/// Template matching the default output of `status`.
+const DEFAULT_FORMAT: &str = "{title} - {artist} {liked?\u{2665}:\u{2661}}";
+
+/// How often the interpolated progress is re-rendered.
+const TICK: Duration = Duration::from_secs(1);
+
+/// How each status line is rendered.
+#[derive(Debug, Clone)]
+pub enum StatusLine {
+    /// Plain template.
+    Template(Template),
+
+    /// Status bar output, with a template for its text.
+    Bar(Bar, Template),
+}
+
+impl StatusLine {
+    /// Pick the rendering for the `--bar` and `--format` options of `status`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is invalid.
+    pub fn new(
+        bar: Option<Bar>,
+        format: Option<&str>,
+        formats: &HashMap<String, String>,
+    ) -> Result<Self> {
+        Ok(match bar {
+            Some(bar) => Self::Bar(
+                bar,
+                Template::named(format.unwrap_or(DEFAULT_BAR_FORMAT), formats)?,
+            ),
+            None => Self::Template(Template::named(format.unwrap_or(DEFAULT_FORMAT), formats)?),
+        })
+    }
+
+    /// Whether rendering needs the liked status.
+    fn uses_liked(&self) -> bool {
+        match self {
+            Self::Template(template) => template.uses(Field::Liked),
+            Self::Bar(..) => true,
+        }
+    }
+
+    /// Render a snapshot, which is empty while nothing is playing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bar output can't be built.
+    pub fn render(&self, snapshot: &Snapshot) -> Result<String> {
+        match self {
+            Self::Template(_) if snapshot.title.is_none() => Ok(String::new()),
+            Self::Template(template) => Ok(template.render(snapshot)),
+            Self::Bar(bar, text) => render_bar(*bar, snapshot, text),
+        }
+    }
+}
+
+/// Poll the playback every `interval` and print a line whenever the rendered
+/// status changes.
+///
+/// Between polls the progress is advanced locally every second. While no
+/// device is active the interval doubles up to a minute, and failed polls are
+/// reported and retried.
+pub async fn watch(
+    backend: Arc<dyn PlayerBackend>,
+    line: &StatusLine,
+    interval: Duration,
+    out: &mut impl Write,
+) -> Result<()> {
+    let mut poller = Poller::new(backend, interval, line.uses_liked());
+    let mut last = None;
+
+    loop {
+        let (snapshot, wait) = poller.poll_retrying().await;
+        let polled_at = Instant::now();
+
+        loop {
+            let elapsed = polled_at.elapsed();
+            let rendered = line.render(&snapshot.interpolate(elapsed))?;
+            if last.as_ref() != Some(&rendered) {
+                writeln!(out, "{rendered}")?;
+                out.flush()?;
+                last = Some(rendered);
+            }
+
+            if elapsed >= wait {
+                break;
+            }
+            tokio::time::sleep(TICK.min(wait - elapsed)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rspotify::model::{PlayableItem, TrackId};
+    use rspotify_cli_lib::fake_backend::{playback, track, FakeBackend};
+
+    use super::*;
+
+    fn backend(is_playing: bool) -> Arc<FakeBackend> {
+        let id = TrackId::from_id("4cOdK2wGLETKBW3PvgPWqT")
+            .unwrap()
+            .into_static();
+        let track = track(id, "Title", "Artist", chrono::Duration::seconds(200));
+        let backend = FakeBackend::new();
+        backend.state().playback = Some(rspotify::model::CurrentPlaybackContext {
+            is_playing,
+            ..playback(PlayableItem::Track(track))
+        });
+        Arc::new(backend)
+    }
+
+    /// Watch for `run_for`, returning what was printed.
+    async fn watch_for(backend: Arc<FakeBackend>, format: &str, run_for: Duration) -> String {
+        let line = StatusLine::new(None, Some(format), &HashMap::new()).unwrap();
+        let mut out = Vec::new();
+        let result = tokio::time::timeout(
+            run_for,
+            watch(backend, &line, Duration::from_secs(5), &mut out),
+        )
+        .await;
+        assert!(result.is_err());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ticks_while_playing() {
+        let out = watch_for(backend(true), "{progress}", Duration::from_millis(1500)).await;
+        assert_eq!(out, "0:00\n0:01\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn prints_only_changes() {
+        let out = watch_for(backend(false), "{title}", Duration::from_millis(1500)).await;
+        assert_eq!(out, "Title\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_failed_polls() {
+        let backend = backend(false);
+        backend.state().failing_fetches = 2;
+        let out = watch_for(backend, "{title}", Duration::from_secs(11)).await;
+        assert_eq!(out, "Title\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_device() {
+        let out = watch_for(
+            Arc::new(FakeBackend::new()),
+            "{title}",
+            Duration::from_millis(500),
+        )
+        .await;
+        assert_eq!(out, "\n");
+    }
+}
@@ -0,0 +1,180 @@
+//! Just enough of the WebSocket protocol (RFC 6455) for a server that pushes
+//! text messages.
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Appended to the key of the client to compute the accept key.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest payload accepted from clients, who are not expected to send more
+/// than control frames.
+const MAX_PAYLOAD: u64 = 64 * 1024;
+
+/// Kind of frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// Continuation of a fragmented message.
+    Continuation,
+
+    /// Text message.
+    Text,
+
+    /// Binary message.
+    Binary,
+
+    /// The connection is closing.
+    Close,
+
+    /// Asks for a pong.
+    Ping,
+
+    /// Answer to a ping.
+    Pong,
+}
+
+impl Opcode {
+    /// Returns the opcode of a frame header.
+    const fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xA => Some(Self::Pong),
+            _ => None,
+        }
+    }
+
+    /// Returns the bits of the opcode.
+    const fn bits(self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xA,
+        }
+    }
+}
+
+/// A frame received from a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Kind of frame.
+    pub opcode: Opcode,
+
+    /// Unmasked payload.
+    pub payload: Vec<u8>,
+}
+
+/// Returns the `Sec-WebSocket-Accept` header for a `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    STANDARD.encode(sha1.finalize())
+}
+
+/// Write an unfragmented, unmasked frame, as servers send them.
+pub async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    opcode: Opcode,
+    payload: &[u8],
+) -> Result<()> {
+    let mut frame = vec![0x80 | opcode.bits()];
+    match payload.len() {
+        #[allow(clippy::cast_possible_truncation)]
+        len @ 0..=125 => frame.push(len as u8),
+        #[allow(clippy::cast_possible_truncation)]
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend((len as u16).to_be_bytes());
+        },
+        len => {
+            frame.push(127);
+            frame.extend((len as u64).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read a frame, which clients must mask.
+///
+/// # Errors
+///
+/// Returns an error if the connection is closed, or the frame is invalid or
+/// too large.
+pub async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> Result<Frame> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header).await?;
+    let opcode = Opcode::from_bits(header[0] & 0x0F)
+        .ok_or_else(|| anyhow::anyhow!("Unknown opcode {}", header[0] & 0x0F))?;
+    if header[1] & 0x80 == 0 {
+        anyhow::bail!("Unmasked frame from a client");
+    }
+
+    let len = match header[1] & 0x7F {
+        126 => u64::from(reader.read_u16().await?),
+        127 => reader.read_u64().await?,
+        len => u64::from(len),
+    };
+    if len > MAX_PAYLOAD {
+        anyhow::bail!("Frame of {len} bytes is too large");
+    }
+
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask).await?;
+    let mut payload = vec![0; usize::try_from(len)?];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame { opcode, payload })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept() {
+        // example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn frames() {
+        let mut out = Vec::new();
+        write_frame(&mut out, Opcode::Text, b"Hello").await.unwrap();
+        assert_eq!(out, b"\x81\x05Hello");
+
+        let mut out = Vec::new();
+        write_frame(&mut out, Opcode::Text, &[b'a'; 200])
+            .await
+            .unwrap();
+        assert_eq!(&out[..4], [0x81, 126, 0, 200]);
+
+        // masked "Hello" from RFC 6455
+        let mut masked: &[u8] = &[
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        let frame = read_frame(&mut masked).await.unwrap();
+        assert_eq!(frame.opcode, Opcode::Text);
+        assert_eq!(frame.payload, b"Hello");
+
+        let mut unmasked: &[u8] = b"\x81\x05Hello";
+        assert!(read_frame(&mut unmasked).await.is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! End-to-end tests for the HTTP API, run against a local mock server.
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::Stdio,
+};
+
+use common::{Cli, Killed, MockServer};
+
+/// Start the API and return it with its address.
+fn start_api(cli: &Cli) -> (Killed, String) {
+    let mut child = cli
+        .command(&["api", "--listen", "127.0.0.1:0"])
+        .env("SPOTIFY_API_TOKEN", "secret")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let address = line
+        .trim()
+        .strip_prefix("Listening on http://")
+        .unwrap()
+        .to_owned();
+    (Killed(child), address)
+}
+
+/// Send a request and return the status code and body of the response.
+fn request(address: &str, method: &str, target: &str, token: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{method} {target} HTTP/1.1\r\nHost: {address}\r\nAuthorization: Bearer {token}\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_owned())
+        .unwrap_or_default();
+    (status, body)
+}
+
+#[test]
+fn serves_requests() {
+    let cli = Cli::new(MockServer::start());
+    let (_api, address) = start_api(&cli);
+
+    let (status, _) = request(&address, "GET", "/status", "wrong", "");
+    assert_eq!(status, 401);
+
+    let (status, body) = request(&address, "GET", "/status", "secret", "");
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["title"], "Title");
+
+    let (status, _) = request(&address, "POST", "/control/next", "secret", "");
+    assert_eq!(status, 204);
+    assert!(cli.server.find("POST", "/v1/me/player/next").is_some());
+
+    let (status, _) = request(&address, "PUT", "/volume", "secret", r#"{"volume":30}"#);
+    assert_eq!(status, 204);
+    assert!(cli
+        .server
+        .find("PUT", "/v1/me/player/volume?volume_percent=30")
+        .is_some());
+
+    let (status, _) = request(
+        &address,
+        "POST",
+        "/play",
+        "secret",
+        r#"{"uri":"spotify:track:4cOdK2wGLETKBW3PvgPWqT"}"#,
+    );
+    assert_eq!(status, 204);
+    let play = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    assert!(play.body.contains("spotify:track:4cOdK2wGLETKBW3PvgPWqT"));
+
+    let (status, body) = request(&address, "GET", "/search?q=some+song", "secret", "");
+    assert_eq!(status, 200);
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json[0]["name"], "Title");
+
+    let (status, body) = request(&address, "POST", "/play", "secret", "{}");
+    assert_eq!(status, 400);
+    assert!(body.contains("error"));
+}
+
+/// Read a server frame and return its opcode and payload.
+fn read_frame(stream: &mut TcpStream) -> (u8, String) {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).unwrap();
+    let len = match header[1] {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).unwrap();
+            usize::from(u16::from_be_bytes(len))
+        },
+        len => usize::from(len),
+    };
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).unwrap();
+    (header[0] & 0x0F, String::from_utf8(payload).unwrap())
+}
+
+#[test]
+fn pushes_updates() {
+    let cli = Cli::new(MockServer::start());
+    let (_api, address) = start_api(&cli);
+
+    let (status, _) = request(&address, "GET", "/ws?token=wrong", "", "");
+    assert_eq!(status, 401);
+
+    let mut stream = TcpStream::connect(&address).unwrap();
+    write!(
+        stream,
+        "GET /ws?token=secret HTTP/1.1\r\nHost: {address}\r\nUpgrade: websocket\r\n\
+         Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n"
+    )
+    .unwrap();
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        response.push(byte[0]);
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101"), "{response}");
+    assert!(
+        response.contains("sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"),
+        "{response}"
+    );
+
+    let (opcode, state) = read_frame(&mut stream);
+    assert_eq!(opcode, 0x1);
+    let state: serde_json::Value = serde_json::from_str(&state).unwrap();
+    assert_eq!(state["type"], "state");
+    assert_eq!(state["data"]["title"], "Title");
+
+    let (_, tick) = read_frame(&mut stream);
+    let tick: serde_json::Value = serde_json::from_str(&tick).unwrap();
+    assert_eq!(tick["type"], "tick");
+    assert_eq!(tick["duration"], 200);
+
+    // masked close frame with an empty payload
+    stream.write_all(&[0x88, 0x80, 1, 2, 3, 4]).unwrap();
+    loop {
+        let (opcode, _) = read_frame(&mut stream);
+        if opcode == 0x8 {
+            break;
+        }
+    }
+}
+
+#[test]
+fn requires_token() {
+    let cli = Cli::new(MockServer::start());
+    let output = cli.run(&["api"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No API token is set"));
+}
@@ -0,0 +1,159 @@
+//! End-to-end tests for the authorization flows, run against a local mock
+//! server.
+
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    process::Stdio,
+};
+
+use common::{Cli, MockServer};
+
+#[test]
+fn refresh_token() {
+    let cli = Cli::without_token(MockServer::start());
+    assert_eq!(
+        cli.stdout(&["auth", "--refresh-token", "obtained-elsewhere"]),
+        "Successfully authorized\n"
+    );
+
+    let request = cli.server.find("POST", "/api/token").unwrap();
+    assert!(request.body.contains("refresh_token=obtained-elsewhere"));
+    assert!(fs::read_to_string(cli.token_path())
+        .unwrap()
+        .contains("mock-access-token"));
+
+    // the cached token is used from now on
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+}
+
+#[test]
+fn local_listener() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let cli = Cli::without_token(MockServer::start());
+    let mut child = cli
+        .command(&["auth", "--no-browser"])
+        .env(
+            "SPOTIFY_REDIRECT_URI",
+            format!("http://127.0.0.1:{port}/callback"),
+        )
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // the listener is bound before the authorization URL is printed
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let url = stdout
+        .by_ref()
+        .lines()
+        .map(Result::unwrap)
+        .find(|line| line.starts_with("http"))
+        .unwrap();
+    let state = url
+        .split(['?', '&'])
+        .find_map(|param| param.strip_prefix("state="))
+        .unwrap();
+
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "GET /callback?code=mock-code&state={state} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    assert!(child.wait().unwrap().success());
+    let request = cli.server.find("POST", "/api/token").unwrap();
+    assert!(request.body.contains("code=mock-code"));
+    assert!(cli.token_path().exists());
+
+    // only the playback preset is requested by default
+    let scope = url
+        .split(['?', '&'])
+        .find_map(|param| param.strip_prefix("scope="))
+        .unwrap();
+    assert!(scope.contains("user-modify-playback-state"));
+    assert!(!scope.contains("playlist-modify-public"));
+}
+
+#[test]
+fn pkce_without_client_secret() {
+    let cli = Cli::without_token(MockServer::start());
+    let output = cli
+        .command(&["auth", "--refresh-token", "obtained-elsewhere"])
+        .env_remove("SPOTIFY_CLIENT_SECRET")
+        .env("SPOTIFY_AUTH_MODE", "pkce")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // PKCE identifies the client in the body instead of with its secret
+    let request = cli.server.find("POST", "/api/token").unwrap();
+    assert!(request.body.contains("client_id=mock-client-id"));
+
+    let output = cli
+        .command(&["status", "--title"])
+        .env_remove("SPOTIFY_CLIENT_SECRET")
+        .env("SPOTIFY_AUTH_MODE", "pkce")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Title\n");
+}
+
+#[test]
+fn auth_code_requires_client_secret() {
+    let cli = Cli::new(MockServer::start());
+    let output = cli
+        .command(&["status"])
+        .env_remove("SPOTIFY_CLIENT_SECRET")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("client secret is required"));
+}
+
+#[test]
+fn status() {
+    let cli = Cli::new(MockServer::start());
+    let status = cli.stdout(&["auth", "status"]);
+    assert!(status.starts_with("Logged in as Mock User (mockuser)\n"));
+    assert!(status.contains("user-modify-playback-state user-read-currently-playing"));
+    assert!(status.ends_with("Expires at: 2100-01-01 00:00:00 UTC\n"));
+    assert!(cli.server.find("POST", "/api/token").is_none());
+}
+
+#[test]
+fn refresh() {
+    let cli = Cli::new(MockServer::start());
+    assert!(cli
+        .stdout(&["auth", "refresh"])
+        .starts_with("Refreshed the token, it expires at "));
+
+    let request = cli.server.find("POST", "/api/token").unwrap();
+    assert!(request.body.contains("refresh_token=mock-refresh-token"));
+    assert!(!fs::read_to_string(cli.token_path())
+        .unwrap()
+        .contains("2100-01-01"));
+}
+
+#[test]
+fn logout() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(cli.stdout(&["auth", "logout"]), "Logged out\n");
+    assert!(!cli.token_path().exists());
+    assert_eq!(cli.stdout(&["auth", "logout"]), "Not logged in\n");
+
+    let output = cli.run(&["auth", "status"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No usable cached token"));
+}
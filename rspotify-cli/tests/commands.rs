@@ -0,0 +1,511 @@
+//! End-to-end tests for every command, run against a local mock server.
+
+mod common;
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    process::Stdio,
+};
+
+use common::{Cli, MockServer};
+
+const TRACK_URI: &str = "spotify:track:4cOdK2wGLETKBW3PvgPWqT";
+
+#[test]
+fn status() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(cli.stdout(&["status"]), "Title - Artist \u{2661}\n");
+    assert!(cli
+        .stdout(&["status", "--id"])
+        .contains("4cOdK2wGLETKBW3PvgPWqT"));
+    assert_eq!(cli.stdout(&["status", "--uri"]), format!("{TRACK_URI}\n"));
+    assert_eq!(
+        cli.stdout(&["status", "--url"]),
+        "https://open.spotify.com/track/4cOdK2wGLETKBW3PvgPWqT\n"
+    );
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+    assert_eq!(cli.stdout(&["status", "--artist"]), "Artist\n");
+    assert_eq!(cli.stdout(&["status", "--progress"]), "1:01\n");
+    assert_eq!(cli.stdout(&["status", "--duration"]), "3:20\n");
+    assert_eq!(cli.stdout(&["status", "--is-playing"]), "true\n");
+    assert_eq!(cli.stdout(&["status", "--repeat-state"]), "Off\n");
+    assert_eq!(cli.stdout(&["status", "--is-shuffled"]), "false\n");
+    assert_eq!(cli.stdout(&["status", "--show-device"]), "Mock device\n");
+    assert_eq!(cli.stdout(&["status", "--playing-type"]), "Track\n");
+    assert_eq!(cli.stdout(&["status", "--is-liked"]), "false\n");
+
+    let json = cli.stdout(&["status", "--json"]);
+    assert!(json.contains(r#""title":"Title""#));
+    assert!(json.contains(r#""progress":61"#));
+}
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn status_format() {
+    let cli = Cli::new(MockServer::start());
+    fs::write(
+        cli.config_dir().join("config.toml"),
+        "[formats]\nbar = \"{title} ({progress}/{duration})\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        cli.stdout(&[
+            "status",
+            "--format",
+            "{artist:>8} {liked?\u{2665}:\u{2661}} {uri}"
+        ]),
+        format!("  Artist \u{2661} {TRACK_URI}\n")
+    );
+    assert_eq!(
+        cli.stdout(&["status", "--format", "bar"]),
+        "Title (1:01/3:20)\n"
+    );
+
+    let output = cli.run(&["status", "--format", "{title"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid status template"));
+}
+
+#[test]
+fn status_bar() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(
+        cli.stdout(&["status", "--bar", "waybar"]),
+        "{\"text\":\"Artist - Title\",\"tooltip\":\"Title\\nArtist\\nMock device 1:01/3:20\",\"alt\":\"playing\",\"class\":[\"playing\"]}\n"
+    );
+    assert_eq!(
+        cli.stdout(&["status", "--bar", "tmux", "--format", "{title}"]),
+        "#[fg=#1db954]\u{25b6}#[default] Title\n"
+    );
+    assert!(!cli
+        .run(&["status", "--bar", "tmux", "--title"])
+        .status
+        .success());
+
+    let cli = Cli::new(MockServer::without_device());
+    assert_eq!(cli.stdout(&["status", "--bar", "i3blocks"]), "\n");
+}
+
+#[test]
+fn status_watch() {
+    let cli = Cli::new(MockServer::start());
+    let mut child = cli
+        .command(&[
+            "status",
+            "--watch",
+            "--interval",
+            "1",
+            "--format",
+            "{title}",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(line, "Title\n");
+
+    assert!(!cli.run(&["status", "--watch", "--title"]).status.success());
+    assert!(!cli.run(&["status", "--interval", "1"]).status.success());
+}
+
+#[test]
+fn status_without_device() {
+    let cli = Cli::new(MockServer::without_device());
+    let output = cli.run(&["status"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Missing metadata"));
+}
+
+#[test]
+fn control() {
+    let cases: &[(&[&str], &str, &str)] = &[
+        (&["--play"], "PUT", "/v1/me/player/play"),
+        (&["--pause"], "PUT", "/v1/me/player/pause"),
+        (&["--toggle-play"], "PUT", "/v1/me/player/pause"),
+        (&["--like"], "PUT", "/v1/me/tracks"),
+        (&["--unlike"], "DELETE", "/v1/me/tracks"),
+        (&["--toggle-like"], "PUT", "/v1/me/tracks"),
+        (&["--previous"], "POST", "/v1/me/player/previous"),
+        (&["--next"], "POST", "/v1/me/player/next"),
+        (
+            &["--repeat", "track"],
+            "PUT",
+            "/v1/me/player/repeat?state=track",
+        ),
+        (
+            &["--cycle-repeat"],
+            "PUT",
+            "/v1/me/player/repeat?state=context",
+        ),
+        (
+            &["--volume", "30"],
+            "PUT",
+            "/v1/me/player/volume?volume_percent=30",
+        ),
+        (
+            &["--volume-up"],
+            "PUT",
+            "/v1/me/player/volume?volume_percent=60",
+        ),
+        (
+            &["--volume-down"],
+            "PUT",
+            "/v1/me/player/volume?volume_percent=40",
+        ),
+        (
+            &["--shuffle", "true"],
+            "PUT",
+            "/v1/me/player/shuffle?state=true",
+        ),
+        (
+            &["--toggle-shuffle"],
+            "PUT",
+            "/v1/me/player/shuffle?state=true",
+        ),
+        (
+            &["--seek", "10"],
+            "PUT",
+            "/v1/me/player/seek?position_ms=10000",
+        ),
+        (
+            &["--seek", "1:23"],
+            "PUT",
+            "/v1/me/player/seek?position_ms=83000",
+        ),
+        (
+            &["--seek", "+15s"],
+            "PUT",
+            "/v1/me/player/seek?position_ms=76000",
+        ),
+        (
+            &["--seek", "-30"],
+            "PUT",
+            "/v1/me/player/seek?position_ms=31000",
+        ),
+        (
+            &["--seek", "50%"],
+            "PUT",
+            "/v1/me/player/seek?position_ms=100000",
+        ),
+        (&["--replay"], "PUT", "/v1/me/player/seek?position_ms=0"),
+    ];
+
+    for (args, method, path) in cases {
+        let cli = Cli::new(MockServer::start());
+        cli.stdout(&[&["control"], *args].concat());
+        assert!(
+            cli.server.find(method, path).is_some(),
+            "{args:?} did not send {method} {path}: {:?}",
+            cli.server.requests()
+        );
+    }
+}
+
+#[test]
+fn seek_past_end() {
+    let cli = Cli::new(MockServer::start());
+    let output = cli.run(&["control", "--seek", "1:02:03"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("past the end of the item at 3:20"));
+    assert!(cli.server.find("PUT", "/v1/me/player/seek").is_none());
+}
+
+#[test]
+fn play_from() {
+    let cli = Cli::new(MockServer::start());
+    cli.stdout(&["play-from", "--uri", TRACK_URI]);
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    assert!(request.body.contains(TRACK_URI));
+
+    let cli = Cli::new(MockServer::start());
+    cli.stdout(&[
+        "play-from",
+        "--url",
+        "https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M",
+    ]);
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    assert!(request
+        .body
+        .contains("spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"));
+}
+
+#[test]
+fn play_from_list() {
+    let cli = Cli::new(MockServer::start());
+    cli.stdout(&[
+        "play-from",
+        "--uri",
+        TRACK_URI,
+        "spotify:episode:512ojhOuo1ktJprKbVcKyQ",
+    ]);
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(
+        body["uris"],
+        serde_json::json!([TRACK_URI, "spotify:episode:512ojhOuo1ktJprKbVcKyQ"])
+    );
+
+    let cli = Cli::new(MockServer::start());
+    let path = cli.config_dir().join("items.txt");
+    fs::write(
+        &path,
+        format!(
+            "# mix\n{TRACK_URI}\n\nhttps://open.spotify.com/track/2takcwOaAZWiXQijPHIx7B?si=x\n"
+        ),
+    )
+    .unwrap();
+    cli.stdout(&["play-from", "--from-file", path.to_str().unwrap()]);
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(
+        body["uris"],
+        serde_json::json!([TRACK_URI, "spotify:track:2takcwOaAZWiXQijPHIx7B"])
+    );
+
+    let cli = Cli::new(MockServer::start());
+    let output = cli
+        .command(&["play-from", "--from-file", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            let mut stdin = child.stdin.take().unwrap();
+            writeln!(stdin, "{TRACK_URI}\nnot an item")?;
+            drop(stdin);
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid item on line 2"));
+    assert!(cli.server.find("PUT", "/v1/me/player/play").is_none());
+}
+
+#[test]
+fn play_from_search() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(
+        cli.stdout(&["play-from", "--search", "title"]),
+        format!("Playing Title - Artist ({TRACK_URI})\n")
+    );
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    assert!(request.body.contains(TRACK_URI));
+
+    let cli = Cli::new(MockServer::start());
+    let output = cli
+        .command(&["play-from", "--search", "title", "--pick"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(b"1\n")?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(" 1. Title - Artist"));
+    assert!(cli.server.find("PUT", "/v1/me/player/play").is_some());
+
+    let cli = Cli::new(MockServer::start());
+    let output = cli.run(&["play-from", "--search", "nothing", "--type", "album"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No album found for nothing"));
+    assert!(cli.server.find("PUT", "/v1/me/player/play").is_none());
+}
+
+#[test]
+fn play_from_offset() {
+    let cli = Cli::new(MockServer::start());
+    cli.stdout(&[
+        "play-from",
+        "--uri",
+        "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+        "--offset",
+        "2",
+        "--position",
+        "1:30",
+    ]);
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body["context_uri"], "spotify:album:4aawyAB9vmqN3uQ7FjRGTy");
+    assert_eq!(body["offset"]["position"], 2);
+    assert_eq!(body["position_ms"], 90000);
+
+    let cli = Cli::new(MockServer::start());
+    cli.stdout(&[
+        "play-from",
+        "--url",
+        "https://open.spotify.com/user/spotify/playlist/37i9dQZF1DXcBWIGoYBM5M",
+        "--offset",
+        TRACK_URI,
+    ]);
+    let request = cli.server.find("PUT", "/v1/me/player/play").unwrap();
+    let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(
+        body["context_uri"],
+        "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"
+    );
+    assert_eq!(body["offset"]["uri"], TRACK_URI);
+
+    let cli = Cli::new(MockServer::start());
+    let output = cli.run(&["play-from", "--uri", TRACK_URI, "--offset", "1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("started at an offset"));
+}
+
+#[test]
+fn devices() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(
+        cli.stdout(&["devices", "list"]),
+        "ID               NAME             TYPE      VOLUME  ACTIVE  RESTRICTED\n\
+         mock-device      Mock device      Computer  50%     yes     no\n\
+         kitchen-speaker  Kitchen Speaker  Speaker   20%     no      no\n"
+    );
+    assert!(cli
+        .stdout(&["devices", "list", "--json"])
+        .contains(r#""id":"kitchen-speaker""#));
+
+    cli.stdout(&["devices", "transfer", "kitchen", "--play"]);
+    let request = cli.server.find("PUT", "/v1/me/player").unwrap();
+    assert_eq!(request.path, "/v1/me/player");
+    assert!(request.body.contains(r#""device_ids":["kitchen-speaker"]"#));
+    assert!(request.body.contains(r#""play":true"#));
+
+    cli.stdout(&["control", "--volume-up", "--device", "ktchn"]);
+    assert!(cli
+        .server
+        .find(
+            "PUT",
+            "/v1/me/player/volume?volume_percent=30&device_id=kitchen-speaker"
+        )
+        .is_some());
+
+    // the device may also come before the command
+    cli.stdout(&["--device", "Mock", "play-from", "--uri", TRACK_URI]);
+    assert!(cli
+        .server
+        .find("PUT", "/v1/me/player/play?device_id=mock-device")
+        .is_some());
+
+    let output = cli.run(&["control", "--next", "--device", "phone"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No device matches phone"));
+
+    // only control and play-from look the device up
+    assert_eq!(
+        cli.stdout(&["status", "--title", "--device", "phone"]),
+        "Title\n"
+    );
+}
+
+#[test]
+fn queue() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(
+        cli.stdout(&["queue", "show"]),
+        "Now playing: Title - Artist (3:20)\n1. Next - Artist (1:40)\nRemaining: 3:59\n"
+    );
+
+    cli.stdout(&[
+        "queue",
+        "add",
+        "https://open.spotify.com/track/2takcwOaAZWiXQijPHIx7B",
+    ]);
+    assert!(cli
+        .server
+        .find(
+            "POST",
+            "/v1/me/player/queue?uri=spotify:track:2takcwOaAZWiXQijPHIx7B"
+        )
+        .is_some());
+
+    let file = cli.config_dir().join("queue.txt");
+    fs::write(
+        &file,
+        "# to queue\nsome song\n\nspotify:episode:512ojhOuo1ktJprKbVcKyQ\n",
+    )
+    .unwrap();
+    cli.stdout(&["queue", "add", "--from-file", file.to_str().unwrap()]);
+    let queued: Vec<_> = cli
+        .server
+        .requests()
+        .into_iter()
+        .filter(|req| req.method == "POST" && req.path.starts_with("/v1/me/player/queue"))
+        .map(|req| req.path)
+        .collect();
+    assert_eq!(
+        queued[1..],
+        [
+            format!("/v1/me/player/queue?uri={TRACK_URI}"),
+            "/v1/me/player/queue?uri=spotify:episode:512ojhOuo1ktJprKbVcKyQ".to_owned()
+        ]
+    );
+
+    fs::write(
+        &file,
+        "spotify:track:4cOdK2wGLETKBW3PvgPWqT\nspotify:nope\n",
+    )
+    .unwrap();
+    let output = cli.run(&["queue", "add", "--from-file", file.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid item on line 2"));
+}
+
+#[test]
+fn search() {
+    let cli = Cli::new(MockServer::start());
+    assert!(cli
+        .stdout(&["search", "--track", "foo"])
+        .contains(r#""name":"Title""#));
+    let request = cli.server.find("GET", "/v1/search").unwrap();
+    assert!(request.path.contains("q=foo"));
+    assert_eq!(cli.stdout(&["search", "--artist", "foo"]), "[]\n");
+}
+
+#[test]
+fn hooks() {
+    let cli = Cli::new(MockServer::start());
+    let output = cli.run(&["hooks"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No hooks are set"));
+
+    fs::write(
+        cli.config_dir().join("config.toml"),
+        "[hooks]\non_track_change = \"notify-send \\\"$RSPOTIFY_TITLE\\\"\"\n",
+    )
+    .unwrap();
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+}
+
+#[test]
+fn refreshes_expired_token() {
+    let cli = Cli::with_token_expiry(MockServer::start(), "2000-01-01T00:00:00Z");
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+    let request = cli.server.find("POST", "/api/token").unwrap();
+    assert!(request.body.contains("refresh_token=mock-refresh-token"));
+}
+
+#[test]
+fn missing_scope() {
+    let cli = Cli::with_scopes(
+        MockServer::start(),
+        "user-read-playback-state user-read-currently-playing user-library-read",
+    );
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+
+    let output = cli.run(&["control", "--next"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("The cached token lacks the user-modify-playback-state scope"));
+    assert!(cli.server.find("POST", "/v1/me/player/next").is_none());
+}
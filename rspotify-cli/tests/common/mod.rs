@@ -0,0 +1,282 @@
+//! Offline stand-in for the Spotify Web API and accounts service.
+
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use tempfile::TempDir;
+
+/// Every scope in the `full` preset, granted to the cached token.
+const SCOPES: &str = "ugc-image-upload user-read-playback-state user-modify-playback-state \
+                      user-read-currently-playing app-remote-control streaming \
+                      playlist-read-private playlist-read-collaborative playlist-modify-private \
+                      playlist-modify-public user-follow-modify user-follow-read \
+                      user-read-playback-position user-top-read user-read-recently-played \
+                      user-library-modify user-library-read user-read-email user-read-private";
+
+/// A request received by the [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct Request {
+    /// HTTP method.
+    pub method: String,
+
+    /// Path including the query string.
+    pub path: String,
+
+    /// Request body.
+    pub body: String,
+}
+
+/// Local HTTP server serving canned Spotify responses.
+pub struct MockServer {
+    /// Base URL of the server, without a trailing slash.
+    url: String,
+
+    /// Every request received so far.
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// Start a server with an active device playing a track.
+    pub fn start() -> Self {
+        Self::with_playback(Some(fixture("current_playback.json")))
+    }
+
+    /// Start a server without an active device.
+    pub fn without_device() -> Self {
+        Self::with_playback(None)
+    }
+
+    /// Start a server that answers `GET me/player` with `playback`.
+    fn with_playback(playback: Option<String>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                handle(stream, playback.as_deref(), &recorded);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Return every request received so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Return the first request with the given method whose path starts with
+    /// `path`.
+    pub fn find(&self, method: &str, path: &str) -> Option<Request> {
+        self.requests()
+            .into_iter()
+            .find(|req| req.method == method && req.path.starts_with(path))
+    }
+}
+
+/// Read a fixture from `tests/fixtures`.
+pub fn fixture(name: &str) -> String {
+    fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap()
+}
+
+/// Serve a single request.
+fn handle(stream: TcpStream, playback: Option<&str>, requests: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).unwrap();
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap();
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    let body = String::from_utf8(body).unwrap();
+
+    let (status, response) = route(&method, &path, playback);
+    requests
+        .lock()
+        .unwrap()
+        .push(Request { method, path, body });
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: \
+         {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )
+    .unwrap();
+}
+
+/// Return the status code and body for a request.
+fn route(method: &str, path: &str, playback: Option<&str>) -> (u16, String) {
+    let (route, query) = path.split_once('?').unwrap_or((path, ""));
+    match (method, route.trim_end_matches('/')) {
+        ("GET", "/v1/me") => (200, fixture("me.json")),
+        ("GET", "/v1/me/player") => playback.map_or((204, String::new()), |p| (200, p.to_owned())),
+        ("GET", "/v1/me/player/devices") => (200, fixture("devices.json")),
+        ("GET", "/v1/me/player/queue") => (200, fixture("queue.json")),
+        ("PUT", "/v1/me/player") => (204, String::new()),
+        ("GET", "/v1/me/tracks/contains") => (200, fixture("saved_tracks_contains.json")),
+        ("PUT" | "DELETE", "/v1/me/tracks") => (200, String::new()),
+        ("PUT" | "POST", route) if route.starts_with("/v1/me/player/") => (204, String::new()),
+        ("GET", "/v1/search") => (200, search(query)),
+        ("POST", "/api/token") => (200, fixture("token.json")),
+        _ => (
+            404,
+            r#"{"error":{"status":404,"message":"Not found"}}"#.to_owned(),
+        ),
+    }
+}
+
+/// Return the canned search result for a search query.
+fn search(query: &str) -> String {
+    let kind = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("type="))
+        .unwrap_or_default();
+    if kind == "track" {
+        fixture("search_tracks.json")
+    } else {
+        format!(
+            r#"{{"{kind}s":{{"href":"","items":[],"limit":5,"next":null,"offset":1,"previous":null,"total":0}}}}"#
+        )
+    }
+}
+
+/// Kill a child process when dropped, so that failed asserts don't leak it.
+pub struct Killed(pub Child);
+
+impl Drop for Killed {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// The CLI binary pointed at a [`MockServer`], with its own config directory.
+pub struct Cli {
+    /// Config directory, used as `XDG_CONFIG_HOME`.
+    dir: TempDir,
+
+    /// Server that answers the CLI's requests.
+    pub server: MockServer,
+}
+
+impl Cli {
+    /// Set up the CLI with a cached token that is still valid.
+    pub fn new(server: MockServer) -> Self {
+        Self::with_token_expiry(server, "2100-01-01T00:00:00Z")
+    }
+
+    /// Set up the CLI without a cached token.
+    pub fn without_token(server: MockServer) -> Self {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("rspotify-cli")).unwrap();
+        Self { dir, server }
+    }
+
+    /// Set up the CLI with a cached token that expires at `expires_at`.
+    pub fn with_token_expiry(server: MockServer, expires_at: &str) -> Self {
+        Self::with_token(server, expires_at, SCOPES)
+    }
+
+    /// Set up the CLI with a valid cached token that was only granted
+    /// `scopes`, and a config requesting only `scopes`.
+    pub fn with_scopes(server: MockServer, scopes: &str) -> Self {
+        let cli = Self::with_token(server, "2100-01-01T00:00:00Z", scopes);
+        let scopes: Vec<_> = scopes.split(' ').map(|s| format!("\"{s}\"")).collect();
+        fs::write(
+            cli.config_dir().join("config.toml"),
+            format!("scopes = [{}]\n", scopes.join(", ")),
+        )
+        .unwrap();
+        cli
+    }
+
+    /// Set up the CLI with a cached token that expires at `expires_at` and was
+    /// granted `scopes`.
+    fn with_token(server: MockServer, expires_at: &str, scopes: &str) -> Self {
+        let cli = Self::without_token(server);
+        fs::write(
+            cli.token_path(),
+            format!(
+                r#"{{"access_token":"mock-access-token","expires_in":3600,"expires_at":"{expires_at}","refresh_token":"mock-refresh-token","scope":"{scopes}"}}"#
+            ),
+        )
+        .unwrap();
+        cli
+    }
+
+    /// Path of the rspotify-cli config directory.
+    pub fn config_dir(&self) -> PathBuf {
+        self.dir.path().join("rspotify-cli")
+    }
+
+    /// Path of the cached token of the default profile.
+    pub fn token_path(&self) -> PathBuf {
+        self.config_dir().join("token.json")
+    }
+
+    /// Build a command that runs the CLI with the given arguments.
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rspotify-cli"));
+        command
+            .args(args)
+            .env("XDG_CONFIG_HOME", self.dir.path())
+            .env("SPOTIFY_CLIENT_ID", "mock-client-id")
+            .env("SPOTIFY_CLIENT_SECRET", "mock-client-secret")
+            .env("SPOTIFY_API_BASE_URL", format!("{}/v1/", self.server.url))
+            .env("SPOTIFY_ACCOUNTS_BASE_URL", format!("{}/", self.server.url))
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Run the CLI with the given arguments.
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Run the CLI and return its standard output, asserting that it succeeded.
+    pub fn stdout(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "{args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
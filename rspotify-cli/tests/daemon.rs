@@ -0,0 +1,81 @@
+//! End-to-end tests for the daemon, run against a local mock server.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    fs,
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    process::{Child, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{Cli, MockServer};
+
+/// Start the daemon and wait until it listens.
+fn start_daemon(cli: &Cli) -> Child {
+    let child = cli
+        .command(&["daemon", "--cache", "60"])
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let socket = cli.config_dir().join("daemon.sock");
+    let started = Instant::now();
+    while !socket.exists() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "daemon did not start"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    child
+}
+
+/// Count the requests for the current playback.
+fn playback_requests(cli: &Cli) -> usize {
+    cli.server
+        .requests()
+        .iter()
+        .filter(|req| req.method == "GET" && req.path.split('?').next() == Some("/v1/me/player"))
+        .count()
+}
+
+#[test]
+fn serves_commands() {
+    let cli = Cli::new(MockServer::start());
+    let mut daemon = start_daemon(&cli);
+
+    let output = cli.run(&["daemon"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already listening"));
+
+    let socket = cli.config_dir().join("daemon.sock");
+    let mode = fs::metadata(&socket).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // the client no longer needs the token
+    fs::remove_file(cli.token_path()).unwrap();
+
+    // a client that sends nothing doesn't hold up the others
+    let _silent = UnixStream::connect(&socket).unwrap();
+    let started = Instant::now();
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(cli.stdout(&["status", "--artist"]), "Artist\n");
+    assert_eq!(playback_requests(&cli), 1);
+
+    cli.stdout(&["control", "--next"]);
+    assert!(cli.server.find("POST", "/v1/me/player/next").is_some());
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+    assert_eq!(playback_requests(&cli), 2);
+
+    let output = cli.run(&["play-from", "--url", "not a url"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unable to parse the invalid URL"));
+
+    daemon.kill().unwrap();
+    daemon.wait().unwrap();
+}
@@ -0,0 +1,94 @@
+//! End-to-end tests for the MPD front-end, run against a local mock server.
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    process::Stdio,
+};
+
+use common::{Cli, Killed, MockServer};
+
+/// A client connected to the server.
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    /// Connect and return the greeting.
+    fn connect(address: &str) -> (Self, String) {
+        let writer = TcpStream::connect(address).unwrap();
+        let mut client = Self {
+            reader: BufReader::new(writer.try_clone().unwrap()),
+            writer,
+        };
+        let mut greeting = String::new();
+        client.reader.read_line(&mut greeting).unwrap();
+        (client, greeting)
+    }
+
+    /// Send commands and return the response, up to its final `OK` or `ACK`.
+    fn send(&mut self, commands: &str) -> String {
+        self.writer.write_all(commands.as_bytes()).unwrap();
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            assert_ne!(self.reader.read_line(&mut line).unwrap(), 0);
+            response.push_str(&line);
+            if line == "OK\n" || line.starts_with("ACK ") {
+                return response;
+            }
+        }
+    }
+}
+
+#[test]
+fn serves_clients() {
+    let cli = Cli::new(MockServer::start());
+    let mut child = cli
+        .command(&["mpd", "--listen", "127.0.0.1:0"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let _server = Killed(child);
+    let address = line.trim().strip_prefix("Listening on ").unwrap();
+
+    let (mut client, greeting) = Client::connect(address);
+    assert!(greeting.starts_with("OK MPD "));
+
+    let status = client.send("status\n");
+    assert!(status.contains("state: play\n"), "{status}");
+    assert!(status.contains("time: 61:200\n"), "{status}");
+
+    let song = client.send("currentsong\n");
+    assert!(song.contains("Title: Title\nArtist: Artist\n"), "{song}");
+
+    assert_eq!(
+        client.send("command_list_ok_begin\nnext\nsetvol 30\ncommand_list_end\n"),
+        "list_OK\nlist_OK\nOK\n"
+    );
+    assert!(cli.server.find("POST", "/v1/me/player/next").is_some());
+    assert!(cli
+        .server
+        .find("PUT", "/v1/me/player/volume?volume_percent=30")
+        .is_some());
+
+    let results = client.send("search title \"Some title\"\n");
+    assert!(
+        results.starts_with("file: spotify:track:4cOdK2wGLETKBW3PvgPWqT\nTitle: Title\n"),
+        "{results}"
+    );
+    assert!(cli.server.find("GET", "/v1/search").is_some());
+
+    assert_eq!(
+        client.send("crossfade 5\n"),
+        "ACK [5@0] {crossfade} unknown command \"crossfade\"\n"
+    );
+    assert_eq!(client.send("ping\n"), "OK\n");
+}
@@ -0,0 +1,129 @@
+//! End-to-end tests for the MPRIS bridge, run on a private D-Bus session bus
+//! against a local mock server.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{Cli, Killed, MockServer};
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, ConnectionBuilder, Proxy, ProxyBuilder},
+    names::BusName,
+    zvariant::{ObjectPath, OwnedValue, Value},
+    CacheProperties,
+};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.rspotify";
+
+/// Start a private session bus, returning it and its address.
+///
+/// Panics if `dbus-daemon` is not installed, so that the test fails instead of
+/// passing without checking anything.
+fn start_bus() -> (Killed, String) {
+    let mut child = Command::new("dbus-daemon")
+        .args(["--session", "--nofork", "--print-address=1"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("dbus-daemon is required to test the MPRIS bridge: {err}"));
+
+    let mut address = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut address)
+        .unwrap();
+    (Killed(child), address.trim().to_owned())
+}
+
+/// Wait until the bridge owns its name on the bus.
+fn wait_for_name(connection: &Connection) {
+    let dbus = DBusProxy::new(connection).unwrap();
+    let name = BusName::try_from(BUS_NAME).unwrap();
+    let started = Instant::now();
+    while !dbus.name_has_owner(name.clone()).unwrap() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "bridge did not register"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn controls_playback() {
+    let (_bus, address) = start_bus();
+
+    let cli = Cli::new(MockServer::start());
+    let _bridge = Killed(
+        cli.command(&["mpris"])
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let connection = ConnectionBuilder::address(address.as_str())
+        .unwrap()
+        .build()
+        .unwrap();
+    wait_for_name(&connection);
+
+    // read the properties from the bridge rather than from signals
+    let player: Proxy<'_> = ProxyBuilder::new_bare(&connection)
+        .destination(BUS_NAME)
+        .unwrap()
+        .path("/org/mpris/MediaPlayer2")
+        .unwrap()
+        .interface("org.mpris.MediaPlayer2.Player")
+        .unwrap()
+        .cache_properties(CacheProperties::No)
+        .build()
+        .unwrap();
+
+    let status: String = player.get_property("PlaybackStatus").unwrap();
+    assert_eq!(status, "Playing");
+
+    let metadata: HashMap<String, OwnedValue> = player.get_property("Metadata").unwrap();
+    assert_eq!(
+        Value::from(metadata["xesam:title"].clone()),
+        Value::from("Title")
+    );
+    assert_eq!(
+        Value::from(metadata["xesam:artist"].clone()),
+        Value::from(vec!["Artist"])
+    );
+    assert_eq!(
+        Value::from(metadata["mpris:trackid"].clone()),
+        Value::from(
+            ObjectPath::try_from("/org/mpris/MediaPlayer2/rspotify/track/4cOdK2wGLETKBW3PvgPWqT")
+                .unwrap()
+        )
+    );
+    assert_eq!(
+        Value::from(metadata["mpris:length"].clone()),
+        Value::from(200_000_000_i64)
+    );
+
+    let _: () = player.call("Next", &()).unwrap();
+    assert!(cli.server.find("POST", "/v1/me/player/next").is_some());
+
+    player.set_property("LoopStatus", "Track").unwrap();
+    assert!(cli
+        .server
+        .find("PUT", "/v1/me/player/repeat?state=track")
+        .is_some());
+    let status: String = player.get_property("LoopStatus").unwrap();
+    assert_eq!(status, "Track");
+
+    player.set_property("Volume", 0.3).unwrap();
+    assert!(cli
+        .server
+        .find("PUT", "/v1/me/player/volume?volume_percent=30")
+        .is_some());
+}
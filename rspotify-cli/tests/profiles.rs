@@ -0,0 +1,84 @@
+//! Tests for named account profiles.
+
+mod common;
+
+use std::fs;
+
+use common::{Cli, MockServer};
+
+/// Set up a `work` profile holding the only cached token.
+fn cli_with_work_profile() -> Cli {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(
+        cli.stdout(&["profile", "add", "work"]),
+        "Added profile work\n"
+    );
+    fs::rename(
+        cli.token_path(),
+        cli.config_dir().join("profiles/work/token.json"),
+    )
+    .unwrap();
+    cli
+}
+
+#[test]
+fn add_list_default_remove() {
+    let cli = Cli::new(MockServer::start());
+    assert_eq!(cli.stdout(&["profile", "list"]), "* default\n");
+
+    cli.stdout(&[
+        "profile",
+        "add",
+        "work",
+        "--client-id",
+        "work-id",
+        "--client-secret",
+        "work-secret",
+    ]);
+    let config = fs::read_to_string(cli.config_dir().join("profiles/work/config.toml")).unwrap();
+    assert!(config.contains(r#"client_id = "work-id""#));
+    assert!(!cli.run(&["profile", "add", "work"]).status.success());
+    assert!(!cli.run(&["profile", "add", "../work"]).status.success());
+
+    cli.stdout(&["profile", "default", "work"]);
+    assert_eq!(cli.stdout(&["profile", "list"]), "  default\n* work\n");
+
+    cli.stdout(&["profile", "remove", "work"]);
+    assert!(!cli.config_dir().join("profiles/work").exists());
+    assert_eq!(cli.stdout(&["profile", "list"]), "* default\n");
+    assert!(!cli.run(&["profile", "remove", "default"]).status.success());
+}
+
+#[test]
+fn profile_has_its_own_token() {
+    let cli = cli_with_work_profile();
+
+    assert_eq!(
+        cli.stdout(&["--profile", "work", "status", "--title"]),
+        "Title\n"
+    );
+    assert_eq!(
+        cli.stdout(&["status", "--title", "--profile", "work"]),
+        "Title\n"
+    );
+    let output = cli
+        .command(&["status", "--title"])
+        .env("RSPOTIFY_PROFILE", "work")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // the default profile has no cached token, so it prompts and fails
+    assert!(!cli.run(&["status", "--title"]).status.success());
+
+    cli.stdout(&["profile", "default", "work"]);
+    assert_eq!(cli.stdout(&["status", "--title"]), "Title\n");
+}
+
+#[test]
+fn unknown_profile() {
+    let cli = Cli::new(MockServer::start());
+    let output = cli.run(&["--profile", "nope", "status"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown profile nope"));
+}
@@ -0,0 +1,63 @@
+//! Tests for keeping the token somewhere other than the plaintext cache.
+
+mod common;
+
+use std::fs;
+
+use common::{Cli, MockServer};
+
+#[test]
+fn migrates_to_encrypted_file() {
+    let cli = Cli::new(MockServer::start());
+    let encrypted_path = cli.config_dir().join("token.enc");
+
+    let output = cli
+        .command(&["status", "--title"])
+        .env("SPOTIFY_TOKEN_STORE", "encrypted-file")
+        .env("RSPOTIFY_TOKEN_PASSPHRASE", "correct horse")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Title\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Moved the token"));
+
+    assert!(!cli.token_path().exists());
+    let encrypted = fs::read_to_string(&encrypted_path).unwrap();
+    assert!(!encrypted.contains("mock-refresh-token"));
+
+    // the token is read from the encrypted file from now on
+    let output = cli
+        .command(&["auth", "refresh"])
+        .env("SPOTIFY_TOKEN_STORE", "encrypted-file")
+        .env("RSPOTIFY_TOKEN_PASSPHRASE", "correct horse")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_ne!(fs::read_to_string(&encrypted_path).unwrap(), encrypted);
+    // and no empty plaintext cache is left behind
+    assert!(!cli.token_path().exists());
+
+    let output = cli
+        .command(&["status", "--title"])
+        .env("SPOTIFY_TOKEN_STORE", "encrypted-file")
+        .env("RSPOTIFY_TOKEN_PASSPHRASE", "wrong")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("passphrase may be wrong"));
+}
+
+#[test]
+fn encrypted_file_requires_passphrase() {
+    let cli = Cli::new(MockServer::start());
+    let output = cli
+        .command(&["status"])
+        .env("SPOTIFY_TOKEN_STORE", "encrypted-file")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("RSPOTIFY_TOKEN_PASSPHRASE"));
+    assert!(fs::read_to_string(cli.token_path())
+        .unwrap()
+        .contains("mock-refresh-token"));
+}